@@ -0,0 +1,908 @@
+//! Version 2 of the HTTP API of the tribbler front-end, served under
+//! `/api/v2`.
+//!
+//! Unlike the legacy `/api` endpoints used by `www/trib.js`, which read a
+//! JSON document smuggled as the single key of an urlencoded form, these take
+//! path parameters, query parameters and JSON request bodies, and answer with
+//! JSON. A failed request is answered with the status code matching its
+//! [TribblerError] (see [status_of]) and a body of the form
+//! `{"error": "..."}`. Every request which changes data acts as the user
+//! signed in with the session cookie set by `POST /session`, see
+//! [crate::auth], and is refused on the behalf of any other user. The
+//! OpenAPI document describing the endpoints is generated from the request
+//! and response types of the handlers by [openapi] and served at
+//! `/api/v2/openapi.json`.
+use std::{error::Error, fmt::Display, sync::Arc};
+
+use actix_web::{
+    delete, error::InternalError, get, http::StatusCode, post, put, web, HttpRequest, HttpResponse,
+    ResponseError,
+};
+use schemars::{
+    gen::{SchemaGenerator, SchemaSettings},
+    schema::{RootSchema, Schema, SchemaObject, SingleOrVec},
+    JsonSchema,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+use tribbler::{
+    err::TribblerError,
+    trib::{DirectMessage, Profile, Server, TagCount, Trib, TribLimits},
+};
+
+use crate::{
+    auth::{self, Authed, Sessions, SESSION_COOKIE},
+    live,
+    rate_limit::RateLimiter,
+};
+
+/// The [Server] the handlers serve
+pub type Srv = Box<dyn Server + Send + Sync>;
+
+/// The result of a handler
+type ApiResult = Result<HttpResponse, ApiError>;
+
+/// An error answered with the status code matching it and an [ErrorBody]
+#[derive(Debug)]
+pub struct ApiError(Box<dyn Error + Send + Sync>);
+
+impl<E: Into<Box<dyn Error + Send + Sync>>> From<E> for ApiError {
+    fn from(e: E) -> Self {
+        ApiError(e.into())
+    }
+}
+
+impl Display for ApiError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl ResponseError for ApiError {
+    fn status_code(&self) -> StatusCode {
+        match self.0.downcast_ref::<TribblerError>() {
+            Some(e) => status_of(e),
+            None => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        HttpResponse::build(self.status_code()).json(ErrorBody {
+            error: self.to_string(),
+        })
+    }
+}
+
+/// Returns the HTTP status code a [TribblerError] is answered with.
+pub fn status_of(err: &TribblerError) -> StatusCode {
+    match err {
+        TribblerError::UserDoesNotExist(_)
+        | TribblerError::TribDoesNotExist(_, _)
+        | TribblerError::TribIdDoesNotExist(_) => StatusCode::NOT_FOUND,
+        TribblerError::UsernameTaken(_)
+        | TribblerError::UsernameConfusable(_, _)
+        | TribblerError::AlreadyFollowing(_, _)
+        | TribblerError::NotFollowing(_, _)
+        | TribblerError::AlreadyRetribbed(_, _)
+        | TribblerError::AlreadyLiked(_, _)
+        | TribblerError::RetribNotEditable(_, _)
+        | TribblerError::AlreadyBlocked(_, _)
+        | TribblerError::NotBlocked(_, _)
+        | TribblerError::AlreadyMuted(_, _)
+        | TribblerError::NotMuted(_, _)
+        | TribblerError::FollowingTooMany => StatusCode::CONFLICT,
+        TribblerError::InvalidUsername(_)
+        | TribblerError::InvalidTag(_)
+        | TribblerError::InvalidDisplayName(_)
+        | TribblerError::TribTooLong
+        | TribblerError::DmTooLong
+        | TribblerError::BioTooLong
+        | TribblerError::PasswordTooShort(_)
+        | TribblerError::WhoWhom(_) => StatusCode::BAD_REQUEST,
+        TribblerError::InvalidCredentials | TribblerError::NotSignedIn => StatusCode::UNAUTHORIZED,
+        TribblerError::Blocked(_, _)
+        | TribblerError::NotMutualFollowers(_, _)
+        | TribblerError::NotSignedInAs(_) => StatusCode::FORBIDDEN,
+        TribblerError::RateLimited(_) => StatusCode::TOO_MANY_REQUESTS,
+        TribblerError::RpcError(_) => StatusCode::BAD_GATEWAY,
+        TribblerError::NotSupported(_) => StatusCode::NOT_IMPLEMENTED,
+        TribblerError::InvalidTribEncoding(_)
+        | TribblerError::InvalidEscape(_)
+        | TribblerError::MaxedSeq
+        | TribblerError::Unknown(_) => StatusCode::INTERNAL_SERVER_ERROR,
+    }
+}
+
+/// The body of every error response
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone)]
+pub struct ErrorBody {
+    /// what went wrong
+    pub error: String,
+}
+
+/// A user to sign up
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone)]
+pub struct NewUser {
+    /// the username
+    pub user: String,
+    /// the password to sign in with
+    pub password: String,
+}
+
+/// A username and password to sign in with
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone)]
+pub struct Credentials {
+    /// the username
+    pub user: String,
+    /// the password
+    pub password: String,
+}
+
+/// The user signed in with a session
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone)]
+pub struct Session {
+    /// the username
+    pub user: String,
+}
+
+/// A trib to post
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone)]
+pub struct NewTrib {
+    /// the content of the trib
+    pub message: String,
+    /// the largest clock the client has seen
+    #[serde(default)]
+    pub clock: u64,
+    /// the id of the trib this trib replies to
+    #[serde(default)]
+    pub reply_to: Option<u64>,
+}
+
+/// The new content of a trib
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone)]
+pub struct TribEdit {
+    /// the new content of the trib
+    pub message: String,
+}
+
+/// The new content of a profile
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone)]
+pub struct ProfileEdit {
+    /// the new display name
+    pub display_name: String,
+    /// the new bio
+    pub bio: String,
+}
+
+/// A direct message to send
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone)]
+pub struct NewDm {
+    /// who to send the message to
+    pub to: String,
+    /// the content of the message
+    pub message: String,
+}
+
+/// Whether a user follows another one
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone)]
+pub struct Following {
+    /// true when the user follows the other one
+    pub following: bool,
+}
+
+/// The follower and following counts of a user
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone)]
+pub struct Counts {
+    /// the count of users following the user
+    pub followers: usize,
+    /// the count of users the user follows
+    pub following: usize,
+}
+
+/// Selects a part of a timeline. With `after`, lists the tribs after that
+/// clock; with `before` or `limit`, lists a page of the tribs before that
+/// clock; otherwise lists the most recent tribs.
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone)]
+pub struct TimelineQuery {
+    /// list the tribs with a larger clock
+    pub after: Option<u64>,
+    /// list the tribs with a smaller clock
+    pub before: Option<u64>,
+    /// the most tribs to list
+    pub limit: Option<usize>,
+}
+
+/// A full-text search
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone)]
+pub struct SearchQuery {
+    /// the words to search for
+    pub q: String,
+    /// the most tribs to list
+    pub limit: Option<usize>,
+}
+
+/// A request for follow suggestions
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone)]
+pub struct SuggestQuery {
+    /// the most users to suggest
+    pub n: Option<usize>,
+}
+
+/// takes a token for `op` for the signed in user and the client of `req`,
+/// when the app has a [RateLimiter]
+fn rate_limit(
+    limiter: &Option<web::Data<RateLimiter>>,
+    req: &HttpRequest,
+    op: &str,
+    authed: &Authed,
+) -> Result<(), ApiError> {
+    if let Some(limiter) = limiter {
+        let ip = req
+            .peer_addr()
+            .map(|a| a.ip().to_string())
+            .unwrap_or_default();
+        limiter.check_client(op, &authed.0, &ip)?;
+    }
+    Ok(())
+}
+
+/// the [TribLimits] of the app, when it has them, or else those of the server
+fn limits_of(limits: &Option<web::Data<TribLimits>>, data: &Srv) -> TribLimits {
+    match limits {
+        Some(limits) => *limits.get_ref(),
+        None => data.limits(),
+    }
+}
+
+/// answers an extractor error with a 400 and an [ErrorBody]
+fn bad_request<E: std::fmt::Debug + Display + 'static>(err: E) -> actix_web::Error {
+    let body = ErrorBody {
+        error: err.to_string(),
+    };
+    InternalError::from_response(err, HttpResponse::BadRequest().json(body)).into()
+}
+
+#[get("/openapi.json")]
+async fn openapi_doc() -> HttpResponse {
+    HttpResponse::Ok().json(openapi())
+}
+
+#[post("/users")]
+async fn sign_up(data: web::Data<Srv>, body: web::Json<NewUser>) -> ApiResult {
+    auth::sign_up(data.as_ref().as_ref(), &body.user, &body.password).await?;
+    Ok(HttpResponse::Created().finish())
+}
+
+#[post("/session")]
+async fn sign_in(
+    data: web::Data<Srv>,
+    sessions: web::Data<Sessions>,
+    body: web::Json<Credentials>,
+) -> ApiResult {
+    auth::check_password(data.as_ref().as_ref(), &body.user, &body.password).await?;
+    let token = sessions.create(&body.user);
+    Ok(HttpResponse::Ok()
+        .cookie(Sessions::cookie(&token))
+        .json(Session {
+            user: body.user.clone(),
+        }))
+}
+
+#[get("/session")]
+async fn session(authed: Authed) -> ApiResult {
+    Ok(HttpResponse::Ok().json(Session { user: authed.0 }))
+}
+
+#[delete("/session")]
+async fn sign_out(sessions: web::Data<Sessions>, req: HttpRequest) -> ApiResult {
+    if let Some(c) = req.cookie(SESSION_COOKIE) {
+        sessions.remove(c.value());
+    }
+    Ok(HttpResponse::NoContent()
+        .cookie(Sessions::removal_cookie())
+        .finish())
+}
+
+#[get("/users")]
+async fn list_users(data: web::Data<Srv>) -> ApiResult {
+    Ok(HttpResponse::Ok().json(data.list_users().await?))
+}
+
+#[get("/users/{user}/profile")]
+async fn get_profile(data: web::Data<Srv>, user: web::Path<String>) -> ApiResult {
+    Ok(HttpResponse::Ok().json(data.get_profile(&user).await?))
+}
+
+#[put("/users/{user}/profile")]
+async fn update_profile(
+    data: web::Data<Srv>,
+    authed: Authed,
+    user: web::Path<String>,
+    body: web::Json<ProfileEdit>,
+) -> ApiResult {
+    authed.check(&user)?;
+    data.update_profile(&user, &body.display_name, &body.bio)
+        .await?;
+    Ok(HttpResponse::NoContent().finish())
+}
+
+#[get("/users/{user}/tribs")]
+async fn list_tribs(
+    data: web::Data<Srv>,
+    limits: Option<web::Data<TribLimits>>,
+    user: web::Path<String>,
+    q: web::Query<TimelineQuery>,
+) -> ApiResult {
+    let tribs = match (q.after, q.before, q.limit) {
+        (Some(after), _, _) => data.tribs_since(&user, after).await?,
+        (None, None, None) => data.tribs(&user).await?,
+        (None, before, limit) => {
+            data.tribs_page(
+                &user,
+                before.unwrap_or(u64::MAX),
+                limit.unwrap_or(limits_of(&limits, &data).max_trib_fetch),
+            )
+            .await?
+        }
+    };
+    Ok(HttpResponse::Ok().json(tribs))
+}
+
+#[post("/users/{user}/tribs")]
+async fn post_trib(
+    data: web::Data<Srv>,
+    limiter: Option<web::Data<RateLimiter>>,
+    req: HttpRequest,
+    authed: Authed,
+    user: web::Path<String>,
+    body: web::Json<NewTrib>,
+) -> ApiResult {
+    authed.check(&user)?;
+    rate_limit(&limiter, &req, "post", &authed)?;
+    match body.reply_to {
+        Some(id) => data.reply(&user, &body.message, body.clock, id).await?,
+        None => data.post(&user, &body.message, body.clock).await?,
+    }
+    Ok(HttpResponse::Created().finish())
+}
+
+#[put("/users/{user}/tribs/{clock}")]
+async fn edit_trib(
+    data: web::Data<Srv>,
+    authed: Authed,
+    path: web::Path<(String, u64)>,
+    body: web::Json<TribEdit>,
+) -> ApiResult {
+    let (user, clock) = path.into_inner();
+    authed.check(&user)?;
+    data.edit_trib(&user, clock, &body.message).await?;
+    Ok(HttpResponse::NoContent().finish())
+}
+
+#[delete("/users/{user}/tribs/{clock}")]
+async fn delete_trib(
+    data: web::Data<Srv>,
+    authed: Authed,
+    path: web::Path<(String, u64)>,
+) -> ApiResult {
+    let (user, clock) = path.into_inner();
+    authed.check(&user)?;
+    data.delete_trib(&user, clock).await?;
+    Ok(HttpResponse::NoContent().finish())
+}
+
+#[get("/users/{user}/home")]
+async fn home(
+    data: web::Data<Srv>,
+    limits: Option<web::Data<TribLimits>>,
+    user: web::Path<String>,
+    q: web::Query<TimelineQuery>,
+) -> ApiResult {
+    let tribs = match (q.after, q.before, q.limit) {
+        (Some(after), _, _) => data.home_since(&user, after).await?,
+        (None, None, None) => data.home(&user).await?,
+        (None, before, limit) => {
+            data.home_page(
+                &user,
+                before.unwrap_or(u64::MAX),
+                limit.unwrap_or(limits_of(&limits, &data).max_trib_fetch),
+            )
+            .await?
+        }
+    };
+    Ok(HttpResponse::Ok().json(tribs))
+}
+
+#[get("/users/{user}/home/live")]
+async fn home_live(data: web::Data<Srv>, user: web::Path<String>) -> ApiResult {
+    let rx = data.subscribe().await?;
+    // checks that the user exists
+    data.following_count(&user).await?;
+    Ok(live::home_response(rx, user.into_inner()))
+}
+
+#[get("/users/{user}/following")]
+async fn list_following(data: web::Data<Srv>, user: web::Path<String>) -> ApiResult {
+    Ok(HttpResponse::Ok().json(data.following(&user).await?))
+}
+
+#[get("/users/{user}/following/{whom}")]
+async fn is_following(data: web::Data<Srv>, path: web::Path<(String, String)>) -> ApiResult {
+    let (who, whom) = path.into_inner();
+    let following = data.is_following(&who, &whom).await?;
+    Ok(HttpResponse::Ok().json(Following { following }))
+}
+
+#[put("/users/{user}/following/{whom}")]
+async fn follow(
+    data: web::Data<Srv>,
+    limiter: Option<web::Data<RateLimiter>>,
+    req: HttpRequest,
+    authed: Authed,
+    path: web::Path<(String, String)>,
+) -> ApiResult {
+    let (who, whom) = path.into_inner();
+    authed.check(&who)?;
+    rate_limit(&limiter, &req, "follow", &authed)?;
+    data.follow(&who, &whom).await?;
+    Ok(HttpResponse::NoContent().finish())
+}
+
+#[delete("/users/{user}/following/{whom}")]
+async fn unfollow(
+    data: web::Data<Srv>,
+    limiter: Option<web::Data<RateLimiter>>,
+    req: HttpRequest,
+    authed: Authed,
+    path: web::Path<(String, String)>,
+) -> ApiResult {
+    let (who, whom) = path.into_inner();
+    authed.check(&who)?;
+    rate_limit(&limiter, &req, "follow", &authed)?;
+    data.unfollow(&who, &whom).await?;
+    Ok(HttpResponse::NoContent().finish())
+}
+
+#[get("/users/{user}/followers")]
+async fn followers(data: web::Data<Srv>, user: web::Path<String>) -> ApiResult {
+    Ok(HttpResponse::Ok().json(data.followers(&user).await?))
+}
+
+#[get("/users/{user}/counts")]
+async fn counts(data: web::Data<Srv>, user: web::Path<String>) -> ApiResult {
+    Ok(HttpResponse::Ok().json(Counts {
+        followers: data.follower_count(&user).await?,
+        following: data.following_count(&user).await?,
+    }))
+}
+
+#[put("/users/{user}/blocking/{whom}")]
+async fn block(
+    data: web::Data<Srv>,
+    authed: Authed,
+    path: web::Path<(String, String)>,
+) -> ApiResult {
+    let (who, whom) = path.into_inner();
+    authed.check(&who)?;
+    data.block(&who, &whom).await?;
+    Ok(HttpResponse::NoContent().finish())
+}
+
+#[delete("/users/{user}/blocking/{whom}")]
+async fn unblock(
+    data: web::Data<Srv>,
+    authed: Authed,
+    path: web::Path<(String, String)>,
+) -> ApiResult {
+    let (who, whom) = path.into_inner();
+    authed.check(&who)?;
+    data.unblock(&who, &whom).await?;
+    Ok(HttpResponse::NoContent().finish())
+}
+
+#[put("/users/{user}/muting/{whom}")]
+async fn mute(
+    data: web::Data<Srv>,
+    authed: Authed,
+    path: web::Path<(String, String)>,
+) -> ApiResult {
+    let (who, whom) = path.into_inner();
+    authed.check(&who)?;
+    data.mute(&who, &whom).await?;
+    Ok(HttpResponse::NoContent().finish())
+}
+
+#[delete("/users/{user}/muting/{whom}")]
+async fn unmute(
+    data: web::Data<Srv>,
+    authed: Authed,
+    path: web::Path<(String, String)>,
+) -> ApiResult {
+    let (who, whom) = path.into_inner();
+    authed.check(&who)?;
+    data.unmute(&who, &whom).await?;
+    Ok(HttpResponse::NoContent().finish())
+}
+
+#[get("/users/{user}/mentions")]
+async fn mentions(data: web::Data<Srv>, user: web::Path<String>) -> ApiResult {
+    Ok(HttpResponse::Ok().json(data.mentions(&user).await?))
+}
+
+#[get("/users/{user}/suggestions")]
+async fn suggestions(
+    data: web::Data<Srv>,
+    limits: Option<web::Data<TribLimits>>,
+    user: web::Path<String>,
+    q: web::Query<SuggestQuery>,
+) -> ApiResult {
+    let n = q.n.unwrap_or(limits_of(&limits, &data).max_suggestions);
+    Ok(HttpResponse::Ok().json(data.suggest_follows(&user, n).await?))
+}
+
+#[get("/users/{user}/inbox")]
+async fn inbox(data: web::Data<Srv>, authed: Authed, user: web::Path<String>) -> ApiResult {
+    authed.check(&user)?;
+    Ok(HttpResponse::Ok().json(data.inbox(&user).await?))
+}
+
+#[post("/users/{user}/messages")]
+async fn send_dm(
+    data: web::Data<Srv>,
+    limiter: Option<web::Data<RateLimiter>>,
+    req: HttpRequest,
+    authed: Authed,
+    user: web::Path<String>,
+    body: web::Json<NewDm>,
+) -> ApiResult {
+    authed.check(&user)?;
+    rate_limit(&limiter, &req, "dm", &authed)?;
+    data.send_dm(&user, &body.to, &body.message).await?;
+    Ok(HttpResponse::Created().finish())
+}
+
+#[get("/tribs/{id}/thread")]
+async fn thread(data: web::Data<Srv>, id: web::Path<u64>) -> ApiResult {
+    Ok(HttpResponse::Ok().json(data.thread(*id).await?))
+}
+
+#[post("/tribs/{id}/retribs")]
+async fn retrib(
+    data: web::Data<Srv>,
+    limiter: Option<web::Data<RateLimiter>>,
+    req: HttpRequest,
+    authed: Authed,
+    id: web::Path<u64>,
+) -> ApiResult {
+    rate_limit(&limiter, &req, "post", &authed)?;
+    data.retrib(&authed.0, *id).await?;
+    Ok(HttpResponse::Created().finish())
+}
+
+#[post("/tribs/{id}/likes")]
+async fn like(
+    data: web::Data<Srv>,
+    limiter: Option<web::Data<RateLimiter>>,
+    req: HttpRequest,
+    authed: Authed,
+    id: web::Path<u64>,
+) -> ApiResult {
+    rate_limit(&limiter, &req, "like", &authed)?;
+    data.like(&authed.0, *id).await?;
+    Ok(HttpResponse::Created().finish())
+}
+
+#[get("/search")]
+async fn search(
+    data: web::Data<Srv>,
+    limits: Option<web::Data<TribLimits>>,
+    q: web::Query<SearchQuery>,
+) -> ApiResult {
+    let limit = q.limit.unwrap_or(limits_of(&limits, &data).max_trib_fetch);
+    Ok(HttpResponse::Ok().json(data.search(&q.q, limit).await?))
+}
+
+#[get("/tags/trending")]
+async fn trending_tags(data: web::Data<Srv>) -> ApiResult {
+    Ok(HttpResponse::Ok().json(data.trending_tags().await?))
+}
+
+#[get("/tags/{tag}")]
+async fn search_tag(data: web::Data<Srv>, tag: web::Path<String>) -> ApiResult {
+    Ok(HttpResponse::Ok().json(data.search_tag(&tag).await?))
+}
+
+/// generates the schema of a request or response type
+type SchemaFn = fn(&mut SchemaGenerator) -> Schema;
+
+/// generates the schema of a query parameter type
+type QueryFn = fn(&mut SchemaGenerator) -> RootSchema;
+
+fn schema<T: JsonSchema>(gen: &mut SchemaGenerator) -> Schema {
+    gen.subschema_for::<T>()
+}
+
+fn query<T: JsonSchema>(gen: &mut SchemaGenerator) -> RootSchema {
+    gen.clone().into_root_schema_for::<T>()
+}
+
+/// Describes an endpoint in the OpenAPI document
+pub struct Route {
+    /// the HTTP method, in lowercase
+    pub method: &'static str,
+    /// the path under `/api/v2`, with `{}` around path parameters
+    pub path: &'static str,
+    /// what the endpoint does
+    pub summary: &'static str,
+    /// the status code of a successful response
+    pub status: u16,
+    params: Option<SchemaFn>,
+    query: Option<QueryFn>,
+    body: Option<SchemaFn>,
+    response: Option<SchemaFn>,
+}
+
+const fn route(
+    method: &'static str,
+    path: &'static str,
+    summary: &'static str,
+    status: u16,
+) -> Route {
+    Route {
+        method,
+        path,
+        summary,
+        status,
+        params: None,
+        query: None,
+        body: None,
+        response: None,
+    }
+}
+
+impl Route {
+    /// sets the type of the path parameters, the `T` of the `web::Path<T>`
+    /// of the handler, a tuple when there is more than one
+    const fn params(mut self, f: SchemaFn) -> Route {
+        self.params = Some(f);
+        self
+    }
+
+    const fn query(mut self, f: QueryFn) -> Route {
+        self.query = Some(f);
+        self
+    }
+
+    const fn body(mut self, f: SchemaFn) -> Route {
+        self.body = Some(f);
+        self
+    }
+
+    const fn response(mut self, f: SchemaFn) -> Route {
+        self.response = Some(f);
+        self
+    }
+}
+
+type Tribs = Vec<Arc<Trib>>;
+
+/// Declares [configure], registering `handler` for every entry, and
+/// [ROUTES], describing them, from the same table so that no endpoint is
+/// served without being documented
+macro_rules! routes {
+    ($($handler:ident => $route:expr,)*) => {
+        /// Registers the endpoints under `/api/v2`.
+        pub fn configure(cfg: &mut web::ServiceConfig) {
+            cfg.service(
+                web::scope("/api/v2")
+                    .app_data(web::JsonConfig::default().error_handler(|e, _| bad_request(e)))
+                    .app_data(web::QueryConfig::default().error_handler(|e, _| bad_request(e)))
+                    .app_data(web::PathConfig::default().error_handler(|e, _| bad_request(e)))
+                    $(.service($handler))*,
+            );
+        }
+
+        /// The endpoints served by [configure], as described in the OpenAPI
+        /// document
+        pub const ROUTES: &[Route] = &[$($route,)*];
+    };
+}
+
+routes! {
+    openapi_doc => route("get", "/openapi.json", "This OpenAPI document", 200),
+    sign_up => route("post", "/users", "Sign up a user", 201).body(schema::<NewUser>),
+    list_users => route("get", "/users", "List users", 200).response(schema::<Vec<String>>),
+    sign_in => route("post", "/session", "Sign in, setting the session cookie", 200)
+        .body(schema::<Credentials>)
+        .response(schema::<Session>),
+    session => route("get", "/session", "Get the signed in user", 200)
+        .response(schema::<Session>),
+    sign_out => route("delete", "/session", "Sign out", 204),
+    get_profile => route("get", "/users/{user}/profile", "Get a profile", 200)
+        .params(schema::<String>)
+        .response(schema::<Profile>),
+    update_profile => route("put", "/users/{user}/profile", "Update a profile", 204)
+        .params(schema::<String>)
+        .body(schema::<ProfileEdit>),
+    list_tribs => route("get", "/users/{user}/tribs", "List the tribs of a user", 200)
+        .params(schema::<String>)
+        .query(query::<TimelineQuery>)
+        .response(schema::<Tribs>),
+    post_trib => route("post", "/users/{user}/tribs", "Post a trib as the signed in user", 201)
+        .params(schema::<String>)
+        .body(schema::<NewTrib>),
+    edit_trib => route("put", "/users/{user}/tribs/{clock}", "Edit a trib", 204)
+        .params(schema::<(String, u64)>)
+        .body(schema::<TribEdit>),
+    delete_trib => route("delete", "/users/{user}/tribs/{clock}", "Delete a trib", 204)
+        .params(schema::<(String, u64)>),
+    home => route("get", "/users/{user}/home", "List the home timeline of a user", 200)
+        .params(schema::<String>)
+        .query(query::<TimelineQuery>)
+        .response(schema::<Tribs>),
+    home_live => route(
+        "get",
+        "/users/{user}/home/live",
+        "Stream the tribs posted to the home timeline as server-sent events",
+        200,
+    )
+    .params(schema::<String>),
+    list_following => route("get", "/users/{user}/following", "List followed users", 200)
+        .params(schema::<String>)
+        .response(schema::<Vec<String>>),
+    is_following => route("get", "/users/{user}/following/{whom}", "Check following", 200)
+        .params(schema::<(String, String)>)
+        .response(schema::<Following>),
+    follow => route("put", "/users/{user}/following/{whom}", "Follow a user", 204)
+        .params(schema::<(String, String)>),
+    unfollow => route("delete", "/users/{user}/following/{whom}", "Unfollow a user", 204)
+        .params(schema::<(String, String)>),
+    followers => route("get", "/users/{user}/followers", "List followers", 200)
+        .params(schema::<String>)
+        .response(schema::<Vec<String>>),
+    counts => route("get", "/users/{user}/counts", "Count follows", 200)
+        .params(schema::<String>)
+        .response(schema::<Counts>),
+    block => route("put", "/users/{user}/blocking/{whom}", "Block a user", 204)
+        .params(schema::<(String, String)>),
+    unblock => route("delete", "/users/{user}/blocking/{whom}", "Unblock a user", 204)
+        .params(schema::<(String, String)>),
+    mute => route("put", "/users/{user}/muting/{whom}", "Mute a user", 204)
+        .params(schema::<(String, String)>),
+    unmute => route("delete", "/users/{user}/muting/{whom}", "Unmute a user", 204)
+        .params(schema::<(String, String)>),
+    mentions => route("get", "/users/{user}/mentions", "List the tribs mentioning a user", 200)
+        .params(schema::<String>)
+        .response(schema::<Tribs>),
+    suggestions => route("get", "/users/{user}/suggestions", "Suggest users to follow", 200)
+        .params(schema::<String>)
+        .query(query::<SuggestQuery>)
+        .response(schema::<Vec<String>>),
+    inbox => route(
+        "get",
+        "/users/{user}/inbox",
+        "List the direct messages received by the signed in user",
+        200,
+    )
+    .params(schema::<String>)
+    .response(schema::<Vec<Arc<DirectMessage>>>),
+    send_dm => route(
+        "post",
+        "/users/{user}/messages",
+        "Send a direct message as the signed in user",
+        201,
+    )
+    .params(schema::<String>)
+    .body(schema::<NewDm>),
+    thread => route("get", "/tribs/{id}/thread", "List the thread of a trib", 200)
+        .params(schema::<u64>)
+        .response(schema::<Tribs>),
+    retrib => route("post", "/tribs/{id}/retribs", "Retrib a trib as the signed in user", 201)
+        .params(schema::<u64>),
+    like => route("post", "/tribs/{id}/likes", "Like a trib as the signed in user", 201)
+        .params(schema::<u64>),
+    search => route("get", "/search", "Search tribs", 200)
+        .query(query::<SearchQuery>)
+        .response(schema::<Tribs>),
+    trending_tags => route("get", "/tags/trending", "List trending tags", 200)
+        .response(schema::<Vec<TagCount>>),
+    search_tag => route("get", "/tags/{tag}", "List the tribs with a tag", 200)
+        .params(schema::<String>)
+        .response(schema::<Tribs>),
+}
+
+/// the names of the path parameters of a route
+fn path_params(path: &str) -> Vec<&str> {
+    path.split('/')
+        .filter_map(|s| s.strip_prefix('{').and_then(|s| s.strip_suffix('}')))
+        .collect()
+}
+
+/// the schemas of the path parameters of a route, one for each item when
+/// they are a tuple
+fn param_schemas(s: Schema) -> Vec<Schema> {
+    if let Schema::Object(SchemaObject {
+        array: Some(array), ..
+    }) = &s
+    {
+        if let Some(SingleOrVec::Vec(items)) = &array.items {
+            return items.clone();
+        }
+    }
+    vec![s]
+}
+
+/// describes an operation of the OpenAPI document
+fn operation(r: &Route, gen: &mut SchemaGenerator) -> Value {
+    let mut params = vec![];
+    let types = match r.params {
+        Some(f) => param_schemas(f(gen)),
+        None => vec![],
+    };
+    for (name, s) in path_params(r.path).into_iter().zip(types) {
+        params.push(json!({
+            "name": name,
+            "in": "path",
+            "required": true,
+            "schema": s,
+        }));
+    }
+    if let Some(f) = r.query {
+        let root = f(gen);
+        if let Some(obj) = root.schema.object {
+            for (name, s) in obj.properties.iter() {
+                params.push(json!({
+                    "name": name,
+                    "in": "query",
+                    "required": obj.required.contains(name),
+                    "schema": s,
+                }));
+            }
+        }
+    }
+    let mut op = json!({
+        "summary": r.summary,
+        "parameters": params,
+        "responses": {
+            "default": {
+                "description": "The error which occurred",
+                "content": {
+                    "application/json": { "schema": schema::<ErrorBody>(gen) },
+                },
+            },
+        },
+    });
+    let mut ok = json!({ "description": "Success" });
+    if let Some(f) = r.response {
+        ok["content"] = json!({ "application/json": { "schema": f(gen) } });
+    }
+    op["responses"][r.status.to_string()] = ok;
+    if let Some(f) = r.body {
+        op["requestBody"] = json!({
+            "required": true,
+            "content": { "application/json": { "schema": f(gen) } },
+        });
+    }
+    op
+}
+
+/// Generates the OpenAPI document of the endpoints in [ROUTES].
+pub fn openapi() -> Value {
+    let mut gen = SchemaSettings::openapi3().into_generator();
+    let mut paths = Map::new();
+    for r in ROUTES.iter() {
+        let op = operation(r, &mut gen);
+        let item = paths.entry(r.path.to_string()).or_insert_with(|| json!({}));
+        item[r.method] = op;
+    }
+    json!({
+        "openapi": "3.0.3",
+        "info": {
+            "title": "Tribbler",
+            "version": "2",
+        },
+        "servers": [{ "url": "/api/v2" }],
+        "paths": paths,
+        "components": { "schemas": gen.take_definitions() },
+    })
+}
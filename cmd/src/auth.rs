@@ -0,0 +1,258 @@
+//! Password sign in for the tribbler front-end.
+//!
+//! Passwords are hashed with Argon2 into PHC strings, which are kept with
+//! [Server::set_credential]. Signing in creates a session, whose random token
+//! is handed to the browser in the [SESSION_COOKIE] cookie. The
+//! [bind_session] middleware looks up the session of every request and binds
+//! its user to the request, where handlers get it with the [Authed]
+//! extractor.
+use std::{
+    collections::HashMap,
+    future::{ready, Ready},
+    sync::RwLock,
+    time::{Duration, Instant},
+};
+
+use actix_web::{
+    body::MessageBody,
+    cookie::{self, Cookie, SameSite},
+    dev::{Payload, ServiceRequest, ServiceResponse},
+    middleware::Next,
+    web, FromRequest, HttpMessage, HttpRequest,
+};
+use argon2::{
+    password_hash::{rand_core::OsRng, PasswordHash, PasswordHasher, PasswordVerifier, SaltString},
+    Argon2,
+};
+use rand::Rng;
+use tribbler::{
+    err::{TribResult, TribblerError},
+    trib::Server,
+};
+
+use crate::api_v2::ApiError;
+
+/// Minimum length of a password
+pub const MIN_PASSWORD_LEN: usize = 8;
+
+/// The name of the cookie holding the session token
+pub const SESSION_COOKIE: &str = "trib_session";
+
+/// How long a session lasts after signing in
+pub const SESSION_TTL: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+
+/// Hashes a password into a PHC string, with a random salt.
+pub fn hash_password(password: &str) -> TribResult<String> {
+    if password.chars().count() < MIN_PASSWORD_LEN {
+        return Err(Box::new(TribblerError::PasswordTooShort(MIN_PASSWORD_LEN)));
+    }
+    let salt = SaltString::generate(&mut OsRng);
+    match Argon2::default().hash_password(password.as_bytes(), &salt) {
+        Ok(h) => Ok(h.to_string()),
+        Err(e) => Err(Box::new(TribblerError::Unknown(e.to_string()))),
+    }
+}
+
+/// Checks a password against a hash made by [hash_password].
+pub fn verify_password(password: &str, hash: &str) -> bool {
+    match PasswordHash::new(hash) {
+        Ok(h) => Argon2::default()
+            .verify_password(password.as_bytes(), &h)
+            .is_ok(),
+        Err(_) => false,
+    }
+}
+
+/// runs `f` on the blocking thread pool, as hashing a password takes tens
+/// of milliseconds on purpose and would stall the async workers
+async fn off_executor<R: Send + 'static>(f: impl FnOnce() -> R + Send + 'static) -> TribResult<R> {
+    match web::block(f).await {
+        Ok(r) => Ok(r),
+        Err(e) => Err(Box::new(TribblerError::Unknown(e.to_string()))),
+    }
+}
+
+/// Signs up `user` with `password`.
+/// Returns error when the password is too short, and all the errors of
+/// [Server::sign_up_with_credential].
+pub async fn sign_up(
+    server: &(dyn Server + Send + Sync),
+    user: &str,
+    password: &str,
+) -> TribResult<()> {
+    let password = password.to_string();
+    let hash = off_executor(move || hash_password(&password)).await??;
+    server.sign_up_with_credential(user, &hash).await
+}
+
+/// Checks the password of `user`. Returns [TribblerError::InvalidCredentials]
+/// when the user does not exist, has no password, or the password is wrong.
+pub async fn check_password(
+    server: &(dyn Server + Send + Sync),
+    user: &str,
+    password: &str,
+) -> TribResult<()> {
+    if let Ok(Some(hash)) = server.credential(user).await {
+        let password = password.to_string();
+        if off_executor(move || verify_password(&password, &hash)).await? {
+            return Ok(());
+        }
+    }
+    Err(Box::new(TribblerError::InvalidCredentials))
+}
+
+/// The signed in sessions, by token
+#[derive(Default)]
+pub struct Sessions {
+    sessions: RwLock<HashMap<String, (String, Instant)>>,
+}
+
+impl Sessions {
+    /// Creates a [Sessions] with no session
+    pub fn new() -> Sessions {
+        Sessions::default()
+    }
+
+    /// Creates a session for `user` and returns its token.
+    pub fn create(&self, user: &str) -> String {
+        let token: String = rand::thread_rng()
+            .gen::<[u8; 32]>()
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect();
+        let mut sessions = self.sessions.write().unwrap();
+        let now = Instant::now();
+        sessions.retain(|_, (_, expiry)| *expiry > now);
+        sessions.insert(token.clone(), (user.to_string(), now + SESSION_TTL));
+        token
+    }
+
+    /// Returns the user of the session with `token`, unless it expired.
+    pub fn user(&self, token: &str) -> Option<String> {
+        let sessions = self.sessions.read().unwrap();
+        match sessions.get(token) {
+            Some((user, expiry)) if *expiry > Instant::now() => Some(user.clone()),
+            _ => None,
+        }
+    }
+
+    /// Ends the session with `token`.
+    pub fn remove(&self, token: &str) {
+        self.sessions.write().unwrap().remove(token);
+    }
+
+    /// Returns the cookie handing out the session `token`.
+    pub fn cookie(token: &str) -> Cookie<'static> {
+        Cookie::build(SESSION_COOKIE, token.to_string())
+            .path("/")
+            .http_only(true)
+            .same_site(SameSite::Strict)
+            .max_age(cookie::time::Duration::seconds(SESSION_TTL.as_secs() as i64))
+            .finish()
+    }
+
+    /// Returns the cookie removing the session cookie from the browser.
+    pub fn removal_cookie() -> Cookie<'static> {
+        let mut c = Sessions::cookie("");
+        c.make_removal();
+        c
+    }
+}
+
+/// The user of the signed in session of a request. Extracting it fails with
+/// [TribblerError::NotSignedIn] when there is none.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Authed(pub String);
+
+impl Authed {
+    /// Checks that the session may act as `who`.
+    pub fn check(&self, who: &str) -> TribResult<()> {
+        match self.0 == who {
+            true => Ok(()),
+            false => Err(Box::new(TribblerError::NotSignedInAs(who.to_string()))),
+        }
+    }
+}
+
+impl FromRequest for Authed {
+    type Error = ApiError;
+    type Future = Ready<Result<Authed, ApiError>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        ready(match req.extensions().get::<Authed>() {
+            Some(a) => Ok(a.clone()),
+            None => Err(TribblerError::NotSignedIn.into()),
+        })
+    }
+}
+
+/// Binds the user of the session named by the [SESSION_COOKIE] cookie to the
+/// request as an [Authed], when the session exists in the app's [Sessions].
+pub async fn bind_session(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+    let user = match (
+        req.app_data::<web::Data<Sessions>>(),
+        req.cookie(SESSION_COOKIE),
+    ) {
+        (Some(sessions), Some(c)) => sessions.user(c.value()),
+        _ => None,
+    };
+    if let Some(user) = user {
+        req.extensions_mut().insert(Authed(user));
+    }
+    next.call(req).await
+}
+
+#[cfg(test)]
+mod test {
+    use tribbler::{err::TribResult, ref_impl::RefServer, trib::Server};
+
+    use super::{check_password, hash_password, sign_up, verify_password, Sessions};
+
+    #[test]
+    fn hashes() -> TribResult<()> {
+        let h1 = hash_password("correct horse")?;
+        let h2 = hash_password("correct horse")?;
+        assert_ne!(h1, h2);
+        assert!(!h1.contains("correct horse"));
+        assert!(verify_password("correct horse", &h1));
+        assert!(verify_password("correct horse", &h2));
+        assert!(!verify_password("wrong horse", &h1));
+        assert!(!verify_password("correct horse", "not a hash"));
+        assert!(hash_password("short").is_err());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn passwords() -> TribResult<()> {
+        let server = RefServer::new();
+        sign_up(&server, "alice", "alice's password").await?;
+        server.sign_up("bob").await?;
+        check_password(&server, "alice", "alice's password").await?;
+        assert!(check_password(&server, "alice", "bob's password")
+            .await
+            .is_err());
+        // a user signed up without a password can never sign in
+        assert!(check_password(&server, "bob", "").await.is_err());
+        assert!(check_password(&server, "carol", "password").await.is_err());
+        assert!(sign_up(&server, "carol", "short").await.is_err());
+        assert!(server.list_users().await?.len() == 2);
+        Ok(())
+    }
+
+    #[test]
+    fn sessions() {
+        let sessions = Sessions::new();
+        let t1 = sessions.create("alice");
+        let t2 = sessions.create("alice");
+        assert_ne!(t1, t2);
+        assert_eq!(64, t1.len());
+        assert_eq!(Some("alice".to_string()), sessions.user(&t1));
+        sessions.remove(&t1);
+        assert_eq!(None, sessions.user(&t1));
+        assert_eq!(Some("alice".to_string()), sessions.user(&t2));
+        assert_eq!(None, sessions.user("nope"));
+    }
+}
@@ -59,7 +59,11 @@ fn main() -> TribResult<()> {
         p += 1;
     }
 
-    let cfg = config::Config { backs, keepers };
+    let cfg = config::Config {
+        backs,
+        keepers,
+        ..Default::default()
+    };
 
     cfg.write(Some(&args.file))
 }
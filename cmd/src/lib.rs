@@ -1,2 +1,6 @@
+pub mod api_v2;
+pub mod auth;
 pub mod bins_run;
 pub mod client_cmds;
+pub mod live;
+pub mod rate_limit;
@@ -0,0 +1,97 @@
+//! Streams the tribs posted to a home timeline as server-sent events, fed by
+//! the [Posted] notifications of [tribbler::trib::Server::subscribe].
+//!
+//! Every trib on the home timeline is sent as an unnamed event whose data is
+//! the JSON of the trib. When the subscriber falls behind and misses some
+//! tribs, a `lagged` event carrying the count of missed notifications is sent
+//! instead, so the client can fetch the timeline again. A comment is sent
+//! every [HEARTBEAT] to keep idle connections open.
+use std::{convert::Infallible, sync::Arc, time::Duration};
+
+use actix_web::{http::header, web::Bytes, HttpResponse};
+use tokio::time::{interval_at, Instant};
+use tokio_stream::{
+    wrappers::{errors::BroadcastStreamRecvError, BroadcastStream, IntervalStream},
+    Stream, StreamExt,
+};
+use tribbler::trib::{PostReceiver, Posted, Trib};
+
+/// How often an idle event stream sends a comment
+pub const HEARTBEAT: Duration = Duration::from_secs(15);
+
+/// Formats a trib as an event.
+pub fn trib_event(trib: &Trib) -> Bytes {
+    Bytes::from(format!(
+        "data: {}\n\n",
+        serde_json::to_string(trib).unwrap()
+    ))
+}
+
+/// Returns the events of the tribs posted to the home timeline of `user`
+/// from the notifications received by `rx`.
+pub fn home_events(
+    rx: PostReceiver,
+    user: String,
+) -> impl Stream<Item = Result<Bytes, Infallible>> {
+    let posts = BroadcastStream::new(rx).filter_map(move |r: Result<Arc<Posted>, _>| match r {
+        Ok(p) if p.homes.contains(&user) => Some(trib_event(&p.trib)),
+        Ok(_) => None,
+        Err(BroadcastStreamRecvError::Lagged(n)) => {
+            Some(Bytes::from(format!("event: lagged\ndata: {}\n\n", n)))
+        }
+    });
+    let pings = IntervalStream::new(interval_at(Instant::now() + HEARTBEAT, HEARTBEAT))
+        .map(|_| Bytes::from_static(b": ping\n\n"));
+    posts.merge(pings).map(Ok)
+}
+
+/// Answers with the event stream of the home timeline of `user`.
+pub fn home_response(rx: PostReceiver, user: String) -> HttpResponse {
+    HttpResponse::Ok()
+        .content_type("text/event-stream")
+        .insert_header((header::CACHE_CONTROL, "no-cache"))
+        .streaming(home_events(rx, user))
+}
+
+#[cfg(test)]
+mod test {
+    use tokio_stream::StreamExt;
+    use tribbler::{err::TribResult, ref_impl::RefServer, trib::Server};
+
+    use super::home_events;
+
+    #[tokio::test]
+    async fn home_events_of_user() -> TribResult<()> {
+        let server = RefServer::new();
+        server.sign_up("alice").await?;
+        server.sign_up("bob").await?;
+        server.sign_up("carol").await?;
+        server.follow("alice", "bob").await?;
+        let events = home_events(server.subscribe().await?, "alice".to_string());
+        tokio::pin!(events);
+        server.post("carol", "not for alice", 0).await?;
+        server.post("bob", "hello", 0).await?;
+        let ev = events.next().await.unwrap()?;
+        let ev = std::str::from_utf8(&ev)?;
+        assert!(ev.starts_with("data: {"));
+        assert!(ev.ends_with("}\n\n"));
+        assert!(ev.contains("\"message\":\"hello\""));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn lagged_events() -> TribResult<()> {
+        let server = RefServer::new();
+        server.sign_up("alice").await?;
+        let events = home_events(server.subscribe().await?, "alice".to_string());
+        tokio::pin!(events);
+        for i in 0..2000 {
+            server.post("alice", &format!("m{}", i), 0).await?;
+        }
+        let ev = events.next().await.unwrap()?;
+        assert!(ev.starts_with(b"event: lagged\n"));
+        let ev = events.next().await.unwrap()?;
+        assert!(ev.starts_with(b"data: "));
+        Ok(())
+    }
+}
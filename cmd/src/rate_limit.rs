@@ -0,0 +1,265 @@
+//! Token-bucket rate limiting for the tribbler front-end.
+//!
+//! Every operation has a [Limit]. Each client key, such as the signed in user
+//! or the IP address of a request, gets its own bucket per operation, which
+//! holds up to `burst` tokens and is refilled at `per_minute` tokens a minute.
+//! A request takes one token from the bucket of each of its keys, and is
+//! refused when any of them is empty. Buckets which have refilled are no
+//! different from new ones, so they are dropped from time to time.
+use std::{
+    collections::HashMap,
+    str::FromStr,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use tribbler::err::{TribResult, TribblerError};
+
+/// The rate allowed for one operation
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Limit {
+    /// the count of requests which can be made at once
+    pub burst: u32,
+    /// the count of requests allowed a minute in the long run
+    pub per_minute: u32,
+}
+
+/// A [Limit] for a named operation, parsed from `op=burst/per_minute`
+///
+/// ```rust
+/// use cmd::rate_limit::{Limit, OpLimit};
+/// let l: OpLimit = "post=5/30".parse().unwrap();
+/// assert_eq!("post", l.op);
+/// assert_eq!(Limit { burst: 5, per_minute: 30 }, l.limit);
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct OpLimit {
+    /// the name of the operation
+    pub op: String,
+    /// the rate allowed for the operation
+    pub limit: Limit,
+}
+
+impl FromStr for OpLimit {
+    type Err = TribblerError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || {
+            TribblerError::Unknown(format!(
+                "{} is not a valid rate limit, expected op=burst/per_minute",
+                s
+            ))
+        };
+        let (op, rate) = s.split_once('=').ok_or_else(invalid)?;
+        if op.is_empty() {
+            return Err(invalid());
+        }
+        let (burst, per_minute) = rate.split_once('/').ok_or_else(invalid)?;
+        Ok(OpLimit {
+            op: op.to_string(),
+            limit: Limit {
+                burst: burst.parse().map_err(|_| invalid())?,
+                per_minute: per_minute.parse().map_err(|_| invalid())?,
+            },
+        })
+    }
+}
+
+/// the limits used for operations which are not configured otherwise
+pub fn default_limits() -> HashMap<String, Limit> {
+    HashMap::from([
+        (
+            "post".to_string(),
+            Limit {
+                burst: 10,
+                per_minute: 60,
+            },
+        ),
+        (
+            "follow".to_string(),
+            Limit {
+                burst: 20,
+                per_minute: 60,
+            },
+        ),
+        (
+            "dm".to_string(),
+            Limit {
+                burst: 10,
+                per_minute: 30,
+            },
+        ),
+        (
+            "like".to_string(),
+            Limit {
+                burst: 30,
+                per_minute: 120,
+            },
+        ),
+    ])
+}
+
+/// How often idle buckets are looked for
+pub const SWEEP_INTERVAL: Duration = Duration::from_secs(60);
+
+#[derive(Debug)]
+struct Bucket {
+    tokens: f64,
+    last: Instant,
+}
+
+impl Bucket {
+    /// refills the bucket for the time passed since it was last used
+    fn refill(&mut self, limit: &Limit, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.last).as_secs_f64();
+        self.tokens =
+            (self.tokens + elapsed * limit.per_minute as f64 / 60.0).min(limit.burst as f64);
+        self.last = now;
+    }
+
+    /// whether the bucket has refilled by `now`, so that it can be dropped
+    fn is_full(&self, limit: &Limit, now: Instant) -> bool {
+        let elapsed = now.saturating_duration_since(self.last).as_secs_f64();
+        limit.per_minute > 0
+            && self.tokens + elapsed * limit.per_minute as f64 / 60.0 >= limit.burst as f64
+    }
+}
+
+/// A set of token buckets, one for each operation and client key
+#[derive(Debug)]
+pub struct RateLimiter {
+    limits: HashMap<String, Limit>,
+    buckets: Mutex<Buckets>,
+}
+
+#[derive(Debug)]
+struct Buckets {
+    buckets: HashMap<(String, String), Bucket>,
+    last_sweep: Instant,
+}
+
+impl RateLimiter {
+    /// Creates a [RateLimiter] enforcing `limits`. Operations without a
+    /// limit are not limited.
+    pub fn new(limits: HashMap<String, Limit>) -> RateLimiter {
+        RateLimiter {
+            limits,
+            buckets: Mutex::new(Buckets {
+                buckets: HashMap::new(),
+                last_sweep: Instant::now(),
+            }),
+        }
+    }
+
+    /// Takes a token for `op` from the bucket of every key. Returns
+    /// [TribblerError::RateLimited] without taking any token when one of
+    /// the buckets is empty.
+    pub fn check(&self, op: &str, keys: &[&str]) -> TribResult<()> {
+        self.check_at(op, keys, Instant::now())
+    }
+
+    /// Takes a token for `op` from the buckets of user `who` and of the
+    /// client address `ip`
+    pub fn check_client(&self, op: &str, who: &str, ip: &str) -> TribResult<()> {
+        self.check(op, &[&format!("user:{}", who), &format!("ip:{}", ip)])
+    }
+
+    /// Same as [RateLimiter::check], at the given time
+    pub fn check_at(&self, op: &str, keys: &[&str], now: Instant) -> TribResult<()> {
+        let limit = match self.limits.get(op) {
+            Some(l) => l,
+            None => return Ok(()),
+        };
+        let mut state = self.buckets.lock().unwrap();
+        if now.saturating_duration_since(state.last_sweep) >= SWEEP_INTERVAL {
+            let limits = &self.limits;
+            state.buckets.retain(|(op, _), b| match limits.get(op) {
+                Some(l) => !b.is_full(l, now),
+                None => false,
+            });
+            state.last_sweep = now;
+        }
+        let buckets = &mut state.buckets;
+        for key in keys.iter() {
+            let b = buckets
+                .entry((op.to_string(), key.to_string()))
+                .or_insert(Bucket {
+                    tokens: limit.burst as f64,
+                    last: now,
+                });
+            b.refill(limit, now);
+            if b.tokens < 1.0 {
+                return Err(Box::new(TribblerError::RateLimited(op.to_string())));
+            }
+        }
+        for key in keys.iter() {
+            if let Some(b) = buckets.get_mut(&(op.to_string(), key.to_string())) {
+                b.tokens -= 1.0;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        collections::HashMap,
+        time::{Duration, Instant},
+    };
+
+    use super::{Limit, RateLimiter, SWEEP_INTERVAL};
+
+    #[test]
+    fn buckets() {
+        let limiter = RateLimiter::new(HashMap::from([(
+            "post".to_string(),
+            Limit {
+                burst: 2,
+                per_minute: 6,
+            },
+        )]));
+        let t = Instant::now();
+        assert!(limiter.check_at("post", &["alice", "ip"], t).is_ok());
+        assert!(limiter.check_at("post", &["alice", "ip"], t).is_ok());
+        assert!(limiter.check_at("post", &["alice", "ip"], t).is_err());
+        // the shared ip bucket is empty too
+        assert!(limiter.check_at("post", &["bob", "ip"], t).is_err());
+        assert!(limiter.check_at("post", &["bob", "other"], t).is_ok());
+        assert!(limiter.check_at("follow", &["alice", "ip"], t).is_ok());
+
+        let later = t + Duration::from_secs(10);
+        assert!(limiter.check_at("post", &["alice", "ip"], later).is_ok());
+        assert!(limiter.check_at("post", &["alice", "ip"], later).is_err());
+    }
+
+    #[test]
+    fn sweeps() {
+        let limiter = RateLimiter::new(HashMap::from([(
+            "post".to_string(),
+            Limit {
+                burst: 2,
+                per_minute: 1,
+            },
+        )]));
+        let t = Instant::now();
+        assert!(limiter.check_at("post", &["alice"], t).is_ok());
+        assert!(limiter.check_at("post", &["bob"], t).is_ok());
+        assert!(limiter.check_at("post", &["bob"], t).is_ok());
+        assert_eq!(2, limiter.buckets.lock().unwrap().buckets.len());
+
+        // alice has refilled after a minute, bob needs two
+        let later = t + SWEEP_INTERVAL;
+        assert!(limiter.check_at("post", &["carol"], later).is_ok());
+        let buckets = &limiter.buckets.lock().unwrap().buckets;
+        assert_eq!(2, buckets.len());
+        assert!(!buckets.contains_key(&("post".to_string(), "alice".to_string())));
+    }
+
+    #[test]
+    fn parse() {
+        assert!("post=1".parse::<super::OpLimit>().is_err());
+        assert!("post=a/1".parse::<super::OpLimit>().is_err());
+        assert!("=1/1".parse::<super::OpLimit>().is_err());
+    }
+}
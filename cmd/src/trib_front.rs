@@ -1,17 +1,22 @@
-use std::str::FromStr;
+use std::{path::Path, str::FromStr};
 
 use actix_files::Files;
-use actix_web::{web, App, HttpServer};
+use actix_web::{middleware, web, App, HttpServer};
 use clap::Parser;
+use cmd::auth::{self, Sessions};
+use cmd::rate_limit::{default_limits, OpLimit, RateLimiter};
 use lab::lab2;
 use log::{info, warn, LevelFilter};
 use tribbler::config::Config;
 use tribbler::config::DEFAULT_CONFIG_LOCATION;
 use tribbler::err::{TribResult, TribblerError};
 use tribbler::ref_impl::RefServer;
-use tribbler::trib::Server;
+use tribbler::trib::{Server, TribLimits};
 
-type Srv = Box<dyn Server + Send + Sync>;
+use cmd::api_v2::Srv;
+
+/// the password of the users of the pre-populated test server
+const DEMO_PASSWORD: &str = "tribbler";
 
 #[derive(Debug, Clone)]
 enum ServerType {
@@ -46,6 +51,8 @@ struct Cfg {
     #[clap(short, long, default_value = "ref")]
     server_type: ServerType,
 
+    /// the config file, whose limits also apply to the ref server when it
+    /// exists
     #[clap(short, long, default_value = DEFAULT_CONFIG_LOCATION)]
     config: String,
 
@@ -56,6 +63,17 @@ struct Cfg {
     /// the host port to bind
     #[clap(long, default_value = "8080")]
     port: u16,
+
+    /// the rate allowed for an operation (post, follow, dm or like) per
+    /// signed in user and per client IP, as op=burst/per_minute. May be
+    /// repeated.
+    #[clap(long = "rate-limit", multiple_occurrences = true)]
+    rate_limits: Vec<OpLimit>,
+
+    /// the file the ref server keeps its data in, which is loaded on start.
+    /// Without it, the ref server starts empty and keeps its data in memory.
+    #[clap(long)]
+    data_file: Option<String>,
 }
 
 #[tokio::main]
@@ -66,33 +84,98 @@ async fn main() -> TribResult<()> {
         .default_format()
         .filter_level(args.log_level)
         .init();
-    let srv_impl: Srv = match args.server_type {
-        ServerType::Ref => Box::new(RefServer::new()),
+    let (srv_impl, trib_limits): (Srv, TribLimits) = match args.server_type {
+        ServerType::Ref => {
+            let trib_limits = match Path::new(&args.config).exists() {
+                true => Config::read(Some(&args.config))?.limits,
+                false => TribLimits::default(),
+            };
+            let srv = match &args.data_file {
+                Some(path) => {
+                    let srv = RefServer::open_with_limits(path, trib_limits).await?;
+                    info!("Loaded ref server data from {}", path);
+                    srv
+                }
+                None => RefServer::with_limits(trib_limits),
+            };
+            (Box::new(srv), trib_limits)
+        }
         ServerType::Lab => {
+            if args.data_file.is_some() {
+                warn!("--data-file is ignored with the lab server type");
+            }
             let cfg = Config::read(Some(&args.config))?;
             let bc = lab2::new_bin_client(cfg.backs).await?;
-            lab2::new_front(bc).await?
+            (lab2::new_front(bc, cfg.limits).await?, cfg.limits)
         }
     };
     let server: web::Data<Srv> = web::Data::new(srv_impl);
-    match populate(&server) {
-        Ok(_) => info!("Pre-populated test-server successfully"),
-        Err(e) => warn!("Failed to pre-populate test server: {}", e),
+    let trib_limits = web::Data::new(trib_limits);
+    let mut limits = default_limits();
+    for l in args.rate_limits.iter() {
+        limits.insert(l.op.clone(), l.limit);
+    }
+    let limiter = web::Data::new(RateLimiter::new(limits));
+    let sessions = web::Data::new(Sessions::new());
+    // a server loaded from a data file already has its users
+    if server.list_users().await.map_or(true, |u| u.is_empty()) {
+        match populate(&server).await {
+            Ok(_) => info!(
+                "Pre-populated test-server successfully, with password \"{}\"",
+                DEMO_PASSWORD
+            ),
+            Err(e) => warn!("Failed to pre-populate test server: {}", e),
+        }
     }
     let srv = HttpServer::new(move || {
         App::new()
             .app_data(server.clone())
+            .app_data(trib_limits.clone())
+            .app_data(limiter.clone())
+            .app_data(sessions.clone())
+            .wrap(middleware::from_fn(auth::bind_session))
+            .configure(cmd::api_v2::configure)
             .service(
                 web::scope("/api")
                     .service(api::add_user)
+                    .service(api::signup)
+                    .service(api::login)
+                    .service(api::logout)
+                    .service(api::whoami)
                     .service(api::list_users)
+                    .service(api::profile)
+                    .service(api::update_profile)
                     .service(api::list_tribs)
                     .service(api::list_home)
+                    .service(api::list_tribs_page)
+                    .service(api::list_tribs_since)
+                    .service(api::list_home_page)
+                    .service(api::list_home_since)
                     .service(api::is_following)
                     .service(api::follow)
                     .service(api::unfollow)
+                    .service(api::block)
+                    .service(api::unblock)
+                    .service(api::mute)
+                    .service(api::unmute)
                     .service(api::following)
-                    .service(api::post),
+                    .service(api::followers)
+                    .service(api::follow_counts)
+                    .service(api::suggest)
+                    .service(api::send_dm)
+                    .service(api::inbox)
+                    .service(api::post)
+                    .service(api::reply)
+                    .service(api::retrib)
+                    .service(api::like)
+                    .service(api::mentions)
+                    .service(api::thread)
+                    .service(api::search)
+                    .service(api::search_tag)
+                    .service(api::trending_tags)
+                    .service(api::delete_trib)
+                    .service(api::edit_trib)
+                    .service(api::live_home),
             )
             .service(Files::new("/", "./www").index_file("index.html"))
     })
@@ -108,17 +191,17 @@ async fn main() -> TribResult<()> {
     Ok(())
 }
 
-fn populate(server: &web::Data<Box<dyn Server + Send + Sync>>) -> TribResult<()> {
-    server.sign_up("h8liu")?;
-    server.sign_up("fenglu")?;
-    server.sign_up("rkapoor")?;
-    server.post("h8liu", "Hello, world.", 0)?;
-    server.post("h8liu", "Just tribble it.", 0)?;
-    server.post("fenglu", "Double tribble.", 0)?;
-    server.post("rkapoor", "Triple tribble.", 0)?;
-    server.follow("fenglu", "h8liu")?;
-    server.follow("fenglu", "rkapoor")?;
-    server.follow("rkapoor", "h8liu")?;
+async fn populate(server: &web::Data<Box<dyn Server + Send + Sync>>) -> TribResult<()> {
+    for user in ["h8liu", "fenglu", "rkapoor"] {
+        auth::sign_up(server.as_ref().as_ref(), user, DEMO_PASSWORD).await?;
+    }
+    server.post("h8liu", "Hello, world.", 0).await?;
+    server.post("h8liu", "Just tribble it.", 0).await?;
+    server.post("fenglu", "Double tribble.", 0).await?;
+    server.post("rkapoor", "Triple tribble.", 0).await?;
+    server.follow("fenglu", "h8liu").await?;
+    server.follow("fenglu", "rkapoor").await?;
+    server.follow("rkapoor", "h8liu").await?;
     Ok(())
 }
 
@@ -127,8 +210,19 @@ mod api {
     use std::error::Error;
     use std::{collections::HashMap, sync::Arc};
 
-    use actix_web::{get, http::header::ContentType, post, web, HttpResponse, Responder};
+    use actix_web::{
+        get,
+        http::{header::ContentType, StatusCode},
+        post, web, HttpRequest, HttpResponse, Responder,
+    };
+    use cmd::{
+        api_v2::status_of,
+        auth::{self, Authed, Sessions, SESSION_COOKIE},
+        live,
+        rate_limit::RateLimiter,
+    };
     use log::debug;
+    use tribbler::err::{TribResult, TribblerError};
 
     use crate::Srv;
 
@@ -138,11 +232,63 @@ mod api {
             .body(serde_json::to_string(d).unwrap())
     }
 
+    /// the status an error is answered with: the one [status_of] gives a
+    /// [TribblerError], 400 for a request which does not parse and 500 for
+    /// anything else
+    fn error_status(err: &(dyn Error + 'static)) -> StatusCode {
+        match err.downcast_ref::<TribblerError>() {
+            Some(e) => status_of(e),
+            None if err.is::<serde_json::Error>() => StatusCode::BAD_REQUEST,
+            None => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
     fn err_response(err: Box<dyn Error>) -> HttpResponse {
-        HttpResponse::InternalServerError().body(err.to_string())
+        HttpResponse::build(error_status(err.as_ref())).body(err.to_string())
     }
 
-    /// signs up a new user
+    /// answers an operation which failed with a false [Bool] carrying the
+    /// error, under the status of [error_status]
+    fn refused(err: Box<dyn Error>) -> HttpResponse {
+        HttpResponse::build(error_status(err.as_ref()))
+            .content_type(ContentType::plaintext())
+            .body(
+                serde_json::to_string(&Bool {
+                    v: false,
+                    err: err.to_string(),
+                })
+                .unwrap(),
+            )
+    }
+
+    /// returns the single key of a form, which carries the request
+    fn form_key(form: &HashMap<String, String>) -> Option<&str> {
+        form.keys().next().map(String::as_str)
+    }
+
+    /// answers a form with no key, see [form_key]
+    fn empty_form() -> HttpResponse {
+        HttpResponse::BadRequest().body("empty request")
+    }
+
+    /// takes a token for `op` from the buckets of the signed in user and of
+    /// the client IP
+    fn rate_limit(
+        limiter: &RateLimiter,
+        req: &HttpRequest,
+        op: &str,
+        authed: &Authed,
+    ) -> TribResult<()> {
+        let ip = req
+            .peer_addr()
+            .map(|a| a.ip().to_string())
+            .unwrap_or_default();
+        limiter.check_client(op, &authed.0, &ip)
+    }
+
+    /// signs up a new user without a password, for scripts which only read.
+    /// Such a user can never sign in, so nothing can be done as them; the web
+    /// UI signs up with a password through `signup`.
     #[post("/add-user")]
     pub async fn add_user(
         data: web::Data<Srv>,
@@ -150,19 +296,112 @@ mod api {
     ) -> impl Responder {
         let s = form.0;
         debug!("add-user: {:?}", &s);
-        match data.sign_up(s.keys().next().unwrap()) {
-            Ok(_) => build_resp(&UserList {
-                users: data.list_users().unwrap(),
+        let key = match form_key(&s) {
+            Some(k) => k,
+            None => return empty_form(),
+        };
+        if let Err(e) = data.sign_up(key).await {
+            return err_response(e);
+        }
+        match data.list_users().await {
+            Ok(users) => build_resp(&UserList {
+                users,
                 err: "".to_string(),
             }),
             Err(e) => err_response(e),
         }
     }
 
+    /// answers that `user` signed in, handing out a new session cookie
+    fn signed_in(sessions: &Sessions, user: &str) -> HttpResponse {
+        let token = sessions.create(user);
+        HttpResponse::Ok()
+            .content_type(ContentType::plaintext())
+            .cookie(Sessions::cookie(&token))
+            .body(
+                serde_json::to_string(&Bool {
+                    v: true,
+                    err: "".to_string(),
+                })
+                .unwrap(),
+            )
+    }
+
+    /// signs up a new user with a password, and signs them in
+    #[post("signup")]
+    pub async fn signup(
+        data: web::Data<Srv>,
+        sessions: web::Data<Sessions>,
+        form: web::Form<HashMap<String, String>>,
+    ) -> impl Responder {
+        let s = form.0;
+        let raw = match form_key(&s) {
+            Some(k) => k,
+            None => return empty_form(),
+        };
+        match serde_json::from_str::<Credentials>(raw) {
+            Ok(c) => match auth::sign_up(data.as_ref().as_ref(), &c.user, &c.password).await {
+                Ok(_) => signed_in(&sessions, &c.user),
+                Err(e) => refused(e),
+            },
+            Err(e) => err_response(Box::new(e)),
+        }
+    }
+
+    /// signs in a user with their password
+    #[post("login")]
+    pub async fn login(
+        data: web::Data<Srv>,
+        sessions: web::Data<Sessions>,
+        form: web::Form<HashMap<String, String>>,
+    ) -> impl Responder {
+        let s = form.0;
+        let raw = match form_key(&s) {
+            Some(k) => k,
+            None => return empty_form(),
+        };
+        match serde_json::from_str::<Credentials>(raw) {
+            Ok(c) => match auth::check_password(data.as_ref().as_ref(), &c.user, &c.password).await
+            {
+                Ok(_) => signed_in(&sessions, &c.user),
+                Err(e) => refused(e),
+            },
+            Err(e) => err_response(Box::new(e)),
+        }
+    }
+
+    /// ends the session of the request
+    #[post("logout")]
+    pub async fn logout(sessions: web::Data<Sessions>, req: HttpRequest) -> impl Responder {
+        if let Some(c) = req.cookie(SESSION_COOKIE) {
+            sessions.remove(c.value());
+        }
+        HttpResponse::Ok()
+            .content_type(ContentType::plaintext())
+            .cookie(Sessions::removal_cookie())
+            .body(
+                serde_json::to_string(&Bool {
+                    v: true,
+                    err: "".to_string(),
+                })
+                .unwrap(),
+            )
+    }
+
+    /// gets the user signed in with the session of the request, or an empty
+    /// name when there is none
+    #[get("whoami")]
+    pub async fn whoami(authed: Option<Authed>) -> impl Responder {
+        build_resp(&SignedIn {
+            user: authed.map(|a| a.0).unwrap_or_default(),
+            err: "".to_string(),
+        })
+    }
+
     /// lists all the users registered
     #[get("list-users")]
     pub async fn list_users(data: web::Data<Srv>) -> impl Responder {
-        match data.list_users() {
+        match data.list_users().await {
             Ok(v) => {
                 let ul = UserList {
                     users: v,
@@ -174,6 +413,58 @@ mod api {
         }
     }
 
+    /// gets the profile of a particular user
+    #[post("profile")]
+    pub async fn profile(
+        data: web::Data<Srv>,
+        form: web::Form<HashMap<String, String>>,
+    ) -> impl Responder {
+        let s = form.0;
+        let key = match form_key(&s) {
+            Some(k) => k,
+            None => return empty_form(),
+        };
+        match data.get_profile(key).await {
+            Ok(p) => {
+                let up = UserProfile {
+                    profile: p,
+                    err: "".to_string(),
+                };
+                build_resp(&up)
+            }
+            Err(e) => err_response(e),
+        }
+    }
+
+    /// updates the profile of a particular user
+    #[post("update-profile")]
+    pub async fn update_profile(
+        data: web::Data<Srv>,
+        authed: Authed,
+        form: web::Form<HashMap<String, String>>,
+    ) -> impl Responder {
+        let s = form.0;
+        let raw = match form_key(&s) {
+            Some(k) => k,
+            None => return empty_form(),
+        };
+        match serde_json::from_str::<ProfileEdit>(raw) {
+            Ok(p) => {
+                if let Err(e) = authed.check(&p.user) {
+                    return err_response(e);
+                }
+                match data.update_profile(&p.user, &p.display_name, &p.bio).await {
+                    Ok(_) => build_resp(&Bool {
+                        v: true,
+                        err: "".to_string(),
+                    }),
+                    Err(e) => refused(e),
+                }
+            }
+            Err(e) => err_response(Box::new(e)),
+        }
+    }
+
     /// lists all the tribs for a particular user
     #[post("list-tribs")]
     pub async fn list_tribs(
@@ -181,7 +472,11 @@ mod api {
         form: web::Form<HashMap<String, String>>,
     ) -> impl Responder {
         let s = form.0;
-        match data.tribs(s.keys().next().unwrap()) {
+        let key = match form_key(&s) {
+            Some(k) => k,
+            None => return empty_form(),
+        };
+        match data.tribs(key).await {
             Ok(v) => {
                 let ul = TribList {
                     tribs: v,
@@ -200,7 +495,11 @@ mod api {
         form: web::Form<HashMap<String, String>>,
     ) -> impl Responder {
         let s = form.0;
-        match data.home(s.keys().next().unwrap()) {
+        let key = match form_key(&s) {
+            Some(k) => k,
+            None => return empty_form(),
+        };
+        match data.home(key).await {
             Ok(v) => {
                 let ul = TribList {
                     tribs: v,
@@ -212,6 +511,100 @@ mod api {
         }
     }
 
+    /// lists a page of the tribs a particular user posted before a clock
+    #[post("list-tribs-page")]
+    pub async fn list_tribs_page(
+        data: web::Data<Srv>,
+        limits: web::Data<TribLimits>,
+        form: web::Form<HashMap<String, String>>,
+    ) -> impl Responder {
+        let s = form.0;
+        let raw = match form_key(&s) {
+            Some(k) => k,
+            None => return empty_form(),
+        };
+        match serde_json::from_str::<Page>(raw) {
+            Ok(p) => match data.tribs_page(&p.user, p.before(), p.limit(&limits)).await {
+                Ok(v) => build_resp(&TribList {
+                    tribs: v,
+                    err: "".to_string(),
+                }),
+                Err(e) => err_response(e),
+            },
+            Err(e) => err_response(Box::new(e)),
+        }
+    }
+
+    /// lists the tribs a particular user posted after a clock
+    #[post("list-tribs-since")]
+    pub async fn list_tribs_since(
+        data: web::Data<Srv>,
+        form: web::Form<HashMap<String, String>>,
+    ) -> impl Responder {
+        let s = form.0;
+        let raw = match form_key(&s) {
+            Some(k) => k,
+            None => return empty_form(),
+        };
+        match serde_json::from_str::<Since>(raw) {
+            Ok(p) => match data.tribs_since(&p.user, p.after).await {
+                Ok(v) => build_resp(&TribList {
+                    tribs: v,
+                    err: "".to_string(),
+                }),
+                Err(e) => err_response(e),
+            },
+            Err(e) => err_response(Box::new(e)),
+        }
+    }
+
+    /// lists a page of the home timeline of a particular user before a clock
+    #[post("list-home-page")]
+    pub async fn list_home_page(
+        data: web::Data<Srv>,
+        limits: web::Data<TribLimits>,
+        form: web::Form<HashMap<String, String>>,
+    ) -> impl Responder {
+        let s = form.0;
+        let raw = match form_key(&s) {
+            Some(k) => k,
+            None => return empty_form(),
+        };
+        match serde_json::from_str::<Page>(raw) {
+            Ok(p) => match data.home_page(&p.user, p.before(), p.limit(&limits)).await {
+                Ok(v) => build_resp(&TribList {
+                    tribs: v,
+                    err: "".to_string(),
+                }),
+                Err(e) => err_response(e),
+            },
+            Err(e) => err_response(Box::new(e)),
+        }
+    }
+
+    /// lists the home timeline of a particular user after a clock
+    #[post("list-home-since")]
+    pub async fn list_home_since(
+        data: web::Data<Srv>,
+        form: web::Form<HashMap<String, String>>,
+    ) -> impl Responder {
+        let s = form.0;
+        let raw = match form_key(&s) {
+            Some(k) => k,
+            None => return empty_form(),
+        };
+        match serde_json::from_str::<Since>(raw) {
+            Ok(p) => match data.home_since(&p.user, p.after).await {
+                Ok(v) => build_resp(&TribList {
+                    tribs: v,
+                    err: "".to_string(),
+                }),
+                Err(e) => err_response(e),
+            },
+            Err(e) => err_response(Box::new(e)),
+        }
+    }
+
     /// determines whether a user is following another user or not
     #[post("is-following")]
     pub async fn is_following(
@@ -219,9 +612,15 @@ mod api {
         form: web::Form<HashMap<String, String>>,
     ) -> impl Responder {
         let s = form.0;
-        let raw = s.keys().next().unwrap();
-        let t = serde_json::from_str::<WhoWhom>(raw).unwrap();
-        match data.is_following(&t.who, &t.whom) {
+        let raw = match form_key(&s) {
+            Some(k) => k,
+            None => return empty_form(),
+        };
+        let t = match serde_json::from_str::<WhoWhom>(raw) {
+            Ok(t) => t,
+            Err(e) => return err_response(Box::new(e)),
+        };
+        match data.is_following(&t.who, &t.whom).await {
             Ok(v) => {
                 let ul = Bool {
                     v,
@@ -237,12 +636,27 @@ mod api {
     #[post("follow")]
     pub async fn follow(
         data: web::Data<Srv>,
+        limiter: web::Data<RateLimiter>,
+        req: HttpRequest,
+        authed: Authed,
         form: web::Form<HashMap<String, String>>,
     ) -> impl Responder {
         let s = form.0;
-        let raw = s.keys().next().unwrap();
-        let t = serde_json::from_str::<WhoWhom>(raw).unwrap();
-        match data.follow(&t.who, &t.whom) {
+        let raw = match form_key(&s) {
+            Some(k) => k,
+            None => return empty_form(),
+        };
+        let t = match serde_json::from_str::<WhoWhom>(raw) {
+            Ok(t) => t,
+            Err(e) => return err_response(Box::new(e)),
+        };
+        if let Err(e) = authed.check(&t.who) {
+            return err_response(e);
+        }
+        if let Err(e) = rate_limit(&limiter, &req, "follow", &authed) {
+            return err_response(e);
+        }
+        match data.follow(&t.who, &t.whom).await {
             Ok(_) => {
                 let ul = Bool {
                     v: true,
@@ -258,12 +672,27 @@ mod api {
     #[post("unfollow")]
     pub async fn unfollow(
         data: web::Data<Srv>,
+        limiter: web::Data<RateLimiter>,
+        req: HttpRequest,
+        authed: Authed,
         form: web::Form<HashMap<String, String>>,
     ) -> impl Responder {
         let s = form.0;
-        let raw = s.keys().next().unwrap();
-        let t = serde_json::from_str::<WhoWhom>(raw).unwrap();
-        match data.unfollow(&t.who, &t.whom) {
+        let raw = match form_key(&s) {
+            Some(k) => k,
+            None => return empty_form(),
+        };
+        let t = match serde_json::from_str::<WhoWhom>(raw) {
+            Ok(t) => t,
+            Err(e) => return err_response(Box::new(e)),
+        };
+        if let Err(e) = authed.check(&t.who) {
+            return err_response(e);
+        }
+        if let Err(e) = rate_limit(&limiter, &req, "follow", &authed) {
+            return err_response(e);
+        }
+        match data.unfollow(&t.who, &t.whom).await {
             Ok(_) => {
                 let ul = Bool {
                     v: true,
@@ -275,6 +704,122 @@ mod api {
         }
     }
 
+    /// makes a user block another user
+    #[post("block")]
+    pub async fn block(
+        data: web::Data<Srv>,
+        authed: Authed,
+        form: web::Form<HashMap<String, String>>,
+    ) -> impl Responder {
+        let s = form.0;
+        let raw = match form_key(&s) {
+            Some(k) => k,
+            None => return empty_form(),
+        };
+        match serde_json::from_str::<WhoWhom>(raw) {
+            Ok(t) => {
+                if let Err(e) = authed.check(&t.who) {
+                    return err_response(e);
+                }
+                match data.block(&t.who, &t.whom).await {
+                    Ok(_) => build_resp(&Bool {
+                        v: true,
+                        err: "".to_string(),
+                    }),
+                    Err(e) => refused(e),
+                }
+            }
+            Err(e) => err_response(Box::new(e)),
+        }
+    }
+
+    /// makes a user unblock another user
+    #[post("unblock")]
+    pub async fn unblock(
+        data: web::Data<Srv>,
+        authed: Authed,
+        form: web::Form<HashMap<String, String>>,
+    ) -> impl Responder {
+        let s = form.0;
+        let raw = match form_key(&s) {
+            Some(k) => k,
+            None => return empty_form(),
+        };
+        match serde_json::from_str::<WhoWhom>(raw) {
+            Ok(t) => {
+                if let Err(e) = authed.check(&t.who) {
+                    return err_response(e);
+                }
+                match data.unblock(&t.who, &t.whom).await {
+                    Ok(_) => build_resp(&Bool {
+                        v: true,
+                        err: "".to_string(),
+                    }),
+                    Err(e) => refused(e),
+                }
+            }
+            Err(e) => err_response(Box::new(e)),
+        }
+    }
+
+    /// makes a user mute another user
+    #[post("mute")]
+    pub async fn mute(
+        data: web::Data<Srv>,
+        authed: Authed,
+        form: web::Form<HashMap<String, String>>,
+    ) -> impl Responder {
+        let s = form.0;
+        let raw = match form_key(&s) {
+            Some(k) => k,
+            None => return empty_form(),
+        };
+        match serde_json::from_str::<WhoWhom>(raw) {
+            Ok(t) => {
+                if let Err(e) = authed.check(&t.who) {
+                    return err_response(e);
+                }
+                match data.mute(&t.who, &t.whom).await {
+                    Ok(_) => build_resp(&Bool {
+                        v: true,
+                        err: "".to_string(),
+                    }),
+                    Err(e) => refused(e),
+                }
+            }
+            Err(e) => err_response(Box::new(e)),
+        }
+    }
+
+    /// makes a user unmute another user
+    #[post("unmute")]
+    pub async fn unmute(
+        data: web::Data<Srv>,
+        authed: Authed,
+        form: web::Form<HashMap<String, String>>,
+    ) -> impl Responder {
+        let s = form.0;
+        let raw = match form_key(&s) {
+            Some(k) => k,
+            None => return empty_form(),
+        };
+        match serde_json::from_str::<WhoWhom>(raw) {
+            Ok(t) => {
+                if let Err(e) = authed.check(&t.who) {
+                    return err_response(e);
+                }
+                match data.unmute(&t.who, &t.whom).await {
+                    Ok(_) => build_resp(&Bool {
+                        v: true,
+                        err: "".to_string(),
+                    }),
+                    Err(e) => refused(e),
+                }
+            }
+            Err(e) => err_response(Box::new(e)),
+        }
+    }
+
     /// gets the list of users following a particular user
     #[post("following")]
     pub async fn following(
@@ -282,7 +827,11 @@ mod api {
         form: web::Form<HashMap<String, String>>,
     ) -> impl Responder {
         let s = form.0;
-        match data.following(s.keys().next().unwrap()) {
+        let key = match form_key(&s) {
+            Some(k) => k,
+            None => return empty_form(),
+        };
+        match data.following(key).await {
             Ok(v) => {
                 let ul = UserList {
                     users: v,
@@ -294,34 +843,488 @@ mod api {
         }
     }
 
+    /// gets the list of users that follow a particular user
+    #[post("followers")]
+    pub async fn followers(
+        data: web::Data<Srv>,
+        form: web::Form<HashMap<String, String>>,
+    ) -> impl Responder {
+        let s = form.0;
+        let key = match form_key(&s) {
+            Some(k) => k,
+            None => return empty_form(),
+        };
+        match data.followers(key).await {
+            Ok(v) => {
+                let ul = UserList {
+                    users: v,
+                    err: "".to_string(),
+                };
+                build_resp(&ul)
+            }
+            Err(e) => err_response(e),
+        }
+    }
+
+    /// gets the follower and following counts of a particular user
+    #[post("follow-counts")]
+    pub async fn follow_counts(
+        data: web::Data<Srv>,
+        form: web::Form<HashMap<String, String>>,
+    ) -> impl Responder {
+        let s = form.0;
+        let user = match form_key(&s) {
+            Some(k) => k,
+            None => return empty_form(),
+        };
+        let nfollowers = match data.follower_count(user).await {
+            Ok(v) => v,
+            Err(e) => return err_response(e),
+        };
+        match data.following_count(user).await {
+            Ok(nfollowing) => build_resp(&Counts {
+                followers: nfollowers,
+                following: nfollowing,
+                err: "".to_string(),
+            }),
+            Err(e) => err_response(e),
+        }
+    }
+
+    /// suggests users for a particular user to follow
+    #[post("suggest")]
+    pub async fn suggest(
+        data: web::Data<Srv>,
+        limits: web::Data<TribLimits>,
+        form: web::Form<HashMap<String, String>>,
+    ) -> impl Responder {
+        let s = form.0;
+        let raw = match form_key(&s) {
+            Some(k) => k,
+            None => return empty_form(),
+        };
+        let q = match serde_json::from_str::<Suggest>(raw) {
+            Ok(q) => q,
+            Err(e) => return err_response(Box::new(e)),
+        };
+        match data
+            .suggest_follows(&q.user, q.n.unwrap_or(limits.max_suggestions))
+            .await
+        {
+            Ok(v) => {
+                let ul = UserList {
+                    users: v,
+                    err: "".to_string(),
+                };
+                build_resp(&ul)
+            }
+            Err(e) => err_response(e),
+        }
+    }
+
+    /// sends a direct message from one user to another
+    #[post("send-dm")]
+    pub async fn send_dm(
+        data: web::Data<Srv>,
+        limiter: web::Data<RateLimiter>,
+        req: HttpRequest,
+        authed: Authed,
+        form: web::Form<HashMap<String, String>>,
+    ) -> impl Responder {
+        let s = form.0;
+        let raw = match form_key(&s) {
+            Some(k) => k,
+            None => return empty_form(),
+        };
+        match serde_json::from_str::<Dm>(raw) {
+            Ok(d) => {
+                if let Err(e) = authed.check(&d.from) {
+                    return err_response(e);
+                }
+                if let Err(e) = rate_limit(&limiter, &req, "dm", &authed) {
+                    return err_response(e);
+                }
+                match data.send_dm(&d.from, &d.to, &d.message).await {
+                    Ok(_) => build_resp(&Bool {
+                        v: true,
+                        err: "".to_string(),
+                    }),
+                    Err(e) => refused(e),
+                }
+            }
+            Err(e) => err_response(Box::new(e)),
+        }
+    }
+
+    /// lists the direct messages sent to the signed in user
+    #[post("inbox")]
+    pub async fn inbox(
+        data: web::Data<Srv>,
+        authed: Authed,
+        form: web::Form<HashMap<String, String>>,
+    ) -> impl Responder {
+        let s = form.0;
+        let key = match form_key(&s) {
+            Some(k) => k,
+            None => return empty_form(),
+        };
+        if let Err(e) = authed.check(key) {
+            return err_response(e);
+        }
+        match data.inbox(key).await {
+            Ok(v) => {
+                let dl = DmList {
+                    dms: v,
+                    err: "".to_string(),
+                };
+                build_resp(&dl)
+            }
+            Err(e) => err_response(e),
+        }
+    }
+
     /// adds a post for a particular user
     #[post("post")]
     pub async fn post(
         data: web::Data<Srv>,
+        limiter: web::Data<RateLimiter>,
+        req: HttpRequest,
+        authed: Authed,
         form: web::Form<HashMap<String, String>>,
     ) -> impl Responder {
         let s = form.0;
-        let raw = s.keys().next().unwrap();
+        let raw = match form_key(&s) {
+            Some(k) => k,
+            None => return empty_form(),
+        };
         match serde_json::from_str::<Post>(raw) {
             Ok(p) => {
-                let x = match data.post(&p.who, &p.message, p.clock) {
-                    Ok(_) => Bool {
+                if let Err(e) = authed.check(&p.who) {
+                    return err_response(e);
+                }
+                if let Err(e) = rate_limit(&limiter, &req, "post", &authed) {
+                    return err_response(e);
+                }
+                match data.post(&p.who, &p.message, p.clock).await {
+                    Ok(_) => build_resp(&Bool {
+                        v: true,
+                        err: "".to_string(),
+                    }),
+                    Err(e) => refused(e),
+                }
+            }
+            Err(e) => err_response(Box::new(e)),
+        }
+    }
+
+    /// adds a post for a particular user in reply to another trib
+    #[post("reply")]
+    pub async fn reply(
+        data: web::Data<Srv>,
+        limiter: web::Data<RateLimiter>,
+        req: HttpRequest,
+        authed: Authed,
+        form: web::Form<HashMap<String, String>>,
+    ) -> impl Responder {
+        let s = form.0;
+        let raw = match form_key(&s) {
+            Some(k) => k,
+            None => return empty_form(),
+        };
+        match serde_json::from_str::<Reply>(raw) {
+            Ok(p) => {
+                if let Err(e) = authed.check(&p.who) {
+                    return err_response(e);
+                }
+                if let Err(e) = rate_limit(&limiter, &req, "post", &authed) {
+                    return err_response(e);
+                }
+                match data.reply(&p.who, &p.message, p.clock, p.reply_to).await {
+                    Ok(_) => build_resp(&Bool {
                         v: true,
                         err: "".to_string(),
-                    },
-                    Err(e) => Bool {
-                        v: false,
-                        err: e.to_string(),
-                    },
+                    }),
+                    Err(e) => refused(e),
+                }
+            }
+            Err(e) => err_response(Box::new(e)),
+        }
+    }
+
+    /// retribs a trib for a particular user
+    #[post("retrib")]
+    pub async fn retrib(
+        data: web::Data<Srv>,
+        limiter: web::Data<RateLimiter>,
+        req: HttpRequest,
+        authed: Authed,
+        form: web::Form<HashMap<String, String>>,
+    ) -> impl Responder {
+        let s = form.0;
+        let raw = match form_key(&s) {
+            Some(k) => k,
+            None => return empty_form(),
+        };
+        match serde_json::from_str::<TribId>(raw) {
+            Ok(t) => {
+                if let Err(e) = authed.check(&t.who) {
+                    return err_response(e);
+                }
+                if let Err(e) = rate_limit(&limiter, &req, "post", &authed) {
+                    return err_response(e);
+                }
+                match data.retrib(&t.who, t.id).await {
+                    Ok(_) => build_resp(&Bool {
+                        v: true,
+                        err: "".to_string(),
+                    }),
+                    Err(e) => refused(e),
+                }
+            }
+            Err(e) => err_response(Box::new(e)),
+        }
+    }
+
+    /// likes a trib for a particular user
+    #[post("like")]
+    pub async fn like(
+        data: web::Data<Srv>,
+        limiter: web::Data<RateLimiter>,
+        req: HttpRequest,
+        authed: Authed,
+        form: web::Form<HashMap<String, String>>,
+    ) -> impl Responder {
+        let s = form.0;
+        let raw = match form_key(&s) {
+            Some(k) => k,
+            None => return empty_form(),
+        };
+        match serde_json::from_str::<TribId>(raw) {
+            Ok(t) => {
+                if let Err(e) = authed.check(&t.who) {
+                    return err_response(e);
+                }
+                if let Err(e) = rate_limit(&limiter, &req, "like", &authed) {
+                    return err_response(e);
+                }
+                match data.like(&t.who, t.id).await {
+                    Ok(_) => build_resp(&Bool {
+                        v: true,
+                        err: "".to_string(),
+                    }),
+                    Err(e) => refused(e),
+                }
+            }
+            Err(e) => err_response(Box::new(e)),
+        }
+    }
+
+    /// lists the tribs mentioning a particular user
+    #[post("mentions")]
+    pub async fn mentions(
+        data: web::Data<Srv>,
+        form: web::Form<HashMap<String, String>>,
+    ) -> impl Responder {
+        let s = form.0;
+        let key = match form_key(&s) {
+            Some(k) => k,
+            None => return empty_form(),
+        };
+        match data.mentions(key).await {
+            Ok(v) => {
+                let ul = TribList {
+                    tribs: v,
+                    err: "".to_string(),
+                };
+                build_resp(&ul)
+            }
+            Err(e) => err_response(e),
+        }
+    }
+
+    /// lists the conversation a particular trib belongs to
+    #[post("thread")]
+    pub async fn thread(
+        data: web::Data<Srv>,
+        form: web::Form<HashMap<String, String>>,
+    ) -> impl Responder {
+        let s = form.0;
+        let key = match form_key(&s) {
+            Some(k) => k,
+            None => return empty_form(),
+        };
+        let id = match key.parse::<u64>() {
+            Ok(id) => id,
+            Err(e) => return err_response(Box::new(e)),
+        };
+        match data.thread(id).await {
+            Ok(v) => {
+                let ul = TribList {
+                    tribs: v,
+                    err: "".to_string(),
+                };
+                build_resp(&ul)
+            }
+            Err(e) => err_response(e),
+        }
+    }
+
+    /// lists the tribs best matching a search query
+    #[post("search")]
+    pub async fn search(
+        data: web::Data<Srv>,
+        limits: web::Data<TribLimits>,
+        form: web::Form<HashMap<String, String>>,
+    ) -> impl Responder {
+        let s = form.0;
+        let raw = match form_key(&s) {
+            Some(k) => k,
+            None => return empty_form(),
+        };
+        let q = match serde_json::from_str::<Search>(raw) {
+            Ok(q) => q,
+            Err(e) => return err_response(Box::new(e)),
+        };
+        match data
+            .search(&q.query, q.limit.unwrap_or(limits.max_trib_fetch))
+            .await
+        {
+            Ok(v) => {
+                let ul = TribList {
+                    tribs: v,
+                    err: "".to_string(),
+                };
+                build_resp(&ul)
+            }
+            Err(e) => err_response(e),
+        }
+    }
+
+    /// lists the recent tribs carrying a particular tag
+    #[post("tag/search")]
+    pub async fn search_tag(
+        data: web::Data<Srv>,
+        form: web::Form<HashMap<String, String>>,
+    ) -> impl Responder {
+        let s = form.0;
+        let key = match form_key(&s) {
+            Some(k) => k,
+            None => return empty_form(),
+        };
+        match data.search_tag(key).await {
+            Ok(v) => {
+                let ul = TribList {
+                    tribs: v,
+                    err: "".to_string(),
+                };
+                build_resp(&ul)
+            }
+            Err(e) => err_response(e),
+        }
+    }
+
+    /// lists the most used tags among the recent tribs
+    #[get("tag/trending")]
+    pub async fn trending_tags(data: web::Data<Srv>) -> impl Responder {
+        match data.trending_tags().await {
+            Ok(v) => {
+                let tl = TagList {
+                    tags: v,
+                    err: "".to_string(),
                 };
-                build_resp(&x)
+                build_resp(&tl)
+            }
+            Err(e) => err_response(e),
+        }
+    }
+
+    /// deletes a trib of a particular user
+    #[post("delete-trib")]
+    pub async fn delete_trib(
+        data: web::Data<Srv>,
+        authed: Authed,
+        form: web::Form<HashMap<String, String>>,
+    ) -> impl Responder {
+        let s = form.0;
+        let raw = match form_key(&s) {
+            Some(k) => k,
+            None => return empty_form(),
+        };
+        match serde_json::from_str::<TribRef>(raw) {
+            Ok(t) => {
+                if let Err(e) = authed.check(&t.who) {
+                    return err_response(e);
+                }
+                match data.delete_trib(&t.who, t.clock).await {
+                    Ok(_) => build_resp(&Bool {
+                        v: true,
+                        err: "".to_string(),
+                    }),
+                    Err(e) => refused(e),
+                }
+            }
+            Err(e) => err_response(Box::new(e)),
+        }
+    }
+
+    /// replaces the message of a trib of a particular user
+    #[post("edit-trib")]
+    pub async fn edit_trib(
+        data: web::Data<Srv>,
+        authed: Authed,
+        form: web::Form<HashMap<String, String>>,
+    ) -> impl Responder {
+        let s = form.0;
+        let raw = match form_key(&s) {
+            Some(k) => k,
+            None => return empty_form(),
+        };
+        match serde_json::from_str::<Edit>(raw) {
+            Ok(t) => {
+                if let Err(e) = authed.check(&t.who) {
+                    return err_response(e);
+                }
+                match data.edit_trib(&t.who, t.clock, &t.message).await {
+                    Ok(_) => build_resp(&Bool {
+                        v: true,
+                        err: "".to_string(),
+                    }),
+                    Err(e) => refused(e),
+                }
             }
             Err(e) => err_response(Box::new(e)),
         }
     }
 
+    /// streams the tribs posted to the home timeline of a user as
+    /// server-sent events
+    #[get("live/{user}")]
+    pub async fn live_home(data: web::Data<Srv>, user: web::Path<String>) -> impl Responder {
+        let rx = match data.subscribe().await {
+            Ok(rx) => rx,
+            Err(e) => return err_response(e),
+        };
+        // checks that the user exists
+        match data.following_count(&user).await {
+            Ok(_) => live::home_response(rx, user.into_inner()),
+            Err(e) => err_response(e),
+        }
+    }
+
     use serde::{Deserialize, Serialize};
-    use tribbler::trib::Trib;
+    use tribbler::trib::{DirectMessage, Profile, TagCount, Trib, TribLimits};
+
+    #[derive(Serialize, Deserialize, Debug, Clone)]
+    struct Credentials {
+        user: String,
+        password: String,
+    }
+
+    #[derive(Serialize, Deserialize, Debug, Clone)]
+    struct SignedIn {
+        err: String,
+        user: String,
+    }
 
     #[derive(Serialize, Deserialize, Debug, Clone)]
     struct UserList {
@@ -335,12 +1338,38 @@ mod api {
         tribs: Vec<Arc<Trib>>,
     }
 
+    #[derive(Serialize, Deserialize, Debug, Clone)]
+    struct UserProfile {
+        err: String,
+        profile: Profile,
+    }
+
+    #[derive(Serialize, Deserialize, Debug, Clone)]
+    struct DmList {
+        err: String,
+        dms: Vec<Arc<DirectMessage>>,
+    }
+
+    #[derive(Serialize, Deserialize, Debug, Clone)]
+    struct TagList {
+        err: String,
+        tags: Vec<TagCount>,
+    }
+
     #[derive(Serialize, Deserialize, Debug, Clone)]
     struct Bool {
         err: String,
         v: bool,
     }
 
+    #[derive(Serialize, Deserialize, Debug, Clone)]
+    struct Counts {
+        err: String,
+        followers: usize,
+        following: usize,
+    }
+
+    #[allow(dead_code)]
     #[derive(Serialize, Deserialize, Debug, Clone)]
     struct Clock {
         err: String,
@@ -359,4 +1388,82 @@ mod api {
         message: String,
         clock: u64,
     }
+
+    #[derive(Serialize, Deserialize, Debug, Clone)]
+    struct ProfileEdit {
+        user: String,
+        display_name: String,
+        bio: String,
+    }
+
+    #[derive(Serialize, Deserialize, Debug, Clone)]
+    struct Dm {
+        from: String,
+        to: String,
+        message: String,
+    }
+
+    #[derive(Serialize, Deserialize, Debug, Clone)]
+    struct Reply {
+        who: String,
+        message: String,
+        clock: u64,
+        reply_to: u64,
+    }
+
+    #[derive(Serialize, Deserialize, Debug, Clone)]
+    struct TribId {
+        who: String,
+        id: u64,
+    }
+
+    #[derive(Serialize, Deserialize, Debug, Clone)]
+    struct TribRef {
+        who: String,
+        clock: u64,
+    }
+
+    #[derive(Serialize, Deserialize, Debug, Clone)]
+    struct Edit {
+        who: String,
+        clock: u64,
+        message: String,
+    }
+
+    #[derive(Serialize, Deserialize, Debug, Clone)]
+    struct Page {
+        user: String,
+        before: Option<u64>,
+        limit: Option<usize>,
+    }
+
+    impl Page {
+        /// the clock to page before, defaulting to the newest tribs
+        fn before(&self) -> u64 {
+            self.before.unwrap_or(u64::MAX)
+        }
+
+        /// the page size, defaulting to the `max_trib_fetch` of `limits`
+        fn limit(&self, limits: &TribLimits) -> usize {
+            self.limit.unwrap_or(limits.max_trib_fetch)
+        }
+    }
+
+    #[derive(Serialize, Deserialize, Debug, Clone)]
+    struct Search {
+        query: String,
+        limit: Option<usize>,
+    }
+
+    #[derive(Serialize, Deserialize, Debug, Clone)]
+    struct Suggest {
+        user: String,
+        n: Option<usize>,
+    }
+
+    #[derive(Serialize, Deserialize, Debug, Clone)]
+    struct Since {
+        user: String,
+        after: u64,
+    }
 }
@@ -3,6 +3,7 @@ use async_trait::async_trait;
 use tribbler::{
     self,
     err::TribResult,
+    hlc::Hlc,
     rpc,
     rpc::trib_storage_client::TribStorageClient,
     storage::{KeyList, KeyString, KeyValue, List, Pattern, Storage}, // to implement the RPCs
@@ -45,9 +46,8 @@ impl KeyString for StorageClient {
                 value: kv.value.clone(),
             })
             .await?;
-        match r.into_inner().value {
-            value => Ok(value),
-        }
+        let value = r.into_inner().value;
+        Ok(value)
     }
 
     async fn keys(&self, p: &Pattern) -> TribResult<List> {
@@ -58,9 +58,8 @@ impl KeyString for StorageClient {
                 suffix: p.suffix.clone(),
             })
             .await?;
-        match r.into_inner().list {
-            list => Ok(List(list)),
-        }
+        let list = r.into_inner().list;
+        Ok(List(list))
     }
 }
 
@@ -73,9 +72,8 @@ impl KeyList for StorageClient {
                 key: key.to_string(),
             })
             .await?;
-        match r.into_inner().list {
-            list => Ok(List(list)),
-        }
+        let list = r.into_inner().list;
+        Ok(List(list))
     }
 
     async fn list_append(&self, kv: &KeyValue) -> TribResult<bool> {
@@ -86,9 +84,8 @@ impl KeyList for StorageClient {
                 value: kv.value.clone(),
             })
             .await?;
-        match r.into_inner().value {
-            value => Ok(value),
-        }
+        let value = r.into_inner().value;
+        Ok(value)
     }
 
     async fn list_remove(&self, kv: &KeyValue) -> TribResult<u32> {
@@ -99,9 +96,8 @@ impl KeyList for StorageClient {
                 value: kv.value.clone(),
             })
             .await?;
-        match r.into_inner().removed {
-            removed => Ok(removed),
-        }
+        let removed = r.into_inner().removed;
+        Ok(removed)
     }
 
     async fn list_keys(&self, p: &Pattern) -> TribResult<List> {
@@ -112,9 +108,8 @@ impl KeyList for StorageClient {
                 suffix: p.suffix.clone(),
             })
             .await?;
-        match r.into_inner().list {
-            list => Ok(List(list)),
-        }
+        let list = r.into_inner().list;
+        Ok(List(list))
     }
 }
 
@@ -127,8 +122,17 @@ impl Storage for StorageClient {
                 timestamp: at_least,
             })
             .await?;
-        match r.into_inner().timestamp {
-            timestamp => Ok(timestamp),
-        }
+        let timestamp = r.into_inner().timestamp;
+        Ok(timestamp)
+    }
+
+    async fn hlc(&self, at_least: Hlc) -> TribResult<Hlc> {
+        let mut client = TribStorageClient::connect(self.addr.clone()).await?;
+        let r = client
+            .hlc(rpc::Clock {
+                timestamp: at_least.into(),
+            })
+            .await?;
+        Ok(r.into_inner().timestamp.into())
     }
 }
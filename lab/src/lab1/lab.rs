@@ -2,6 +2,8 @@ use crate::lab1::client::StorageClient;
 use crate::lab1::server::StorageServer;
 use std::boxed::Box;
 use std::net::ToSocketAddrs;
+use tokio::net::TcpListener;
+use tokio_stream::wrappers::TcpListenerStream;
 use tonic::transport::Server;
 use tribbler::err::TribblerError;
 use tribbler::{
@@ -21,6 +23,17 @@ pub async fn serve_back(config: BackConfig) -> TribResult<()> {
     match config.addr.clone().to_socket_addrs() {
         Ok(iterator) => match iterator.last() {
             Some(socket_addr) => {
+                // bind before signaling ready so that clients never race the listener
+                let listener = match TcpListener::bind(socket_addr).await {
+                    Ok(listener) => listener,
+                    Err(e) => {
+                        if let Some(unwrapped_ready) = config.ready {
+                            let _ = unwrapped_ready.send(false);
+                        }
+                        return Err(Box::new(e));
+                    }
+                };
+                let incoming = TcpListenerStream::new(listener);
                 match config.shutdown {
                     Some(mut s) => {
                         let _ = match config.ready {
@@ -29,7 +42,7 @@ pub async fn serve_back(config: BackConfig) -> TribResult<()> {
                         };
                         Server::builder()
                             .add_service(TribStorageServer::new(storage_server))
-                            .serve_with_shutdown(socket_addr, async {
+                            .serve_with_incoming_shutdown(incoming, async {
                                 s.recv().await;
                             }) // block until there is an error, or a shutdown message is received
                             .await?
@@ -41,7 +54,7 @@ pub async fn serve_back(config: BackConfig) -> TribResult<()> {
                         };
                         Server::builder()
                             .add_service(TribStorageServer::new(storage_server))
-                            .serve(socket_addr)
+                            .serve_with_incoming(incoming)
                             .await?
                     }
                 }
@@ -140,4 +140,17 @@ impl rpc::trib_storage_server::TribStorage for StorageServer {
             Err(e) => Err(tonic::Status::unknown("fail clock")),
         }
     }
+
+    async fn hlc(
+        &self,
+        request: tonic::Request<rpc::Clock>,
+    ) -> Result<tonic::Response<rpc::Clock>, tonic::Status> {
+        let t = request.into_inner();
+        match self.storage.hlc(t.timestamp.into()).await {
+            Ok(h) => Ok(Response::new(rpc::Clock {
+                timestamp: h.into(),
+            })),
+            Err(e) => Err(tonic::Status::unknown(format!("fail hlc: {}", e))),
+        }
+    }
 }
@@ -1,4 +1,9 @@
-use tribbler::{config::KeeperConfig, err::TribResult, storage::BinStorage, trib::Server};
+use tribbler::{
+    config::KeeperConfig,
+    err::TribResult,
+    storage::BinStorage,
+    trib::{Server, TribLimits},
+};
 
 /// This function accepts a list of backend addresses, and returns a
 /// type which should implement the [BinStorage] trait to access the
@@ -20,7 +25,7 @@ pub async fn serve_keeper(kc: KeeperConfig) -> TribResult<()> {
 }
 
 /// this function accepts a [BinStorage] client which should be used in order to
-/// implement the [Server] trait.
+/// implement the [Server] trait, enforcing `limits`.
 ///
 /// You'll need to translate calls from the tribbler front-end into storage
 /// calls using the [BinStorage] interface.
@@ -31,6 +36,7 @@ pub async fn serve_keeper(kc: KeeperConfig) -> TribResult<()> {
 #[allow(unused_variables)]
 pub async fn new_front(
     bin_storage: Box<dyn BinStorage>,
+    limits: TribLimits,
 ) -> TribResult<Box<dyn Server + Send + Sync>> {
     todo!()
 }
@@ -96,9 +96,9 @@
 //! Before we look into how the Tribbler service works, let's first define what
 //! a tribble is.
 //!
-//! A [Tribble](tribbler::trib::Trib) is a struct that has 4 fields:
+//! A [Tribble](tribbler::trib::Trib) is a struct that has 7 fields:
 //!
-//! ```rust
+//! ```rust,ignore
 //! pub struct Trib {
 //!     /// who posted this trib
 //!     pub user: String,
@@ -108,6 +108,16 @@
 //!     pub time: u64,
 //!     /// the logical timestamp when posted
 //!     pub clock: u64,
+//!     /// the id of this trib, unique among all tribs of the service
+//!     pub id: u64,
+//!     /// the physical timestamp of the last edit, if the trib was edited
+//!     pub edited: Option<u64>,
+//!     /// the id of the trib this trib replies to, if it is a reply
+//!     pub reply_to: Option<u64>,
+//!     /// the id of the original trib, if this trib is a retrib of it
+//!     pub retrib_of: Option<u64>,
+//!     /// the count of users who liked the trib
+//!     pub likes: u64,
 //! }
 //! ```
 //!
@@ -126,7 +136,21 @@
 //!  3. `user` The user id.
 //!  4. `message` The message content.
 //!
-//! We call this the *Tribble Order*.
+//! We call this the *Tribble Order*. It is the order of the [Ord]
+//! implementation of [Trib](tribbler::trib::Trib), so sorting tribbles sorts
+//! them in the Tribble Order.
+//! The [timeline](tribbler::timeline) module builds on it to merge the
+//! timelines of several users, pick the newest tribbles and drop duplicates.
+//!
+//! Besides [clock](tribbler::storage::Storage::clock), the storage offers
+//! [hlc](tribbler::storage::Storage::hlc), which returns
+//! [Hlc](tribbler::hlc::Hlc) timestamps of a hybrid logical clock. These are
+//! `u64` logical clock values which also follow the physical time of the
+//! back-end, so tribbles posted on different bins sort closer to the order
+//! they were posted in. They exceed the integers a JavaScript number holds
+//! exactly, though, and the web front-end hands the clocks of tribbles to the
+//! browser as numbers, so keep to `clock` for the `clock` of tribbles served
+//! there.
 //!
 //! ## Tribbler Service Interface
 //!
@@ -134,10 +158,15 @@
 //! [Server](tribbler::trib::Server) interface (in `tribbler/src/trib.rs`). This
 //! is how the web user interface interacts with a Tribbler server.
 //!
+//! Only the methods below are required. The other methods of the trait, like
+//! replies, likes or direct messages, have default implementations which
+//! return [NotSupported](tribbler::err::TribblerError::NotSupported), so your
+//! front-end builds without them and can take them on one at a time.
+//!
 //! --------------
 //!
-//! ```rust
-//! fn sign_up(&self, user: &str) -> TribResult<()>;
+//! ```rust,ignore
+//! async fn sign_up(&self, user: &str) -> TribResult<()>;
 //! ```
 //!
 //! Creates a new user. After a user is created, it will never disappear in the
@@ -153,10 +182,17 @@
 //! [is_valid_username](tribbler::trib::is_valid_username) which you can use to
 //! check if a username is valid.
 //!
+//! When `unicode_usernames` is set in the limits, usernames may instead use
+//! the letters and digits of any single script, and must be in the normal
+//! form of [normalize_username](tribbler::trib::normalize_username). A
+//! sign-up should then also fail when the name has the same
+//! [username_skeleton](tribbler::trib::username_skeleton) as an existing user,
+//! with [UsernameConfusable](tribbler::err::TribblerError::UsernameConfusable).
+//!
 //! ---
 //!
-//! ```rust
-//! fn list_users(&self) -> TribResult<Vec<String>>;
+//! ```rust,ignore
+//! async fn list_users(&self) -> TribResult<Vec<String>>;
 //! ```
 //!
 //! Lists at least [MIN_LIST_USER](tribbler::trib::MIN_LIST_USER) different
@@ -170,19 +206,21 @@
 //!
 //! ---
 //!
-//! ```rust
-//! fn post(&self, who: &str, post: &str, clock: u64) -> TribResult<()>;
+//! ```rust,ignore
+//! async fn post(&self, who: &str, post: &str, clock: u64) -> TribResult<()>;
 //! ```
 //!
 //! Posts a tribble. `clock` is the maximum clock value this user client has
 //! ever seen (from reading tribbles via `home()` or `tribs()`). It returns
 //! error when the user does not exist or the post is too long (longer than
-//! [MAX_TRIB_LEN](tribbler::trib::MAX_TRIB_LEN)`).
+//! [MAX_TRIB_LEN](tribbler::trib::MAX_TRIB_LEN)`). Lengths are counted in
+//! user-perceived characters with [text_len](tribbler::trib::text_len), not in
+//! bytes, so that a trib of 140 emoji is still valid.
 //!
 //! ---
 //!
-//! ```rust
-//! fn tribs(&self, user: &str) -> TribResult<Vec<Arc<Trib>>>;
+//! ```rust,ignore
+//! async fn tribs(&self, user: &str) -> TribResult<Vec<Arc<Trib>>>;
 //! ```
 //!
 //! Lists the recent [MAX_TRIB_FETCH](tribbler::trib::MAX_TRIB_FETCH) tribbles
@@ -191,11 +229,11 @@
 //!
 //! ---
 //!
-//! ```rust
-//! fn follow(&self, who: &str, whom: &str) -> TribResult<()>;
-//! fn unfollow(&self, who: &str, whom: &str) -> TribResult<()>;
-//! fn is_following(&self, who: &str, whom: &str) -> TribResult<bool>;
-//! fn following(&self, who: &str) -> TribResult<Vec<String>>;
+//! ```rust,ignore
+//! async fn follow(&self, who: &str, whom: &str) -> TribResult<()>;
+//! async fn unfollow(&self, who: &str, whom: &str) -> TribResult<()>;
+//! async fn is_following(&self, who: &str, whom: &str) -> TribResult<bool>;
+//! async fn following(&self, who: &str) -> TribResult<Vec<String>>;
 //! ```
 //!
 //! These are functions to follow/unfollow, check following and listing all
@@ -212,8 +250,8 @@
 //!
 //! ---
 //!
-//! ```rust
-//! fn home(&self, user: &str) -> TribResult<Vec<Arc<Trib>>>;
+//! ```rust,ignore
+//! async fn home(&self, user: &str) -> TribResult<Vec<Arc<Trib>>>;
 //! ```
 //!
 //! List the recent [MAX_TRIB_FETCH](tribbler::trib::MAX_TRIB_FETCH) tribbles
@@ -234,6 +272,52 @@
 //!
 //! ---
 //!
+//! ```rust,ignore
+//! async fn search_tag(&self, tag: &str) -> TribResult<Vec<Arc<Trib>>>;
+//! async fn trending_tags(&self) -> TribResult<Vec<TagCount>>;
+//! ```
+//!
+//! These list the recent tribbles carrying a `#tag`, and the most used tags
+//! among the recent tribbles. Tags are extracted from a message with
+//! [parse_tags](tribbler::trib::parse_tags) when it is posted. The tag index
+//! can live in the bin storage like everything else: for example, a bin per
+//! tag holding a list of references to the tagged tribbles, which is checked
+//! against the current tribble on read, since a tribble may be edited or
+//! deleted after it is indexed.
+//!
+//! ---
+//!
+//! ```rust,ignore
+//! async fn set_credential(&self, user: &str, credential: &str) -> TribResult<()>;
+//! async fn credential(&self, user: &str) -> TribResult<Option<String>>;
+//! async fn sign_up_with_credential(&self, user: &str, credential: &str) -> TribResult<()>;
+//! ```
+//!
+//! These store and fetch an opaque credential per user, which `trib-front`
+//! uses for the password hashes of its sign in. Keep it in the user's bin
+//! like the rest of the user's data; the front-end does the hashing, so the
+//! storage never sees a password. `trib-front` signs users up with
+//! `sign_up_with_credential`, whose default implementation signs up and then
+//! sets the credential. Override it if you can store both at once, so that a
+//! failure in between does not leave a user who can never sign in.
+//!
+//! ---
+//!
+//! ```rust,ignore
+//! async fn subscribe(&self) -> TribResult<PostReceiver>;
+//! ```
+//!
+//! This returns a receiver of a [Posted](tribbler::trib::Posted)
+//! notification for every tribble posted through this front-end from now on,
+//! which `trib-front` uses to push new tribbles to the browser. Keep a
+//! `tokio::sync::broadcast` sender in your front-end and send to it at the end
+//! of a successful `post()`, listing the author and the followers whose home
+//! timeline the tribble shows on. Tribbles posted through other front-ends
+//! need not be notified; the browser catches up whenever it fetches the home
+//! timeline again.
+//!
+//! ---
+//!
 //! In addition to normal errors, these calls might also return IO errors if the
 //! implementation is unable to communicate with a remote service. Returning a
 //! `Ok()` type means that the call is successfully executed; returning an
@@ -257,7 +341,7 @@
 //!
 //! You can find these entry functions in `lab/src/lab2/lab.rs`:
 //!
-//! ```rust
+//! ```rust,ignore
 //! pub async fn new_bin_client(backs: Vec<String>) -> TribResult<Box<dyn BinStorage>>
 //! ```
 //!
@@ -274,12 +358,25 @@
 //! For your convenience, we have provided a small package called
 //! [tribbler::colon] which you can use for escaping all the colons in a string.
 //! The escaping rule is simple, all `'|'` characters are escaped into `"||"`,
-//! and all `':'` characters are escaped into `"|:"`. Please use this package
-//! wisely.
+//! and all `':'` characters are escaped into `"|;"`. Please use this package
+//! wisely. An escaped string holds no `':'`, so
+//! [bin_key](tribbler::colon::bin_key) can put the escaped bin name before
+//! `"::"` and a key without two bins ever sharing a key, and
+//! [split_bin_key](tribbler::colon::split_bin_key) splits such a key back.
+//! Where a string may not come from [escape](tribbler::colon::escape), use
+//! [try_unescape](tribbler::colon::try_unescape), which returns an error on
+//! malformed escape sequences instead of guessing.
+//!
+//! To store tribbles as values in a bin, you can use [tribbler::codec], whose
+//! [encode_trib](tribbler::codec::encode_trib) writes a
+//! [Trib](tribbler::trib::Trib) into a compact string tagged with its version,
+//! and whose [decode_trib](tribbler::codec::decode_trib) reads it back. It
+//! also reads tribbles stored as JSON, so values written with `serde_json`
+//! stay readable.
 //!
 //! ---
 //!
-//! ```rust
+//! ```rust,ignore
 //! pub async fn serve_keeper(kc: KeeperConfig) -> TribResult<()>
 //! ```
 //!
@@ -309,12 +406,12 @@
 //!   when this keeper was created relative to other keepers. For Lab 2, you may
 //!   ignore this field.
 //! - `ready: Option<Sender<bool>>`: A ready signal channel. It works similarly
-//!   to the `ready` channel in [tribbler::config::BackConfig] from Lab
-//!   1. When a keeper sends `true` on this channel, the distributed bin storage
-//!   should be ready to serve. Therefore, if you need to initialize the
-//!   physical back-ends in some way, make sure you do it before you send a
-//!   signal over `ready`. Don't forget to send `false` to `ready` if the
-//!   initialization fails.
+//!   to the `ready` channel in [tribbler::config::BackConfig] from Lab 1. When
+//!   a keeper sends `true` on this channel, the distributed bin storage should
+//!   be ready to serve. Therefore, if you need to initialize the physical
+//!   back-ends in some way, make sure you do it before you send a signal over
+//!   `ready`. Don't forget to send `false` to `ready` if the initialization
+//!   fails.
 //!
 //! Although an address for each keeper is given in the `addrs` field, your
 //! keeper does not necessarily need to listen on the address given. The keeper
@@ -343,8 +440,11 @@
 //!
 //! ---
 //!
-//! ```rust
-//! pub fn new_front(bin_storage: Box<dyn BinStorage>) -> TribResult<Box<dyn Server>>
+//! ```rust,ignore
+//! pub fn new_front(
+//!     bin_storage: Box<dyn BinStorage>,
+//!     limits: TribLimits,
+//! ) -> TribResult<Box<dyn Server>>
 //! ```
 //!
 //! This function takes a bin storage structure, and returns an implementation
@@ -367,6 +467,17 @@
 //! perform Tribbler related tasks. A front-ends may spawn additional background
 //! go routines if additional work is required by your implementation.
 //!
+//! The [TribLimits](tribbler::trib::TribLimits) are read from the `limits` of
+//! `bins.json`, and default to the constants named in the API above. Your
+//! front-end should enforce them in place of the constants: use
+//! [TribLimits::is_valid_username](tribbler::trib::TribLimits::is_valid_username)
+//! to check usernames and
+//! [TribLimits::parse_tags](tribbler::trib::TribLimits::parse_tags) to find
+//! tags, and the field named after a constant, such as `limits.max_trib_len`
+//! for `MAX_TRIB_LEN`, wherever the API above names that constant. Return
+//! them from [Server::limits](tribbler::trib::Server::limits) too, which the
+//! default paging methods go by.
+//!
 //! ## Putting the pieces together
 //!
 //! Your first step should be to write the bin storage service. Since we might
@@ -381,7 +492,7 @@
 //!
 //! Find a directory to use as your working directory, then run:
 //!
-//! ```
+//! ```text
 //! $ cargo run --bin bins-mkcfg -- --backs 3
 //! ```
 //!
@@ -394,7 +505,7 @@
 //!
 //! With this configuration file generated, we can now launch the back-ends:
 //!
-//! ```
+//! ```text
 //! $ cargo run --bin bins-back
 //! ```
 //!
@@ -408,7 +519,7 @@
 //!
 //! After the back-ends are ready, we can now start the keeper.
 //!
-//! ```
+//! ```text
 //! $ cargo run --bin bins-keep
 //! ```
 //!
@@ -417,7 +528,7 @@
 //!
 //! To play with this distributed bin storage, we can use `bins-client`:
 //!
-//! ```
+//! ```text
 //! $ bins-client
 //! (working on bin "")
 //! > bin a
@@ -447,7 +558,7 @@
 //! Once our bin storage is up and running, we can launch our Tribbler
 //! front-end:
 //!
-//! ```
+//! ```text
 //! $ cargo run --bin trib-front -- -s lab
 //! ```
 //!
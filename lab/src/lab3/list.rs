@@ -0,0 +1,323 @@
+//! A replicated representation of the key-list part of the storage interface.
+//!
+//! Every list operation is written to the back-ends as a tagged log entry
+//! instead of as a raw value. The tag is a `(clock, writer)` pair where the
+//! clock comes from [Storage::clock] and the writer is the id of the client
+//! that issued the operation, so any two entries can be totally ordered. On
+//! read the logs of all reachable replicas are merged and sorted by tag, which
+//! gives every reader the same list once the concurrent appends have returned.
+//!
+//! Appends that were retried may reach a replica twice, but they carry the
+//! same tag and are collapsed when merged. Removes are recorded as tombstones
+//! which hide every matching append with a smaller tag.
+use std::collections::{BTreeSet, HashMap};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use async_trait::async_trait;
+use tribbler::{
+    colon,
+    err::{TribResult, TribblerError},
+    storage::{KeyList, KeyValue, List, Pattern, Storage},
+};
+
+/// number of attempts made to write an entry to a single replica
+const WRITE_ATTEMPTS: usize = 2;
+
+/// The kind of operation recorded by a [ListEntry]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum ListOp {
+    /// appends the value to the end of the list
+    Append,
+    /// removes every earlier append of the value
+    Remove,
+}
+
+/// A single tagged operation in the replicated log of a list.
+///
+/// Entries are ordered by `(clock, writer)` first, which is the order the
+/// operations are applied in.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ListEntry {
+    /// the clock value returned by [Storage::clock] for this operation
+    pub clock: u64,
+    /// the id of the client which issued this operation
+    pub writer: u64,
+    /// the kind of operation
+    pub op: ListOp,
+    /// the value appended or removed
+    pub value: String,
+}
+
+impl ListEntry {
+    /// the `(clock, writer)` tag of this entry
+    pub fn tag(&self) -> (u64, u64) {
+        (self.clock, self.writer)
+    }
+
+    /// Encodes the entry into the string stored in a back-end list
+    ///
+    /// ```rust
+    /// use lab::lab3::{ListEntry, ListOp};
+    /// let e = ListEntry {
+    ///     clock: 7,
+    ///     writer: 1,
+    ///     op: ListOp::Append,
+    ///     value: "a:b".to_string(),
+    /// };
+    /// assert_eq!("a:7:1:a|;b", e.encode());
+    /// assert_eq!(e, ListEntry::decode(&e.encode()).unwrap());
+    /// ```
+    pub fn encode(&self) -> String {
+        let op = match self.op {
+            ListOp::Append => "a",
+            ListOp::Remove => "r",
+        };
+        format!(
+            "{}:{}:{}:{}",
+            op,
+            self.clock,
+            self.writer,
+            colon::escape(self.value.as_str())
+        )
+    }
+
+    /// Decodes an entry produced by [ListEntry::encode]. Returns an error
+    /// when the string is not a valid entry.
+    pub fn decode(raw: &str) -> TribResult<ListEntry> {
+        let malformed = || TribblerError::Unknown(format!("malformed list entry: {}", raw));
+        let parts: Vec<&str> = raw.splitn(4, ':').collect();
+        if parts.len() != 4 {
+            return Err(Box::new(malformed()));
+        }
+        let op = match parts[0] {
+            "a" => ListOp::Append,
+            "r" => ListOp::Remove,
+            _ => return Err(Box::new(malformed())),
+        };
+        let clock = parts[1].parse::<u64>().map_err(|_| malformed())?;
+        let writer = parts[2].parse::<u64>().map_err(|_| malformed())?;
+        Ok(ListEntry {
+            clock,
+            writer,
+            op,
+            value: colon::try_unescape(parts[3]).map_err(|_| malformed())?,
+        })
+    }
+}
+
+/// The merged log of a single list, built from the entries read from one or
+/// more replicas.
+#[derive(Debug, Clone, Default)]
+pub struct ListLog {
+    entries: BTreeSet<ListEntry>,
+}
+
+impl ListLog {
+    /// Creates an empty [ListLog]
+    pub fn new() -> ListLog {
+        ListLog::default()
+    }
+
+    /// Adds a single entry to the log. Duplicate entries are ignored.
+    pub fn insert(&mut self, entry: ListEntry) {
+        self.entries.insert(entry);
+    }
+
+    /// Decodes and merges the raw contents of a replica's list into the log.
+    pub fn merge<T: AsRef<str>>(&mut self, raw: &[T]) -> TribResult<()> {
+        for r in raw.iter() {
+            self.insert(ListEntry::decode(r.as_ref())?);
+        }
+        Ok(())
+    }
+
+    /// the largest clock value of any entry in the log, or 0 when empty
+    pub fn max_clock(&self) -> u64 {
+        self.entries.iter().map(|e| e.clock).max().unwrap_or(0)
+    }
+
+    /// the number of entries in the log, including tombstones
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// returns true when the log holds no entries
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Applies the log in tag order and returns the resulting list values.
+    pub fn values(&self) -> Vec<String> {
+        // the latest tombstone of each removed value
+        let mut removed: HashMap<&str, (u64, u64)> = HashMap::new();
+        for e in self.entries.iter().filter(|e| e.op == ListOp::Remove) {
+            removed.insert(e.value.as_str(), e.tag());
+        }
+        self.entries
+            .iter()
+            .filter(|e| e.op == ListOp::Append)
+            .filter(|e| match removed.get(e.value.as_str()) {
+                Some(tag) => e.tag() > *tag,
+                None => true,
+            })
+            .map(|e| e.value.clone())
+            .collect()
+    }
+}
+
+/// A [KeyList] implementation which stores every list on all of the given
+/// replicas using [ListEntry] logs.
+///
+/// Operations succeed as long as at least one replica is reachable.
+pub struct ReplicaSet {
+    replicas: Vec<Box<dyn Storage>>,
+    writer: u64,
+    last_clock: AtomicU64,
+}
+
+impl ReplicaSet {
+    /// Creates a [ReplicaSet] over `replicas`. `writer` must be unique among
+    /// all clients writing to the same replicas.
+    pub fn new(replicas: Vec<Box<dyn Storage>>, writer: u64) -> ReplicaSet {
+        ReplicaSet {
+            replicas,
+            writer,
+            last_clock: AtomicU64::new(0),
+        }
+    }
+
+    /// the writer id used to tag entries issued by this client
+    pub fn writer(&self) -> u64 {
+        self.writer
+    }
+
+    /// Fetches a clock no smaller than `at_least` and strictly larger than any
+    /// clock this client used before, from the first replica that answers.
+    async fn next_clock(&self, at_least: u64) -> TribResult<u64> {
+        let floor = self
+            .last_clock
+            .load(Ordering::SeqCst)
+            .saturating_add(1)
+            .max(at_least);
+        let mut last_err: Option<Box<dyn std::error::Error + Send + Sync>> = None;
+        for replica in self.replicas.iter() {
+            match replica.clock(floor).await {
+                Ok(c) => {
+                    self.last_clock.fetch_max(c, Ordering::SeqCst);
+                    return Ok(c);
+                }
+                Err(e) => last_err = Some(e),
+            }
+        }
+        Err(last_err.unwrap_or_else(|| Box::new(no_replicas())))
+    }
+
+    /// Appends the encoded entry to every replica. Returns an error only if
+    /// no replica accepted it.
+    async fn write(&self, key: &str, entry: &ListEntry) -> TribResult<()> {
+        let kv = KeyValue::new(key, &entry.encode());
+        let mut written = false;
+        let mut last_err: Option<Box<dyn std::error::Error + Send + Sync>> = None;
+        for replica in self.replicas.iter() {
+            // a retry carries the same tag, so a replica which applied both
+            // attempts only ends up with a duplicate that is merged away
+            for _ in 0..WRITE_ATTEMPTS {
+                match replica.list_append(&kv).await {
+                    Ok(_) => {
+                        written = true;
+                        break;
+                    }
+                    Err(e) => last_err = Some(e),
+                }
+            }
+        }
+        match (written, last_err) {
+            (true, _) => Ok(()),
+            (false, Some(e)) => Err(e),
+            (false, None) => Err(Box::new(no_replicas())),
+        }
+    }
+
+    /// Reads and merges the log of `key` from every reachable replica.
+    pub async fn read_log(&self, key: &str) -> TribResult<ListLog> {
+        let mut log = ListLog::new();
+        let mut read = false;
+        let mut last_err: Option<Box<dyn std::error::Error + Send + Sync>> = None;
+        for replica in self.replicas.iter() {
+            match replica.list_get(key).await {
+                Ok(List(raw)) => {
+                    log.merge(&raw)?;
+                    read = true;
+                }
+                Err(e) => last_err = Some(e),
+            }
+        }
+        match (read, last_err) {
+            (true, _) => Ok(log),
+            (false, Some(e)) => Err(e),
+            (false, None) => Err(Box::new(no_replicas())),
+        }
+    }
+}
+
+fn no_replicas() -> TribblerError {
+    TribblerError::Unknown("no replicas available".to_string())
+}
+
+#[async_trait]
+impl KeyList for ReplicaSet {
+    async fn list_get(&self, key: &str) -> TribResult<List> {
+        Ok(List(self.read_log(key).await?.values()))
+    }
+
+    async fn list_append(&self, kv: &KeyValue) -> TribResult<bool> {
+        let entry = ListEntry {
+            clock: self.next_clock(0).await?,
+            writer: self.writer,
+            op: ListOp::Append,
+            value: kv.value.clone(),
+        };
+        self.write(&kv.key, &entry).await?;
+        Ok(true)
+    }
+
+    async fn list_remove(&self, kv: &KeyValue) -> TribResult<u32> {
+        let log = self.read_log(&kv.key).await?;
+        let removed = log.values().iter().filter(|v| **v == kv.value).count();
+        // the tombstone must order after every append that was counted
+        let entry = ListEntry {
+            clock: self.next_clock(log.max_clock().saturating_add(1)).await?,
+            writer: self.writer,
+            op: ListOp::Remove,
+            value: kv.value.clone(),
+        };
+        self.write(&kv.key, &entry).await?;
+        Ok(removed as u32)
+    }
+
+    async fn list_keys(&self, p: &Pattern) -> TribResult<List> {
+        let mut keys = BTreeSet::new();
+        let mut read = false;
+        let mut last_err: Option<Box<dyn std::error::Error + Send + Sync>> = None;
+        for replica in self.replicas.iter() {
+            match replica.list_keys(p).await {
+                Ok(List(k)) => {
+                    keys.extend(k);
+                    read = true;
+                }
+                Err(e) => last_err = Some(e),
+            }
+        }
+        if !read {
+            return Err(last_err.unwrap_or_else(|| Box::new(no_replicas())));
+        }
+        // lists holding only tombstones are empty to the caller
+        let mut result = vec![];
+        for key in keys.into_iter() {
+            if !self.read_log(&key).await?.values().is_empty() {
+                result.push(key);
+            }
+        }
+        Ok(List(result))
+    }
+}
@@ -128,8 +128,8 @@
 //!   semicolon `;` at the end of a statement.
 //! - Store items on the heap using the `Box<T>` type.
 //! - You can write interfaces in Rust using the `trait` keyword, and then
-//! implement a trait for a specific struct with `impl <Trait> for <Struct> {
-//! ... }`
+//!   implement a trait for a specific struct with `impl <Trait> for <Struct> {
+//!   ... }`
 //!
 //! ## The Tribbler Story
 //!
@@ -8,7 +8,6 @@ use std::{
 };
 
 use lab::{self, lab1};
-use log::LevelFilter;
 use tokio::{sync::mpsc::Sender as MpscSender, task::JoinHandle};
 
 use tribbler::addr::rand::rand_port;
@@ -17,6 +16,7 @@ use tribbler::{
     self,
     config::BackConfig,
     err::{TribResult, TribblerError},
+    hlc::Hlc,
     storage::{KeyList, KeyString, KeyValue, MemStorage, Pattern, Storage},
 };
 
@@ -42,7 +42,7 @@ async fn setup(
     let (shut_tx, shut_rx) = tokio::sync::mpsc::channel(1);
     let cfg = BackConfig {
         addr: addr.to_string(),
-        storage: storage,
+        storage,
         ready: Some(tx.clone()),
         shutdown: Some(shut_rx),
     };
@@ -250,6 +250,19 @@ async fn test_clock() -> TribResult<()> {
     Ok(())
 }
 
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn test_hlc() -> TribResult<()> {
+    let (client, _srv, _shut) = setup(None, None).await?;
+    let before = Hlc::now();
+    let h1 = client.hlc(Hlc::default()).await?;
+    assert!(h1 >= before);
+    let ahead = Hlc::new(before.physical() + 60_000, 0);
+    assert_eq!(ahead, client.hlc(ahead).await?);
+    assert_eq!(0, client.clock(0).await?);
+    assert_eq!(Hlc::new(ahead.physical(), 1), client.hlc(h1).await?);
+    Ok(())
+}
+
 #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
 async fn test_spawn_same_addr() -> TribResult<()> {
     let addr = DEFAULT_HOST.to_string();
@@ -262,7 +275,7 @@ async fn test_spawn_same_addr() -> TribResult<()> {
         shutdown: Some(shut_rx),
     };
     let handle = spawn_back(cfg);
-    assert_eq!(true, rx.recv_timeout(Duration::from_secs(2))?);
+    assert!(rx.recv_timeout(Duration::from_secs(2))?);
     let _ = shut_tx.send(()).await;
     let _ = handle.await;
     thread::sleep(Duration::from_millis(500));
@@ -272,8 +285,8 @@ async fn test_spawn_same_addr() -> TribResult<()> {
         ready: Some(tx),
         shutdown: None,
     };
-    let _ = spawn_back(cfg);
-    assert_eq!(true, rx.recv_timeout(Duration::from_secs(2))?);
+    let _handle = spawn_back(cfg);
+    assert!(rx.recv_timeout(Duration::from_secs(2))?);
 
     let client = lab1::new_client(format!("http://{}", addr.clone()).as_str()).await?;
     client.set(&kv("hello", "hi")).await?;
@@ -293,10 +306,10 @@ async fn test_back_spawn_new_storage() -> TribResult<()> {
         shutdown: Some(shut_rx),
     };
     let handle = spawn_back(cfg);
-    assert_eq!(true, rx.recv_timeout(Duration::from_secs(2))?);
+    assert!(rx.recv_timeout(Duration::from_secs(2))?);
     let client = lab1::new_client(format!("http://{}", host).as_mut()).await?;
     client.set(&kv("hello", "hi")).await?;
-    let _ = shut_tx.send(()).await?;
+    shut_tx.send(()).await?;
     let _ = handle.await;
     tokio::time::sleep(Duration::from_millis(500)).await;
     let (shut_tx, shut_rx) = tokio::sync::mpsc::channel(1);
@@ -306,8 +319,8 @@ async fn test_back_spawn_new_storage() -> TribResult<()> {
         ready: Some(tx),
         shutdown: Some(shut_rx),
     };
-    let _ = spawn_back(cfg);
-    assert_eq!(true, rx.recv_timeout(Duration::from_secs(2))?);
+    let _handle = spawn_back(cfg);
+    assert!(rx.recv_timeout(Duration::from_secs(2))?);
     assert_eq!(None, client.get("hello").await?);
     let _ = shut_tx.send(()).await;
     Ok(())
@@ -349,12 +362,11 @@ async fn test_shutdown() -> TribResult<()> {
     let _ = shutdown.send(()).await;
     let r = srv.await.unwrap();
     assert!(r.is_ok());
-    match client.get("hello").await {
-        Ok(v) => panic!(
+    if let Ok(v) = client.get("hello").await {
+        panic!(
             "uh oh..somehow the client still completed this request: {:?}",
             v
-        ),
-        Err(_) => (),
+        )
     };
     Ok(())
 }
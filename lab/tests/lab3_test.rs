@@ -0,0 +1,169 @@
+use std::sync::Arc;
+
+use lab::lab3::{ListEntry, ListLog, ListOp, ReplicaSet};
+use tribbler::{
+    err::TribResult,
+    hlc::Hlc,
+    storage::{KeyList, KeyValue, MemStorage, Pattern, Storage},
+};
+
+/// a [Storage] which forwards every call to a shared [MemStorage], so that
+/// several [ReplicaSet]s can be built over the same back-ends
+struct Shared(Arc<MemStorage>);
+
+#[async_trait::async_trait]
+impl tribbler::storage::KeyString for Shared {
+    async fn get(&self, key: &str) -> TribResult<Option<String>> {
+        self.0.get(key).await
+    }
+
+    async fn set(&self, kv: &KeyValue) -> TribResult<bool> {
+        self.0.set(kv).await
+    }
+
+    async fn keys(&self, p: &Pattern) -> TribResult<tribbler::storage::List> {
+        self.0.keys(p).await
+    }
+}
+
+#[async_trait::async_trait]
+impl KeyList for Shared {
+    async fn list_get(&self, key: &str) -> TribResult<tribbler::storage::List> {
+        self.0.list_get(key).await
+    }
+
+    async fn list_append(&self, kv: &KeyValue) -> TribResult<bool> {
+        self.0.list_append(kv).await
+    }
+
+    async fn list_remove(&self, kv: &KeyValue) -> TribResult<u32> {
+        self.0.list_remove(kv).await
+    }
+
+    async fn list_keys(&self, p: &Pattern) -> TribResult<tribbler::storage::List> {
+        self.0.list_keys(p).await
+    }
+}
+
+#[async_trait::async_trait]
+impl Storage for Shared {
+    async fn clock(&self, at_least: u64) -> TribResult<u64> {
+        self.0.clock(at_least).await
+    }
+
+    async fn hlc(&self, at_least: Hlc) -> TribResult<Hlc> {
+        self.0.hlc(at_least).await
+    }
+}
+
+fn replicas(backs: &[Arc<MemStorage>]) -> Vec<Box<dyn Storage>> {
+    backs
+        .iter()
+        .map(|b| Box::new(Shared(b.clone())) as Box<dyn Storage>)
+        .collect()
+}
+
+fn backs(n: usize) -> Vec<Arc<MemStorage>> {
+    (0..n).map(|_| Arc::new(MemStorage::new())).collect()
+}
+
+fn entry(clock: u64, writer: u64, op: ListOp, value: &str) -> ListEntry {
+    ListEntry {
+        clock,
+        writer,
+        op,
+        value: value.to_string(),
+    }
+}
+
+#[test]
+fn test_entry_round_trip() -> TribResult<()> {
+    for v in ["", "a", "a:b", "|;", "::||::", "a|:b"] {
+        let e = entry(42, 3, ListOp::Remove, v);
+        assert_eq!(e, ListEntry::decode(&e.encode())?);
+    }
+    assert!(ListEntry::decode("x:1:1:v").is_err());
+    assert!(ListEntry::decode("a:one:1:v").is_err());
+    assert!(ListEntry::decode("a:1:1").is_err());
+    Ok(())
+}
+
+#[test]
+fn test_log_order_and_dedup() -> TribResult<()> {
+    let a = entry(2, 1, ListOp::Append, "a").encode();
+    let b = entry(1, 2, ListOp::Append, "b").encode();
+    let c = entry(2, 0, ListOp::Append, "c").encode();
+    let mut l1 = ListLog::new();
+    l1.merge(&[a.clone(), b.clone(), a.clone()])?;
+    l1.merge(std::slice::from_ref(&c))?;
+    let mut l2 = ListLog::new();
+    l2.merge(&[c, a])?;
+    l2.merge(&[b])?;
+    assert_eq!(3, l1.len());
+    assert_eq!(vec!["b", "c", "a"], l1.values());
+    assert_eq!(l1.values(), l2.values());
+    Ok(())
+}
+
+#[test]
+fn test_log_tombstone() -> TribResult<()> {
+    let mut log = ListLog::new();
+    log.insert(entry(1, 1, ListOp::Append, "a"));
+    log.insert(entry(2, 1, ListOp::Append, "b"));
+    log.insert(entry(3, 2, ListOp::Append, "a"));
+    log.insert(entry(4, 1, ListOp::Remove, "a"));
+    log.insert(entry(5, 2, ListOp::Append, "a"));
+    assert_eq!(vec!["b", "a"], log.values());
+    assert_eq!(5, log.max_clock());
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_replicas_agree() -> TribResult<()> {
+    let backs = backs(3);
+    let w1 = Arc::new(ReplicaSet::new(replicas(&backs), 1));
+    let mut reversed = replicas(&backs);
+    reversed.reverse();
+    let w2 = Arc::new(ReplicaSet::new(reversed, 2));
+    let mut handles = vec![];
+    for i in 0..10 {
+        let w = if i % 2 == 0 { w1.clone() } else { w2.clone() };
+        handles.push(tokio::spawn(async move {
+            w.list_append(&KeyValue::new("k", &format!("v{}", i))).await
+        }));
+    }
+    for h in handles {
+        assert!(h.await.unwrap()?);
+    }
+    let l1 = w1.list_get("k").await?.0;
+    let l2 = w2.list_get("k").await?.0;
+    assert_eq!(10, l1.len());
+    assert_eq!(l1, l2);
+    // each back-end alone holds the full log
+    for b in backs.iter() {
+        let only = ReplicaSet::new(replicas(std::slice::from_ref(b)), 3);
+        assert_eq!(l1, only.list_get("k").await?.0);
+    }
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_replicas_remove() -> TribResult<()> {
+    let backs = backs(3);
+    let w = ReplicaSet::new(replicas(&backs), 1);
+    let kv = KeyValue::new("k", "x");
+    assert!(w.list_append(&kv).await?);
+    assert!(w.list_append(&KeyValue::new("k", "y")).await?);
+    assert!(w.list_append(&kv).await?);
+    assert_eq!(2, w.list_remove(&kv).await?);
+    assert_eq!(vec!["y"], w.list_get("k").await?.0);
+    assert_eq!(0, w.list_remove(&kv).await?);
+    assert!(w.list_append(&kv).await?);
+    assert_eq!(vec!["y", "x"], w.list_get("k").await?.0);
+
+    assert_eq!(1, w.list_remove(&KeyValue::new("k", "y")).await?);
+    assert_eq!(1, w.list_remove(&kv).await?);
+    assert!(w.list_get("k").await?.0.is_empty());
+    assert!(w.list_keys(&Pattern::default()).await?.0.is_empty());
+    Ok(())
+}
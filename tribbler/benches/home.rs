@@ -0,0 +1,132 @@
+//! Times the writes which maintain the home timeline of a user following
+//! [MAX_FOLLOWING] accounts, against the same writes the way [RefServer]
+//! made them before it kept home buffers: rebuilding the home of the writer
+//! and of each of their followers from all the tribs they follow. A copy of
+//! that rebuild is kept here, run over the same tribs.
+//!
+//! Run with `cargo bench -p tribbler`.
+use std::{
+    collections::HashMap,
+    iter,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use tribbler::{
+    err::TribResult,
+    ref_impl::RefServer,
+    trib::{Server, Trib, MAX_FOLLOWING},
+};
+
+/// Tribs posted by each followed account
+const TRIBS_PER_USER: usize = 20;
+
+/// Times of each benchmark
+const ROUNDS: u32 = 50;
+
+fn report(name: &str, total: Duration) {
+    println!("{:<32} {:>12.3?}/iter", name, total / ROUNDS);
+}
+
+/// The accounts each user follows and is followed by, and the tribs each
+/// user posted
+#[derive(Default)]
+struct Graph {
+    following: HashMap<String, Vec<String>>,
+    followers: HashMap<String, Vec<String>>,
+    tribs: HashMap<String, Vec<Arc<Trib>>>,
+}
+
+impl Graph {
+    /// the home of `who` as [RefServer] used to build it: the tribs of `who`
+    /// and of every account they follow, gathered and sorted
+    fn rebuild_home(&self, who: &str) -> Vec<Arc<Trib>> {
+        let mut home: Vec<Arc<Trib>> = vec![];
+        let following = self.following.get(who).into_iter().flatten();
+        for user in iter::once(who).chain(following.map(String::as_str)) {
+            home.extend(self.tribs.get(user).into_iter().flatten().cloned());
+        }
+        home.sort_by_key(|t| t.clock);
+        home
+    }
+
+    /// what every write by `who` used to cost: rebuilding the homes of `who`
+    /// and of each of their followers
+    fn rebuild_homes(&self, who: &str) -> Vec<Vec<Arc<Trib>>> {
+        let followers = self.followers.get(who).into_iter().flatten();
+        iter::once(who)
+            .chain(followers.map(String::as_str))
+            .map(|u| self.rebuild_home(u))
+            .collect()
+    }
+}
+
+#[tokio::main]
+async fn main() -> TribResult<()> {
+    let server = RefServer::new();
+    server.sign_up("reader").await?;
+    let mut graph = Graph::default();
+    for i in 0..MAX_FOLLOWING {
+        let user = format!("writer{}", i);
+        server.sign_up(&user).await?;
+        for j in 0..TRIBS_PER_USER {
+            server.post(&user, &format!("trib {}", j), 0).await?;
+        }
+        graph.tribs.insert(user.clone(), server.tribs(&user).await?);
+        // leave one slot to follow and unfollow below
+        if i + 1 < MAX_FOLLOWING {
+            server.follow("reader", &user).await?;
+        }
+    }
+    let last = format!("writer{}", MAX_FOLLOWING - 1);
+    let mut following = server.following("reader").await?;
+    following.push(last.clone());
+    graph.following.insert("reader".to_string(), following);
+    graph
+        .followers
+        .insert("writer0".to_string(), server.followers("writer0").await?);
+    println!(
+        "reader follows {} accounts with {} tribs in total",
+        MAX_FOLLOWING,
+        graph.rebuild_home("reader").len()
+    );
+
+    let start = Instant::now();
+    for _ in 0..ROUNDS {
+        graph.rebuild_homes("reader");
+    }
+    report("follow or unfollow (before)", start.elapsed());
+
+    let start = Instant::now();
+    for _ in 0..ROUNDS {
+        graph.rebuild_homes("writer0");
+    }
+    report("post (before)", start.elapsed());
+
+    let (mut follow, mut unfollow) = (Duration::ZERO, Duration::ZERO);
+    for _ in 0..ROUNDS {
+        let start = Instant::now();
+        server.follow("reader", &last).await?;
+        follow += start.elapsed();
+        let start = Instant::now();
+        server.unfollow("reader", &last).await?;
+        unfollow += start.elapsed();
+    }
+    report("follow", follow);
+    report("unfollow", unfollow);
+
+    let start = Instant::now();
+    for i in 0..ROUNDS {
+        server
+            .post("writer0", &format!("new trib {}", i), 0)
+            .await?;
+    }
+    report("post", start.elapsed());
+
+    let start = Instant::now();
+    for _ in 0..ROUNDS {
+        server.home("reader").await?;
+    }
+    report("home", start.elapsed());
+    Ok(())
+}
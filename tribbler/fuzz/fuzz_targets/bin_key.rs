@@ -0,0 +1,19 @@
+//! Checks that two different bins and keys never share a key, and that a key
+//! splits back into its bin and key.
+//!
+//! Run with `cargo +nightly fuzz run bin_key` in `tribbler/`.
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+use tribbler::colon::{bin_key, bin_prefix, split_bin_key};
+
+fuzz_target!(|keys: ((&str, &str), (&str, &str))| {
+    let ((a, k1), (b, k2)) = keys;
+    let key = bin_key(a, k1);
+    assert_eq!((a.to_string(), k1), split_bin_key(&key).unwrap());
+    if (a, k1) != (b, k2) {
+        assert_ne!(key, bin_key(b, k2));
+    }
+    if a != b {
+        assert!(!bin_prefix(b).starts_with(&bin_prefix(a)));
+    }
+});
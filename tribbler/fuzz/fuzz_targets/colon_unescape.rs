@@ -0,0 +1,16 @@
+//! Unescapes arbitrary text, which must never panic, and checks that only
+//! the escaped form of a string unescapes into it.
+//!
+//! Run with `cargo +nightly fuzz run colon_unescape` in `tribbler/`.
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+use tribbler::colon::{escape, try_unescape, unescape};
+
+fuzz_target!(|s: &str| {
+    let lenient = unescape(s);
+    if let Ok(u) = try_unescape(s) {
+        assert_eq!(s, escape(u.as_str()));
+        assert_eq!(u, lenient);
+    }
+    assert_eq!(s, try_unescape(&escape(s)).unwrap());
+});
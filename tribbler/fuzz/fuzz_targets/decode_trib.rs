@@ -0,0 +1,15 @@
+//! Decodes arbitrary text as a tribble, which must never panic, and checks
+//! that whatever decodes encodes back into a value decoding the same.
+//!
+//! Run with `cargo +nightly fuzz run decode_trib` in `tribbler/`.
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+use tribbler::codec::{decode_trib, encode_trib};
+
+fuzz_target!(|s: &str| {
+    if let Ok(trib) = decode_trib(s) {
+        let encoded = encode_trib(&trib);
+        let again = decode_trib(&encoded).expect("re-encoded trib must decode");
+        assert_eq!(encoded, encode_trib(&again));
+    }
+});
@@ -15,15 +15,11 @@ pub fn get_local_addrs() -> TribResult<Vec<IpAddr>> {
 pub fn check(addr: &str) -> TribResult<bool> {
     let addrs = addr.to_socket_addrs()?;
     let local_addrs = get_local_addrs()?;
-    Ok(local_addrs
-        .iter()
-        .position(|&x| {
-            addrs
-                .clone()
-                .map(|y| y.to_string().starts_with(&x.to_string()))
-                .all(|i| i)
-        })
-        .map_or(false, |_| true))
+    Ok(local_addrs.iter().any(|&x| {
+        addrs
+            .clone()
+            .all(|y| y.to_string().starts_with(&x.to_string()))
+    }))
 }
 
 /// module used to generate random ports to use in network addresses
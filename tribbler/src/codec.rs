@@ -0,0 +1,308 @@
+//! module containing a compact, versioned encoding of [Trib]s, for storing
+//! them as the values of a [KeyList](crate::storage::KeyList) or
+//! [KeyString](crate::storage::KeyString).
+//!
+//! An encoded [Trib] starts with the version of its encoding and a `:`.
+//! Version 1 follows with the fields of the [Trib] in this order, each as a
+//! netstring `<length in bytes>:<text>,`:
+//!
+//! `user`, `message`, `time`, `clock`, `id`, `edited`, `reply_to`,
+//! `retrib_of`, `likes`
+//!
+//! Numbers are written in decimal and [None] as an empty text. Since every
+//! text is prefixed with its length, messages may hold any character,
+//! including the `:` and `|` used elsewhere to separate values.
+//!
+//! Decoding is backward compatible: it accepts the JSON written by
+//! `serde_json` before this encoding existed, and version 1 values with
+//! fields missing after `clock` or extra fields after `likes`, which later
+//! additions to version 1 may write.
+//!
+//! ```rust
+//! use tribbler::codec::{decode_trib, encode_trib};
+//! # use tribbler::trib::Trib;
+//! # let trib = Trib {
+//! #     user: "alice".to_string(),
+//! #     message: "a|b:c".to_string(),
+//! #     time: 1650000000,
+//! #     clock: 42,
+//! #     id: 42,
+//! #     edited: None,
+//! #     reply_to: Some(7),
+//! #     retrib_of: None,
+//! #     likes: 0,
+//! # };
+//! let s = encode_trib(&trib);
+//! assert_eq!("1:5:alice,5:a|b:c,10:1650000000,2:42,2:42,0:,1:7,0:,1:0,", s);
+//! assert_eq!("a|b:c", decode_trib(&s)?.message);
+//! # Ok::<(), Box<dyn std::error::Error + Send + Sync>>(())
+//! ```
+use std::{error::Error, fmt::Write, str::FromStr};
+
+use crate::{
+    err::{TribResult, TribblerError},
+    trib::Trib,
+};
+
+/// The version [encode_trib] writes
+pub const TRIB_ENCODING_VERSION: u32 = 1;
+
+/// Encodes a [Trib] in the latest version of the encoding.
+pub fn encode_trib(trib: &Trib) -> String {
+    let mut s = format!("{}:", TRIB_ENCODING_VERSION);
+    let opt = |v: Option<u64>| v.map_or_else(String::new, |v| v.to_string());
+    for field in [
+        trib.user.as_str(),
+        trib.message.as_str(),
+        &trib.time.to_string(),
+        &trib.clock.to_string(),
+        &trib.id.to_string(),
+        &opt(trib.edited),
+        &opt(trib.reply_to),
+        &opt(trib.retrib_of),
+        &trib.likes.to_string(),
+    ] {
+        let _ = write!(s, "{}:{},", field.len(), field);
+    }
+    s
+}
+
+/// Decodes a [Trib] encoded by [encode_trib] in any version, or written as
+/// JSON. Returns [TribblerError::InvalidTribEncoding] when `s` is not an
+/// encoded [Trib], or is in a version newer than [TRIB_ENCODING_VERSION].
+pub fn decode_trib(s: &str) -> TribResult<Trib> {
+    if s.starts_with('{') {
+        return serde_json::from_str(s).map_err(|e| invalid(&e.to_string()));
+    }
+    let (version, rest) = s
+        .split_once(':')
+        .ok_or_else(|| invalid("missing version"))?;
+    match version.parse::<u32>() {
+        Ok(1) => decode_v1(rest),
+        Ok(v) => Err(invalid(&format!("unsupported version {}", v))),
+        Err(_) => Err(invalid(&format!("bad version {:?}", version))),
+    }
+}
+
+fn invalid(reason: &str) -> Box<dyn Error + Send + Sync> {
+    Box::new(TribblerError::InvalidTribEncoding(reason.to_string()))
+}
+
+fn decode_v1(s: &str) -> TribResult<Trib> {
+    let mut fields = Fields(s);
+    let user = fields.text("user")?;
+    let message = fields.text("message")?;
+    let time = fields.number("time")?;
+    let clock = fields.number("clock")?;
+    let id = fields.optional("id")?.unwrap_or_default();
+    let edited = fields.optional("edited")?;
+    let reply_to = fields.optional("reply_to")?;
+    let retrib_of = fields.optional("retrib_of")?;
+    let likes = fields.optional("likes")?.unwrap_or_default();
+    // fields added later are skipped, but must still be well formed
+    while fields.next()?.is_some() {}
+    Ok(Trib {
+        user,
+        message,
+        time,
+        clock,
+        id,
+        edited,
+        reply_to,
+        retrib_of,
+        likes,
+    })
+}
+
+/// The netstrings left to decode
+struct Fields<'a>(&'a str);
+
+impl<'a> Fields<'a> {
+    /// returns the next netstring, or [None] at the end
+    fn next(&mut self) -> TribResult<Option<&'a str>> {
+        if self.0.is_empty() {
+            return Ok(None);
+        }
+        let (len, rest) = self
+            .0
+            .split_once(':')
+            .ok_or_else(|| invalid("missing field length"))?;
+        let len = parse::<usize>(len).ok_or_else(|| invalid("bad field length"))?;
+        match (rest.get(..len), rest.get(len..)) {
+            (Some(field), Some(tail)) if tail.starts_with(',') => {
+                self.0 = &tail[1..];
+                Ok(Some(field))
+            }
+            _ => Err(invalid("field cut short")),
+        }
+    }
+
+    fn text(&mut self, name: &str) -> TribResult<String> {
+        match self.next()? {
+            Some(field) => Ok(field.to_string()),
+            None => Err(invalid(&format!("missing {}", name))),
+        }
+    }
+
+    fn number(&mut self, name: &str) -> TribResult<u64> {
+        match self.optional(name)? {
+            Some(v) => Ok(v),
+            None => Err(invalid(&format!("missing {}", name))),
+        }
+    }
+
+    /// returns [None] for an empty or missing field
+    fn optional(&mut self, name: &str) -> TribResult<Option<u64>> {
+        match self.next()? {
+            None | Some("") => Ok(None),
+            Some(field) => match parse(field) {
+                Some(v) => Ok(Some(v)),
+                None => Err(invalid(&format!("bad {}", name))),
+            },
+        }
+    }
+}
+
+/// parses a number written only in decimal digits
+fn parse<T: FromStr>(s: &str) -> Option<T> {
+    match !s.is_empty() && s.bytes().all(|b| b.is_ascii_digit()) {
+        true => s.parse().ok(),
+        false => None,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use proptest::prelude::*;
+
+    use super::{decode_trib, encode_trib};
+    use crate::{err::TribblerError, trib::Trib};
+
+    fn all_fields(t: &Trib) -> serde_json::Value {
+        serde_json::to_value(t).unwrap()
+    }
+
+    fn tribs() -> impl Strategy<Value = Trib> {
+        (
+            ("[a-z0-9]{1,15}", any::<String>(), "[:|,0-9a-z]{0,20}"),
+            (any::<u64>(), any::<u64>(), any::<u64>(), any::<u64>()),
+            (
+                any::<Option<u64>>(),
+                any::<Option<u64>>(),
+                any::<Option<u64>>(),
+            ),
+        )
+            .prop_map(
+                |((user, text, seps), (time, clock, id, likes), (edited, reply_to, retrib_of))| {
+                    Trib {
+                        user,
+                        message: text + &seps,
+                        time,
+                        clock,
+                        id,
+                        edited,
+                        reply_to,
+                        retrib_of,
+                        likes,
+                    }
+                },
+            )
+    }
+
+    proptest! {
+        #[test]
+        fn round_trip(trib in tribs()) {
+            let decoded = decode_trib(&encode_trib(&trib)).unwrap();
+            prop_assert_eq!(all_fields(&trib), all_fields(&decoded));
+        }
+
+        #[test]
+        fn distinct_encodings(a in tribs(), b in tribs()) {
+            prop_assume!(all_fields(&a) != all_fields(&b));
+            prop_assert_ne!(encode_trib(&a), encode_trib(&b));
+        }
+
+        #[test]
+        fn decodes_json(trib in tribs()) {
+            let decoded = decode_trib(&serde_json::to_string(&trib).unwrap()).unwrap();
+            prop_assert_eq!(all_fields(&trib), all_fields(&decoded));
+        }
+
+        #[test]
+        fn never_panics(s in any::<String>()) {
+            let _ = decode_trib(&s);
+        }
+
+        #[test]
+        fn cut_short(trib in tribs(), cut in any::<prop::sample::Index>()) {
+            let s = encode_trib(&trib);
+            let n = cut.index(s.len());
+            // cutting a whole number of trailing fields is allowed
+            if let (Some(head), Ok(decoded)) = (s.get(..n), decode_trib(s.get(..n).unwrap_or(""))) {
+                prop_assert!(head.ends_with(','));
+                prop_assert_eq!(&trib.message, &decoded.message);
+            }
+        }
+    }
+
+    #[test]
+    fn messages_with_separators() {
+        for message in ["", ":", "|", "1:a,", "a|b:c", "::||,,", "é:😀|"] {
+            let trib = Trib {
+                user: "alice".to_string(),
+                message: message.to_string(),
+                time: 0,
+                clock: 1,
+                id: 1,
+                edited: Some(2),
+                reply_to: None,
+                retrib_of: Some(0),
+                likes: 3,
+            };
+            let decoded = decode_trib(&encode_trib(&trib)).unwrap();
+            assert_eq!(all_fields(&trib), all_fields(&decoded));
+        }
+    }
+
+    #[test]
+    fn older_and_newer_fields() {
+        // a version 1 value written before `likes` existed
+        let t = decode_trib("1:3:bob,2:hi,1:5,1:6,1:6,0:,0:,0:,").unwrap();
+        assert_eq!(("bob", 6, 0), (t.user.as_str(), t.id, t.likes));
+        // with only the fields every version 1 value has
+        let t = decode_trib("1:3:bob,2:hi,1:5,1:6,").unwrap();
+        assert_eq!((5, 6, 0, None), (t.time, t.clock, t.id, t.reply_to));
+        // with a field added after `likes`
+        let t = decode_trib("1:3:bob,2:hi,1:5,1:6,1:6,0:,0:,0:,1:2,3:new,").unwrap();
+        assert_eq!(2, t.likes);
+    }
+
+    #[test]
+    fn invalid() {
+        for s in [
+            "",
+            "1",
+            "2:3:bob,2:hi,1:5,1:6,",
+            "x:3:bob,",
+            "1:3:bob,2:hi,1:5,",
+            "1:3:bob,2:hi,1:5,2:6,",
+            "1:3:bob,2:hi,1:5,1:6,1:6",
+            "1:3:bob,2:hi,2:+5,1:6,",
+            "1:3:bob,2:hi,1:5,1:6,1:x,",
+            "1:4:bob,",
+            "1:2:é,",
+            "{\"user\":",
+        ] {
+            assert!(decode_trib(s).is_err(), "{:?}", s);
+        }
+        let err = decode_trib("2:").unwrap_err();
+        assert_eq!(
+            "invalid trib encoding: unsupported version 2",
+            err.to_string()
+        );
+        assert!(matches!(
+            err.downcast_ref::<TribblerError>(),
+            Some(TribblerError::InvalidTribEncoding(_))
+        ));
+    }
+}
@@ -1,4 +1,11 @@
 //! module with functions to escape and unescape strings used in trib
+//!
+//! An escaped string holds no `:`, so `:` is free to separate escaped values,
+//! like the name of a bin from the keys in it. See [bin_key].
+use crate::err::{TribResult, TribblerError};
+
+/// Separates the escaped name of a bin from a key in it. See [bin_key].
+pub const BIN_SEPARATOR: &str = "::";
 
 /// Escapes a string with backslashes and colons
 ///
@@ -13,6 +20,10 @@ pub fn escape<T: Into<String>>(s: T) -> String {
 
 /// Unescapes a string with the values used in [escape]. See [escape] for
 /// examples.
+///
+/// Malformed escape sequences are kept as they are, which may unescape two
+/// strings into the same one. Use [try_unescape] for strings which may not
+/// come from [escape].
 pub fn unescape<T: Into<String>>(s: T) -> String {
     let mut out = vec![];
     let mut escaping = false;
@@ -38,9 +49,68 @@ pub fn unescape<T: Into<String>>(s: T) -> String {
     out.into_iter().collect()
 }
 
+/// Unescapes a string produced by [escape]. Unlike [unescape], returns
+/// [TribblerError::InvalidEscape] when `s` holds a `:`, or a `|` which does
+/// not start `||` or `|;`, so that only [escape] can produce a string this
+/// accepts.
+///
+/// ```rust
+/// use tribbler::colon::*;
+/// assert_eq!("a:b", try_unescape("a|;b").unwrap());
+/// assert!(try_unescape("a|b").is_err());
+/// assert!(try_unescape("a:b").is_err());
+/// ```
+pub fn try_unescape(s: &str) -> TribResult<String> {
+    let invalid = || Box::new(TribblerError::InvalidEscape(s.to_string()));
+    let mut out = String::with_capacity(s.len());
+    let mut chars = s.chars();
+    while let Some(x) = chars.next() {
+        match x {
+            '|' => match chars.next() {
+                Some(';') => out.push(':'),
+                Some('|') => out.push('|'),
+                _ => return Err(invalid()),
+            },
+            ':' => return Err(invalid()),
+            _ => out.push(x),
+        }
+    }
+    Ok(out)
+}
+
+/// Returns the prefix of the keys of the bin `name`: the escaped name followed
+/// by [BIN_SEPARATOR]. Since escaped names hold no `:`, the prefix of one bin
+/// is never a prefix of the keys of another.
+pub fn bin_prefix<T: Into<String>>(name: T) -> String {
+    escape(name) + BIN_SEPARATOR
+}
+
+/// Returns the key storing `key` of the bin `name` in a store shared by many
+/// bins. Different bins and keys never share a key.
+///
+/// ```rust
+/// use tribbler::colon::*;
+/// assert_eq!("a|;b::c:d", bin_key("a:b", "c:d"));
+/// assert_eq!(("a:b".to_string(), "c:d"), split_bin_key("a|;b::c:d").unwrap());
+/// ```
+pub fn bin_key(name: &str, key: &str) -> String {
+    bin_prefix(name) + key
+}
+
+/// Splits a key produced by [bin_key] into the name of its bin and its key in
+/// the bin. Returns [TribblerError::InvalidEscape] for other strings.
+pub fn split_bin_key(s: &str) -> TribResult<(String, &str)> {
+    match s.split_once(BIN_SEPARATOR) {
+        Some((name, key)) => Ok((try_unescape(name)?, key)),
+        None => Err(Box::new(TribblerError::InvalidEscape(s.to_string()))),
+    }
+}
+
 #[cfg(test)]
 mod test {
-    use super::{escape, unescape};
+    use proptest::prelude::*;
+
+    use super::{bin_key, bin_prefix, escape, split_bin_key, try_unescape, unescape};
 
     fn check(s: &str) {
         assert_eq!(unescape(escape(s)), s);
@@ -78,4 +148,63 @@ mod test {
     fn t8() {
         check("::||::||;;||;;||;:");
     }
+
+    #[test]
+    fn strict() {
+        for s in ["|", "a|", "|:", "|a", "a:b", "|||", "::"] {
+            assert!(try_unescape(s).is_err(), "{:?}", s);
+        }
+        // what the lenient unescape makes of them
+        assert_eq!("a", unescape("|a"));
+        assert_eq!("a", unescape("a|"));
+        assert!(split_bin_key("alice").is_err());
+        assert!(split_bin_key("a|b::key").is_err());
+        assert_eq!(
+            ("alice".to_string(), "a::b"),
+            split_bin_key("alice::a::b").unwrap()
+        );
+    }
+
+    /// strings heavy in the characters escaping handles
+    fn texts() -> impl Strategy<Value = String> {
+        prop_oneof!["[:|;a]{0,12}", any::<String>()]
+    }
+
+    proptest! {
+        #[test]
+        fn round_trip(s in texts()) {
+            prop_assert_eq!(&s, &try_unescape(&escape(s.as_str())).unwrap());
+            prop_assert_eq!(&s, &unescape(escape(s.as_str())));
+            prop_assert!(!escape(s.as_str()).contains(':'));
+        }
+
+        #[test]
+        fn only_escaped_unescape(s in texts()) {
+            // so no two strings unescape into the same one
+            if let Ok(u) = try_unescape(&s) {
+                prop_assert_eq!(&s, &escape(u.as_str()));
+                prop_assert_eq!(u, unescape(s.as_str()));
+            }
+        }
+
+        #[test]
+        fn no_collisions(a in texts(), b in texts()) {
+            prop_assume!(a != b);
+            prop_assert_ne!(escape(a.as_str()), escape(b.as_str()));
+            // bin prefixes are prefix-free
+            prop_assert!(!bin_prefix(b.as_str()).starts_with(&bin_prefix(a.as_str())));
+        }
+
+        #[test]
+        fn no_bin_key_collisions(a in texts(), k1 in texts(), b in texts(), k2 in texts()) {
+            prop_assume!((&a, &k1) != (&b, &k2));
+            prop_assert_ne!(bin_key(&a, &k1), bin_key(&b, &k2));
+        }
+
+        #[test]
+        fn split_bin_keys(name in texts(), key in texts()) {
+            let k = bin_key(&name, &key);
+            prop_assert_eq!((name, key.as_str()), split_bin_key(&k).unwrap());
+        }
+    }
 }
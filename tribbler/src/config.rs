@@ -12,6 +12,7 @@ use tokio::sync::mpsc::Receiver;
 
 use crate::err::TribResult;
 use crate::storage::Storage;
+use crate::trib::TribLimits;
 
 pub const DEFAULT_CONFIG_LOCATION: &str = "bins.json";
 
@@ -60,15 +61,18 @@ pub struct KeeperConfig {
 
 impl KeeperConfig {
     pub fn addr(&self) -> &str {
-        &self.addrs[self.this as usize]
+        &self.addrs[self.this]
     }
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone, Default)]
-/// A config file defining the backend and keeper network addresses
+/// A config file defining the backend and keeper network addresses, and the
+/// limits of the tribbler service
 pub struct Config {
     pub backs: Vec<String>,
     pub keepers: Vec<String>,
+    #[serde(default)]
+    pub limits: TribLimits,
 }
 
 impl Config {
@@ -104,7 +108,7 @@ impl Config {
         };
         let mut contents = serde_json::to_vec_pretty(&self)?;
         contents.append(&mut "\n".as_bytes().to_vec());
-        let _ = handle.write_all(&contents)?;
+        handle.write_all(&contents)?;
         Ok(())
     }
 
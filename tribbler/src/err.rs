@@ -12,6 +12,14 @@ pub enum TribblerError {
     UsernameTaken(String),
     /// when a username is invalid in any way
     InvalidUsername(String),
+    /// when a username is confusable with the name of another user, under
+    /// the Unicode username policy of [crate::trib::TribLimits]
+    UsernameConfusable(String, String),
+    /// raised when a stored value is not a [crate::trib::Trib] encoded by
+    /// [crate::codec]
+    InvalidTribEncoding(String),
+    /// raised when a string is not one [crate::colon::escape] produces
+    InvalidEscape(String),
     /// generic error for anything that occurs with RPC communication
     RpcError(String),
     /// raised when too a user tries to follow more than
@@ -21,12 +29,58 @@ pub enum TribblerError {
     AlreadyFollowing(String, String),
     /// raised when a user tries to unfollow a user they are not following
     NotFollowing(String, String),
+    /// raised when a user tries to follow or message a user who blocked them
+    Blocked(String, String),
+    /// raised when a user tries to block a user they are already blocking
+    AlreadyBlocked(String, String),
+    /// raised when a user tries to unblock a user they are not blocking
+    NotBlocked(String, String),
+    /// raised when a user tries to mute a user they are already muting
+    AlreadyMuted(String, String),
+    /// raised when a user tries to unmute a user they are not muting
+    NotMuted(String, String),
     /// raised when a trib message exceeds [crate::trib::MAX_TRIB_LEN]
     TribTooLong,
+    /// raised when a display name is empty or exceeds
+    /// [crate::trib::MAX_DISPLAY_NAME_LEN]
+    InvalidDisplayName(String),
+    /// raised when a profile bio exceeds [crate::trib::MAX_BIO_LEN]
+    BioTooLong,
+    /// raised when a direct message exceeds [crate::trib::MAX_DM_LEN]
+    DmTooLong,
+    /// raised when a user tries to message a user who they do not follow
+    /// each other with
+    NotMutualFollowers(String, String),
+    /// raised when a user has no trib with the given clock, or it was deleted
+    TribDoesNotExist(String, u64),
+    /// raised when there is no trib with the given id, or it was deleted
+    TribIdDoesNotExist(u64),
+    /// raised when a user tries to retrib a trib they already retribbed
+    AlreadyRetribbed(String, u64),
+    /// raised when a user tries to like a trib they already liked
+    AlreadyLiked(String, u64),
+    /// raised when a user tries to edit a retrib, whose message is the one of
+    /// the original trib
+    RetribNotEditable(String, u64),
+    /// when a hashtag is invalid in any way
+    InvalidTag(String),
     /// when someone tries to follow or check if a user is following themselves
     WhoWhom(String),
     /// when there are no more seq numbers to give out
     MaxedSeq,
+    /// raised when a client performs an operation more often than it is
+    /// allowed to
+    RateLimited(String),
+    /// raised when a password is shorter than the front-end allows
+    PasswordTooShort(usize),
+    /// raised when a username and password do not match a stored credential
+    InvalidCredentials,
+    /// raised when an operation requires a signed in session and there is none
+    NotSignedIn,
+    /// raised when a session tries to act as a user other than its own
+    NotSignedInAs(String),
+    /// raised when a [crate::trib::Server] does not implement an operation
+    NotSupported(String),
     /// catch-all error for other issues
     Unknown(String),
 }
@@ -37,14 +91,55 @@ impl Display for TribblerError {
             TribblerError::UserDoesNotExist(x) => format!("user \"{}\" does not exist", x),
             TribblerError::UsernameTaken(x) => format!("username \"{}\" already taken", x),
             TribblerError::InvalidUsername(x) => format!("username \"{}\" is invalid", x),
+            TribblerError::UsernameConfusable(x, y) => {
+                format!("username \"{}\" is confusable with \"{}\"", x, y)
+            }
+            TribblerError::InvalidTribEncoding(x) => format!("invalid trib encoding: {}", x),
+            TribblerError::InvalidEscape(x) => format!("invalid escaped string \"{}\"", x),
             TribblerError::RpcError(x) => format!("rpc error: {}", x),
             TribblerError::FollowingTooMany => "following too many users".to_string(),
             TribblerError::AlreadyFollowing(who, whom) => {
                 format!("{} already following {}", who, whom)
             }
             TribblerError::NotFollowing(who, whom) => format!("{} doesn't follow {}", who, whom),
+            TribblerError::Blocked(who, whom) => format!("{} is blocked by {}", who, whom),
+            TribblerError::AlreadyBlocked(who, whom) => {
+                format!("{} already blocking {}", who, whom)
+            }
+            TribblerError::NotBlocked(who, whom) => format!("{} isn't blocking {}", who, whom),
+            TribblerError::AlreadyMuted(who, whom) => format!("{} already muting {}", who, whom),
+            TribblerError::NotMuted(who, whom) => format!("{} isn't muting {}", who, whom),
             TribblerError::TribTooLong => "tribbler post exceed character limit".to_string(),
+            TribblerError::InvalidDisplayName(x) => format!("display name \"{}\" is invalid", x),
+            TribblerError::BioTooLong => "profile bio exceed character limit".to_string(),
+            TribblerError::DmTooLong => "direct message exceed character limit".to_string(),
+            TribblerError::NotMutualFollowers(from, to) => {
+                format!("{} and {} don't follow each other", from, to)
+            }
+            TribblerError::TribDoesNotExist(user, clock) => {
+                format!("trib {} of user \"{}\" does not exist", clock, user)
+            }
+            TribblerError::TribIdDoesNotExist(id) => format!("trib {} does not exist", id),
+            TribblerError::AlreadyRetribbed(who, id) => {
+                format!("{} already retribbed trib {}", who, id)
+            }
+            TribblerError::AlreadyLiked(who, id) => format!("{} already liked trib {}", who, id),
+            TribblerError::RetribNotEditable(who, clock) => {
+                format!(
+                    "trib {} of {} is a retrib, which can't be edited",
+                    clock, who
+                )
+            }
+            TribblerError::InvalidTag(x) => format!("tag \"{}\" is invalid", x),
             TribblerError::WhoWhom(x) => format!("user {} can't follow themself", x),
+            TribblerError::RateLimited(x) => format!("too many {} requests", x),
+            TribblerError::PasswordTooShort(x) => {
+                format!("password must be at least {} characters", x)
+            }
+            TribblerError::InvalidCredentials => "invalid username or password".to_string(),
+            TribblerError::NotSignedIn => "not signed in".to_string(),
+            TribblerError::NotSignedInAs(x) => format!("not signed in as {}", x),
+            TribblerError::NotSupported(x) => format!("{} is not supported", x),
             TribblerError::Unknown(x) => format!("unknown error: {}", x),
             x => format!("{:?}", x),
         };
@@ -67,7 +162,7 @@ impl From<tonic::transport::Error> for TribblerError {
 }
 
 /// A [Result] type which either returns `T` or a [boxed error](https://doc.rust-lang.org/rust-by-example/error/multiple_error_types/boxing_errors.html)
-pub type TribResult<T> = Result<T, Box<(dyn Error + Send + Sync)>>;
+pub type TribResult<T> = Result<T, Box<dyn Error + Send + Sync>>;
 
 impl From<Box<dyn Error>> for TribblerError {
     fn from(x: Box<dyn Error>) -> Self {
@@ -0,0 +1,144 @@
+//! module containing a hybrid logical clock, which combines the physical time
+//! of a machine with a logical counter.
+//!
+//! A hybrid logical clock (HLC) timestamp is packed into a `u64`: the upper
+//! 48 bits hold milliseconds since the UNIX epoch, and the lower
+//! [LOGICAL_BITS] bits count events within the same millisecond. Like a
+//! logical clock, an HLC never goes backwards and moves past every timestamp
+//! it receives; unlike one, it stays close to real time, so timestamps from
+//! front-ends which rarely talk to each other still sort in about the order
+//! the events happened. Being a plain `u64`, an HLC timestamp can be used as
+//! the `clock` of a [Trib](crate::trib::Trib).
+use std::{
+    fmt::Display,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use serde::{Deserialize, Serialize};
+
+/// Count of the low bits of an [Hlc] holding the logical counter
+pub const LOGICAL_BITS: u32 = 16;
+
+const LOGICAL_MASK: u64 = (1 << LOGICAL_BITS) - 1;
+
+/// A hybrid logical clock timestamp. Ordering timestamps orders them by
+/// physical time first, and then by logical counter.
+///
+/// ```rust
+/// use tribbler::hlc::Hlc;
+/// let last = Hlc::new(1000, 7);
+/// // the physical time fell behind: count on from the last timestamp
+/// assert_eq!(Hlc::new(1000, 8), last.tick(Hlc::new(990, 0)));
+/// // the physical time moved on: follow it
+/// assert_eq!(Hlc::new(1001, 0), last.tick(Hlc::new(1001, 0)));
+/// ```
+#[derive(
+    Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash,
+)]
+#[serde(transparent)]
+pub struct Hlc(u64);
+
+impl Hlc {
+    /// Creates an [Hlc] from milliseconds since the UNIX epoch and a logical
+    /// counter. Bits of `physical` beyond 48 are dropped.
+    pub fn new(physical: u64, logical: u16) -> Hlc {
+        Hlc((physical << LOGICAL_BITS) | logical as u64)
+    }
+
+    /// Returns the [Hlc] of the current physical time, with a zero logical
+    /// counter.
+    pub fn now() -> Hlc {
+        let ms = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_millis() as u64);
+        Hlc::new(ms, 0)
+    }
+
+    /// the milliseconds since the UNIX epoch
+    pub fn physical(&self) -> u64 {
+        self.0 >> LOGICAL_BITS
+    }
+
+    /// the logical counter
+    pub fn logical(&self) -> u16 {
+        (self.0 & LOGICAL_MASK) as u16
+    }
+
+    /// Returns the timestamp of an event following the one stamped with this
+    /// [Hlc], at the physical time `now`: `now` when it is later, and the next
+    /// logical count of this timestamp otherwise. A logical counter which
+    /// runs out carries into the physical time. Saturates at [u64::MAX].
+    pub fn tick(self, now: Hlc) -> Hlc {
+        Hlc(now.0.max(self.0.saturating_add(1)))
+    }
+
+    /// Returns the timestamp of receiving a message stamped with `received`
+    /// at the physical time `now`, when the last event was stamped with this
+    /// [Hlc]. The result is larger than both timestamps.
+    pub fn update(self, received: Hlc, now: Hlc) -> Hlc {
+        self.max(received).tick(now)
+    }
+}
+
+impl From<u64> for Hlc {
+    fn from(v: u64) -> Self {
+        Hlc(v)
+    }
+}
+
+impl From<Hlc> for u64 {
+    fn from(h: Hlc) -> Self {
+        h.0
+    }
+}
+
+impl Display for Hlc {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}.{}", self.physical(), self.logical())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Hlc, LOGICAL_BITS};
+
+    #[test]
+    fn fields() {
+        let h = Hlc::new(1_650_000_000_000, 42);
+        assert_eq!(1_650_000_000_000, h.physical());
+        assert_eq!(42, h.logical());
+        assert_eq!(h, Hlc::from(u64::from(h)));
+        assert_eq!("1650000000000.42", h.to_string());
+        assert_eq!(1 << LOGICAL_BITS, u64::from(Hlc::new(1, 0)));
+        assert!(Hlc::new(1, u16::MAX) < Hlc::new(2, 0));
+    }
+
+    #[test]
+    fn tick() {
+        let h = Hlc::new(10, u16::MAX);
+        assert_eq!(Hlc::new(11, 0), h.tick(Hlc::new(3, 0)));
+        assert_eq!(
+            Hlc::from(u64::MAX),
+            Hlc::from(u64::MAX).tick(Hlc::new(3, 0))
+        );
+        let now = Hlc::now();
+        assert!(now.physical() > 1_600_000_000_000);
+        assert!(Hlc::default().tick(now) == now);
+    }
+
+    #[test]
+    fn update() {
+        let last = Hlc::new(10, 0);
+        // a message from a front-end whose clock runs ahead
+        assert_eq!(
+            Hlc::new(20, 6),
+            last.update(Hlc::new(20, 5), Hlc::new(11, 0))
+        );
+        // a message from the past
+        assert_eq!(
+            Hlc::new(12, 0),
+            last.update(Hlc::new(5, 9), Hlc::new(12, 0))
+        );
+        assert_eq!(Hlc::new(10, 1), last.update(Hlc::new(5, 9), Hlc::new(9, 0)));
+    }
+}
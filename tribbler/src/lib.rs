@@ -7,11 +7,14 @@
 //! or a particular entity needs to be modified, please consult with a TA or
 //! instructor first.
 pub mod addr;
+pub mod codec;
 pub mod colon;
 pub mod config;
 pub mod err;
+pub mod hlc;
 pub mod ref_impl;
 /// protobuf-generated RPC stubs and message structs
 pub mod rpc;
 pub mod storage;
+pub mod timeline;
 pub mod trib;
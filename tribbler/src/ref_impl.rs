@@ -3,26 +3,45 @@
 #![allow(dead_code)]
 use std::{
     cmp::{min, Ordering},
-    collections::{HashMap, HashSet},
+    collections::{BTreeSet, HashMap, HashSet, VecDeque},
+    fs::{File, OpenOptions},
+    io::{Read, Write},
+    path::Path,
     sync::{
         atomic::{self, AtomicU64},
-        Arc, RwLock,
+        Arc, Mutex, RwLock,
     },
+    time::SystemTime,
 };
 
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use tokio::sync::broadcast;
+
 use crate::{
     err::{TribResult, TribblerError},
-    trib::{is_valid_username, Server, Trib, MAX_TRIB_FETCH, MAX_TRIB_LEN, MIN_LIST_USER},
+    hlc::Hlc,
+    timeline,
+    trib::{
+        text_len, tokenize, username_skeleton, DirectMessage, PostReceiver, Posted, Profile,
+        Server, TagCount, Trib, TribLimits,
+    },
 };
 
 /// The [User] type holds the data on tribs the user has posted along with
 /// related follower information.
 #[derive(Debug)]
 struct User {
+    profile: Profile,
     following: HashSet<String>,
     followers: HashSet<String>,
+    blocking: HashSet<String>,
+    muting: HashSet<String>,
     seq_tribs: Vec<SeqTrib>,
     tribs: Vec<Arc<Trib>>,
+    tombstones: HashSet<u64>,
+    inbox: Vec<Arc<DirectMessage>>,
+    credential: Option<String>,
 }
 
 /// A [Trib] type with an additional sequence number
@@ -32,34 +51,20 @@ struct SeqTrib {
     trib: Arc<Trib>,
 }
 
-impl Ord for SeqTrib {
-    fn cmp(&self, other: &Self) -> Ordering {
-        self.seq.cmp(&other.seq)
-    }
-}
-
-impl Eq for SeqTrib {}
-
-impl PartialOrd for SeqTrib {
-    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        self.seq.partial_cmp(&other.seq)
-    }
-}
-
-impl PartialEq for SeqTrib {
-    fn eq(&self, other: &Self) -> bool {
-        self.seq == other.seq
-    }
-}
-
 impl User {
-    /// creates a new user reference
-    fn new() -> User {
+    /// creates a new user reference with the given [Profile]
+    fn new(profile: Profile) -> User {
         User {
+            profile,
             following: HashSet::new(),
             followers: HashSet::new(),
+            blocking: HashSet::new(),
+            muting: HashSet::new(),
             seq_tribs: vec![],
             tribs: vec![],
+            tombstones: HashSet::new(),
+            inbox: vec![],
+            credential: None,
         }
     }
 
@@ -88,20 +93,48 @@ impl User {
         self.followers.remove(who);
     }
 
+    /// Checks whether this user is blocking `whom`
+    fn is_blocking(&self, whom: &str) -> bool {
+        self.blocking.contains(whom)
+    }
+
+    /// Checks whether this user is muting `whom`
+    fn is_muting(&self, whom: &str) -> bool {
+        self.muting.contains(whom)
+    }
+
     /// lists the [User]s that this user follows
     fn list_following(&self) -> Vec<String> {
         self.following.iter().map(String::clone).collect()
     }
 
+    /// lists the [User]s that follow this user
+    fn list_followers(&self) -> Vec<String> {
+        self.followers.iter().map(String::clone).collect()
+    }
+
     /// instructs this [User] to post a new [Trib] with the given parameters
     /// returns a reference to the posted [Trib]
-    fn post(&mut self, who: &str, msg: &str, seq: u64, time: u64) -> Arc<Trib> {
+    fn post(
+        &mut self,
+        who: &str,
+        msg: &str,
+        seq: u64,
+        time: u64,
+        reply_to: Option<u64>,
+        retrib_of: Option<u64>,
+    ) -> Arc<Trib> {
         // make the new trib
         let trib = Arc::new(Trib {
             user: who.to_string(),
             message: msg.to_string(),
             time,
             clock: seq,
+            id: seq,
+            edited: None,
+            reply_to,
+            retrib_of,
+            likes: 0,
         });
         // append sequential number
         let seq_trib = SeqTrib {
@@ -115,17 +148,403 @@ impl User {
         trib
     }
 
-    /// Gets the list of [Trib]s posted by this [User]
-    fn list_tribs(&self) -> &[Arc<Trib>] {
+    /// finds the [Trib] with the given id among the ones this [User] posted
+    fn get_trib(&self, id: u64) -> Option<Arc<Trib>> {
+        let i = self.tribs.binary_search_by_key(&id, |t| t.id).ok()?;
+        Some(self.tribs[i].clone())
+    }
+
+    /// checks whether this [User] has a live [Trib] posted at `clock`
+    fn has_trib(&self, clock: u64) -> bool {
+        self.trib_at(clock).is_some()
+    }
+
+    /// finds the live [Trib] this [User] posted at `clock`
+    fn trib_at(&self, clock: u64) -> Option<&Arc<Trib>> {
+        let i = self.tribs.binary_search_by_key(&clock, |t| t.clock).ok()?;
+        Some(&self.tribs[i])
+    }
+
+    /// deletes the [Trib] posted at `clock`, leaving a tombstone in its place.
+    /// returns false when there is no such [Trib]
+    fn delete(&mut self, clock: u64) -> bool {
+        match self.tribs.binary_search_by_key(&clock, |t| t.clock) {
+            Ok(i) => {
+                self.tribs.remove(i);
+                self.tombstones.insert(clock);
+                true
+            }
+            Err(_) => false,
+        }
+    }
+
+    /// replaces the message of the [Trib] posted at `clock`
+    /// returns a reference to the edited [Trib], or [None] if there is none
+    fn edit(&mut self, clock: u64, msg: &str, time: u64) -> Option<Arc<Trib>> {
+        self.replace(clock, |t| Trib {
+            message: msg.to_string(),
+            edited: Some(time),
+            ..t.clone()
+        })
+    }
+
+    /// replaces the [Trib] posted at `clock` with the result of `f`
+    /// returns a reference to the new [Trib], or [None] if there is none
+    fn replace<F: FnOnce(&Trib) -> Trib>(&mut self, clock: u64, f: F) -> Option<Arc<Trib>> {
+        let i = self.tribs.binary_search_by_key(&clock, |t| t.clock).ok()?;
+        let trib = Arc::new(f(&self.tribs[i]));
+        self.tribs[i] = trib.clone();
+        if let Ok(j) = self.seq_tribs.binary_search_by_key(&clock, |t| t.seq) {
+            self.seq_tribs[j].trib = trib.clone();
+        }
+        Some(trib)
+    }
+
+    /// the timeline of the [Trib]s of this [User] which have not been
+    /// deleted, with a sequence number from `from` up to but excluding `to`
+    fn live_tribs(&self, from: u64, to: u64) -> impl DoubleEndedIterator<Item = Arc<Trib>> + '_ {
+        let start = self.seq_tribs.partition_point(|t| t.seq < from);
+        let end = self.seq_tribs.partition_point(|t| t.seq < to);
+        self.seq_tribs[start..end.max(start)]
+            .iter()
+            .filter(|t| !self.tombstones.contains(&t.seq))
+            .map(|t| t.trib.clone())
+    }
+
+    /// Gets the list of the `n` most recent [DirectMessage]s sent to this
+    /// [User]
+    fn list_inbox(&self, n: usize) -> &[Arc<DirectMessage>] {
+        let start = self.inbox.len().saturating_sub(n);
+        &self.inbox[start..]
+    }
+
+    /// Gets the list of the `n` most recent [Trib]s posted by this [User]
+    fn list_tribs(&self, n: usize) -> &[Arc<Trib>] {
         let ntrib = self.tribs.len();
-        let start = match ntrib.cmp(&MAX_TRIB_FETCH) {
-            Ordering::Greater => ntrib - MAX_TRIB_FETCH,
+        let start = match ntrib.cmp(&n) {
+            Ordering::Greater => ntrib - n,
             _ => 0,
         };
         &self.tribs[start..]
     }
 }
 
+/// Secondary indexes over posted [Trib]s, keyed by [Trib] id. Deleted and
+/// edited [Trib]s are not removed from the indexes; entries are checked
+/// against the current [Trib] when they are read.
+#[derive(Debug, Default)]
+struct TribIndex {
+    /// the author of each [Trib]
+    authors: HashMap<u64, String>,
+    /// the ids of the direct replies to each [Trib]
+    replies: HashMap<u64, BTreeSet<u64>>,
+    /// the ids of the retribs of each [Trib]
+    retribs: HashMap<u64, BTreeSet<u64>>,
+    /// the users who liked each [Trib]
+    likes: HashMap<u64, HashSet<String>>,
+    /// the ids of the [Trib]s mentioning each user
+    mentions: HashMap<String, BTreeSet<u64>>,
+    /// the ids of the [Trib]s carrying each tag
+    tags: HashMap<String, BTreeSet<u64>>,
+    /// the ids of the [Trib]s containing each word
+    words: HashMap<String, BTreeSet<u64>>,
+    /// the ids of the last [TribLimits::trending_window] [Trib]s posted,
+    /// oldest first
+    recent: VecDeque<u64>,
+}
+
+impl TribIndex {
+    /// records a newly posted [Trib]
+    fn add(&mut self, trib: &Trib, users: &HashMap<String, User>, limits: &TribLimits) {
+        self.authors.insert(trib.id, trib.user.clone());
+        if let Some(parent) = trib.reply_to {
+            self.replies.entry(parent).or_default().insert(trib.id);
+        }
+        self.recent.push_back(trib.id);
+        while self.recent.len() > limits.trending_window {
+            self.recent.pop_front();
+        }
+        match trib.retrib_of {
+            // a retrib is found through its original [Trib]
+            Some(orig) => {
+                self.retribs.entry(orig).or_default().insert(trib.id);
+            }
+            None => self.add_message(trib, users, limits),
+        }
+    }
+
+    /// records the existing users mentioned by a [Trib], its tags and words
+    fn add_message(&mut self, trib: &Trib, users: &HashMap<String, User>, limits: &TribLimits) {
+        for name in limits.parse_mentions(&trib.message) {
+            if users.contains_key(&name) {
+                self.mentions.entry(name).or_default().insert(trib.id);
+            }
+        }
+        for tag in limits.parse_tags(&trib.message) {
+            self.tags.entry(tag).or_default().insert(trib.id);
+        }
+        for word in tokenize(&trib.message) {
+            self.words.entry(word).or_default().insert(trib.id);
+        }
+    }
+
+    /// finds the current version of the [Trib] with the given id, or [None]
+    /// if it has been deleted
+    fn lookup(&self, users: &HashMap<String, User>, id: u64) -> Option<Arc<Trib>> {
+        let author = self.authors.get(&id)?;
+        users.get(author)?.get_trib(id)
+    }
+
+    /// finds the [Trib] with the given id, or the original [Trib] if it is a
+    /// retrib
+    fn original(&self, users: &HashMap<String, User>, id: u64) -> TribResult<Arc<Trib>> {
+        let trib = self
+            .lookup(users, id)
+            .ok_or(TribblerError::TribIdDoesNotExist(id))?;
+        match trib.retrib_of {
+            Some(orig) => Ok(self
+                .lookup(users, orig)
+                .ok_or(TribblerError::TribIdDoesNotExist(orig))?),
+            None => Ok(trib),
+        }
+    }
+
+    /// checks whether `who` has a live retrib of the [Trib] with the given id
+    fn retribbed(&self, users: &HashMap<String, User>, who: &str, id: u64) -> bool {
+        self.retribs
+            .get(&id)
+            .into_iter()
+            .flatten()
+            .filter_map(|r| self.lookup(users, *r))
+            .any(|t| t.user == who)
+    }
+
+    /// the count of users who liked the [Trib] with the given id
+    fn like_count(&self, id: u64) -> u64 {
+        self.likes.get(&id).map_or(0, |l| l.len() as u64)
+    }
+
+    /// resolves the `n` most recent of `ids` which are still live and for
+    /// which `keep` holds, in Tribble Order
+    fn collect<F: Fn(&Trib) -> bool>(
+        &self,
+        users: &HashMap<String, User>,
+        ids: Option<&BTreeSet<u64>>,
+        n: usize,
+        keep: F,
+    ) -> Vec<Arc<Trib>> {
+        let mut res: Vec<Arc<Trib>> = ids
+            .into_iter()
+            .flat_map(|ids| ids.iter().rev())
+            .filter_map(|id| self.lookup(users, *id))
+            .filter(|t| keep(t))
+            .take(n)
+            .collect();
+        res.reverse();
+        res
+    }
+}
+
+/// Returns at most `n` of the latest tribs with a clock smaller than
+/// `before`. `tribs` must be sorted by clock.
+fn page(tribs: &[Arc<Trib>], before: u64, n: usize) -> Vec<Arc<Trib>> {
+    let end = tribs.partition_point(|t| t.clock < before);
+    let start = end.saturating_sub(n);
+    tribs[start..end].to_vec()
+}
+
+/// Returns at most `n` of the earliest tribs with a clock larger than
+/// `after`. `tribs` must be sorted by clock.
+fn since(tribs: &[Arc<Trib>], after: u64, n: usize) -> Vec<Arc<Trib>> {
+    let start = tribs.partition_point(|t| t.clock <= after);
+    let end = min(start.saturating_add(n), tribs.len());
+    tribs[start..end].to_vec()
+}
+
+/// Appends `trib` to a home buffer, dropping the oldest tribs beyond `n`.
+fn push_home(home: &mut Vec<Arc<Trib>>, trib: Arc<Trib>, n: usize) {
+    home.push(trib);
+    if home.len() > n {
+        home.drain(..home.len() - n);
+    }
+}
+
+/// Replaces the copy of `trib` held by a home buffer, if any.
+fn patch_home(home: &mut [Arc<Trib>], trib: &Arc<Trib>) {
+    if let Ok(i) = home.binary_search_by_key(&trib.clock, |t| t.clock) {
+        home[i] = trib.clone();
+    }
+}
+
+/// Merges the newest live tribs of `from` into a home buffer of `n` tribs.
+fn merge_home(home: &mut Vec<Arc<Trib>>, from: &User, n: usize) {
+    let newest = from.live_tribs(0, u64::MAX).rev().take(n);
+    let merged = timeline::top_n(home.drain(..).chain(newest), n);
+    *home = merged;
+}
+
+/// A mutation of a [RefServer], checked by the [Server] method making it,
+/// recorded in the journal and then applied. Replaying the journal applies the
+/// entries again without checking them, so that a change of [TribLimits]
+/// cannot refuse what was accepted before. Entries carry what cannot be
+/// computed again: the physical time of the change, and the logical clock it
+/// handed out.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(tag = "op", rename_all = "snake_case")]
+enum Entry {
+    SignUp {
+        user: String,
+        time: u64,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        credential: Option<String>,
+    },
+    UpdateProfile {
+        user: String,
+        display_name: String,
+        bio: String,
+    },
+    Credential {
+        user: String,
+        credential: String,
+    },
+    /// a post, reply or retrib
+    Post {
+        who: String,
+        message: String,
+        clock: u64,
+        id: u64,
+        reply_to: Option<u64>,
+        retrib_of: Option<u64>,
+    },
+    Like {
+        who: String,
+        id: u64,
+    },
+    Follow {
+        who: String,
+        whom: String,
+    },
+    Unfollow {
+        who: String,
+        whom: String,
+    },
+    Block {
+        who: String,
+        whom: String,
+    },
+    Unblock {
+        who: String,
+        whom: String,
+    },
+    Mute {
+        who: String,
+        whom: String,
+    },
+    Unmute {
+        who: String,
+        whom: String,
+    },
+    Dm {
+        from: String,
+        to: String,
+        message: String,
+        time: u64,
+        clock: u64,
+    },
+    Delete {
+        user: String,
+        clock: u64,
+    },
+    Edit {
+        user: String,
+        clock: u64,
+        message: String,
+        time: u64,
+    },
+}
+
+/// The append-only file a [RefServer] records its mutations to, one JSON
+/// [Entry] per line
+#[derive(Debug)]
+struct Journal {
+    /// the file, shared with the blocking threads which write it
+    file: Arc<Mutex<File>>,
+}
+
+impl Journal {
+    /// opens the journal at `path`, creating it when missing, and returns it
+    /// along with the entries it holds. A last line cut short by a crash is
+    /// dropped from the file, and so is a line cut short by a failed write
+    /// when an entry was appended to it after all.
+    fn open(path: &Path) -> TribResult<(Journal, Vec<Entry>)> {
+        let mut file = OpenOptions::new()
+            .read(true)
+            .append(true)
+            .create(true)
+            .open(path)?;
+        let mut data = String::new();
+        file.read_to_string(&mut data)?;
+        let complete = data.rfind('\n').map_or(0, |i| i + 1);
+        if complete < data.len() {
+            file.set_len(complete as u64)?;
+        }
+        let mut entries = vec![];
+        for (n, line) in data[..complete].lines().enumerate() {
+            match serde_json::from_str(line).or_else(|e| match line.rfind(ENTRY_START) {
+                // an entry starts past a torn one, which was never applied
+                Some(i) if i > 0 => serde_json::from_str(&line[i..]),
+                _ => Err(e),
+            }) {
+                Ok(entry) => entries.push(entry),
+                Err(e) => {
+                    return Err(Box::new(TribblerError::Unknown(format!(
+                        "{} line {}: {}",
+                        path.display(),
+                        n + 1,
+                        e
+                    ))))
+                }
+            }
+        }
+        let journal = Journal {
+            file: Arc::new(Mutex::new(file)),
+        };
+        Ok((journal, entries))
+    }
+
+    /// appends `entry` to the journal and waits for it to reach the disk, on
+    /// a blocking thread. When either fails, the file is cut back to where it
+    /// was, so that the next entry does not follow a torn line.
+    async fn append(&self, entry: &Entry) -> TribResult<()> {
+        let mut line = serde_json::to_string(entry)?;
+        line.push('\n');
+        let file = self.file.clone();
+        let write = tokio::task::spawn_blocking(move || -> std::io::Result<()> {
+            let mut file = file.lock().unwrap();
+            let len = file.metadata()?.len();
+            let res = file
+                .write_all(line.as_bytes())
+                .and_then(|_| file.sync_data());
+            if res.is_err() {
+                file.set_len(len)?;
+            }
+            res
+        });
+        match write.await {
+            Ok(res) => Ok(res?),
+            Err(e) => Err(Box::new(TribblerError::Unknown(e.to_string()))),
+        }
+    }
+}
+
+/// How every journal [Entry] starts, as its `op` tag is serialized first.
+/// Within a string, the quotes would be escaped.
+const ENTRY_START: &str = "{\"op\":";
+
+/// How many [Posted] notifications a subscriber can fall behind before it
+/// misses some
+const POSTED_BACKLOG: usize = 1024;
+
 /// The [RefServer] is a reference implementation for the [crate::trib::Server]
 ///
 /// This struct should be able to be used across threads when wrapped with an
@@ -138,53 +557,505 @@ impl User {
 ///
 /// ```rust
 /// use std::sync::Arc;
-/// use std::thread;
 /// use tribbler::ref_impl::RefServer;
 /// use tribbler::trib::Server;
 ///
+/// # #[tokio::main]
+/// # async fn main() {
 /// let server = Arc::new(RefServer::default());
 /// let s = server.clone();
-/// let handle = thread::spawn(move ||{
-///     s.sign_up("user2");
+/// let handle = tokio::spawn(async move {
+///     s.sign_up("user2").await.unwrap();
 /// });
-/// server.sign_up("user1");
-/// let _ = handle.join();
-/// assert_eq!(server.list_users().unwrap().len(), 2);
-///
+/// server.sign_up("user1").await.unwrap();
+/// let _ = handle.await;
+/// assert_eq!(server.list_users().await.unwrap().len(), 2);
+/// # }
 /// ```
 pub struct RefServer {
     users: Arc<RwLock<HashMap<String, User>>>,
+    /// the newest [TribLimits::max_trib_fetch] tribs on the home of each
+    /// user, kept up to date as tribs are posted
     homes: Arc<RwLock<HashMap<String, Vec<Arc<Trib>>>>>,
+    /// the first user signed up with each [username_skeleton], to find
+    /// confusable usernames without comparing against every user
+    skeletons: RwLock<HashMap<String, String>>,
+    index: Arc<RwLock<TribIndex>>,
     seq: AtomicU64,
+    posted: broadcast::Sender<Arc<Posted>>,
+    journal: Option<Journal>,
+    /// held by the change being made, see [RefServer::commit]
+    commits: tokio::sync::Mutex<()>,
+    limits: TribLimits,
+    /// see [RefServer::with_hlc_clocks]
+    hlc_clocks: bool,
 }
 
 impl RefServer {
     /// Creates a [RefServer] with no data
     pub fn new() -> RefServer {
+        RefServer::with_limits(TribLimits::default())
+    }
+
+    /// Creates a [RefServer] with no data, which enforces `limits`
+    pub fn with_limits(limits: TribLimits) -> RefServer {
         RefServer {
             users: Arc::new(RwLock::new(HashMap::new())),
             homes: Arc::new(RwLock::new(HashMap::new())),
+            skeletons: RwLock::new(HashMap::new()),
+            index: Arc::new(RwLock::new(TribIndex::default())),
             seq: AtomicU64::new(0),
+            posted: broadcast::channel(POSTED_BACKLOG).0,
+            journal: None,
+            commits: tokio::sync::Mutex::new(()),
+            limits,
+            hlc_clocks: false,
+        }
+    }
+
+    /// Makes the [RefServer] hand out [Hlc] timestamps as the clocks of
+    /// tribs, so that they follow the time they were posted at. These exceed
+    /// the integers a JavaScript number holds exactly, so the web front-ends,
+    /// whose JSON carries clocks as numbers, don't serve such a server.
+    pub fn with_hlc_clocks(mut self) -> RefServer {
+        self.hlc_clocks = true;
+        self
+    }
+
+    /// Creates a [RefServer] which records every change to the journal file
+    /// at `path`, after replaying the changes already recorded there. The
+    /// file is created when missing.
+    pub async fn open(path: impl AsRef<Path>) -> TribResult<RefServer> {
+        RefServer::open_with_limits(path, TribLimits::default()).await
+    }
+
+    /// Like [RefServer::open], for a [RefServer] which enforces `limits`
+    pub async fn open_with_limits(
+        path: impl AsRef<Path>,
+        limits: TribLimits,
+    ) -> TribResult<RefServer> {
+        let (journal, entries) = Journal::open(path.as_ref())?;
+        let mut server = RefServer::with_limits(limits);
+        {
+            let mut users = server.users.write().unwrap();
+            let mut index = server.index.write().unwrap();
+            for entry in entries.iter() {
+                server.apply(entry, &mut users, &mut index);
+            }
+        }
+        server.journal = Some(journal);
+        Ok(server)
+    }
+
+    /// makes a change: `check` checks it against the current state and
+    /// returns the [Entry] making it, which is written to the journal and only
+    /// applied once it reached the disk. Changes are made one at a time, so
+    /// the state checked is still the state when applying; the reads of the
+    /// data go on while the journal is written.
+    async fn commit<F>(&self, check: F) -> TribResult<()>
+    where
+        F: FnOnce(&HashMap<String, User>, &TribIndex) -> TribResult<Entry>,
+    {
+        let _turn = self.commits.lock().await;
+        let entry = {
+            let users = self.users.read().unwrap();
+            let index = self.index.read().unwrap();
+            check(&users, &index)?
+        };
+        if let Some(journal) = &self.journal {
+            journal.append(&entry).await?;
         }
+        let mut users = self.users.write().unwrap();
+        let mut index = self.index.write().unwrap();
+        self.apply(&entry, &mut users, &mut index);
+        Ok(())
     }
 
-    /// rebuilds the users' homepage based on the current set of [SeqTrib]s and
-    /// other users' tribs
+    /// applies a checked [Entry] to the users, the indexes and the homes
+    fn apply(&self, entry: &Entry, users: &mut HashMap<String, User>, index: &mut TribIndex) {
+        match entry {
+            Entry::SignUp {
+                user,
+                time,
+                credential,
+            } => {
+                let profile = Profile {
+                    user: user.clone(),
+                    display_name: user.clone(),
+                    bio: "".to_string(),
+                    join_time: *time,
+                    join_clock: self.seq.load(atomic::Ordering::SeqCst),
+                };
+                let mut new_user = User::new(profile);
+                new_user.credential = credential.clone();
+                users.insert(user.clone(), new_user);
+                self.homes.write().unwrap().insert(user.clone(), vec![]);
+                self.skeletons
+                    .write()
+                    .unwrap()
+                    .entry(username_skeleton(user))
+                    .or_insert_with(|| user.clone());
+            }
+            Entry::UpdateProfile {
+                user,
+                display_name,
+                bio,
+            } => {
+                if let Some(u) = users.get_mut(user) {
+                    u.profile.display_name = display_name.clone();
+                    u.profile.bio = bio.clone();
+                }
+            }
+            Entry::Credential { user, credential } => {
+                if let Some(u) = users.get_mut(user) {
+                    u.credential = Some(credential.clone());
+                }
+            }
+            Entry::Post {
+                who,
+                message,
+                clock,
+                id,
+                reply_to,
+                retrib_of,
+            } => {
+                // hands out the clocks after this one from now on
+                self.seq
+                    .fetch_max(id.saturating_add(1), atomic::Ordering::SeqCst);
+                let user = match users.get_mut(who) {
+                    Some(u) => u,
+                    None => return,
+                };
+                let mut trib = user.post(who, message, *id, *clock, *reply_to, *retrib_of);
+                // a retrib carries the likes of its original
+                if let Some(likes) = retrib_of.map(|id| index.like_count(id)) {
+                    if likes > 0 {
+                        trib = user
+                            .replace(trib.clock, |t| Trib { likes, ..t.clone() })
+                            .unwrap_or(trib);
+                    }
+                }
+                self.publish(trib, users, index);
+            }
+            Entry::Like { who, id } => {
+                let orig = match index.original(users, *id) {
+                    Ok(t) => t,
+                    Err(_) => return,
+                };
+                index.likes.entry(orig.id).or_default().insert(who.clone());
+                // update the count on the original and on all of its retribs
+                let likes = index.like_count(orig.id);
+                let copies = index.retribs.get(&orig.id).into_iter().flatten();
+                let mut liked = vec![];
+                for id in std::iter::once(&orig.id).chain(copies) {
+                    if let Some(author) = index.authors.get(id) {
+                        if let Some(u) = users.get_mut(author) {
+                            liked.extend(u.replace(*id, |t| Trib { likes, ..t.clone() }));
+                        }
+                    }
+                }
+                for trib in liked.iter() {
+                    self.patch_homes(trib, users);
+                }
+            }
+            Entry::Follow { who, whom } => {
+                if let Some(u) = users.get_mut(who) {
+                    u.follow(whom);
+                }
+                if let Some(u) = users.get_mut(whom) {
+                    u.add_follower(who);
+                }
+                // merge the tribs of whom into my home
+                if let (Some(user), Some(followed)) = (users.get(who), users.get(whom)) {
+                    let mut homes = self.homes.write().unwrap();
+                    if let Some(home) = homes.get_mut(who).filter(|_| !user.is_muting(whom)) {
+                        merge_home(home, followed, self.limits.max_trib_fetch);
+                    }
+                }
+            }
+            Entry::Unfollow { who, whom } => {
+                if let Some(u) = users.get_mut(who) {
+                    u.unfollow(whom);
+                }
+                if let Some(u) = users.get_mut(whom) {
+                    u.remove_follower(who);
+                }
+                self.rebuild_own_home(who, users);
+            }
+            Entry::Block { who, whom } => {
+                if let Some(u) = users.get_mut(who) {
+                    u.blocking.insert(whom.clone());
+                }
+                // blocking ends the following in both directions
+                for (a, b) in [(who, whom), (whom, who)] {
+                    if let Some(u) = users.get_mut(a) {
+                        u.unfollow(b);
+                        u.remove_follower(b);
+                    }
+                }
+                self.rebuild_own_home(who, users);
+                self.rebuild_own_home(whom, users);
+            }
+            Entry::Unblock { who, whom } => {
+                if let Some(u) = users.get_mut(who) {
+                    u.blocking.remove(whom);
+                }
+            }
+            Entry::Mute { who, whom } => {
+                if let Some(u) = users.get_mut(who) {
+                    u.muting.insert(whom.clone());
+                }
+                self.rebuild_own_home(who, users);
+            }
+            Entry::Unmute { who, whom } => {
+                if let Some(u) = users.get_mut(who) {
+                    u.muting.remove(whom);
+                }
+                self.rebuild_own_home(who, users);
+            }
+            Entry::Dm {
+                from,
+                to,
+                message,
+                time,
+                clock,
+            } => {
+                self.seq
+                    .fetch_max(clock.saturating_add(1), atomic::Ordering::SeqCst);
+                if let Some(u) = users.get_mut(to) {
+                    u.inbox.push(Arc::new(DirectMessage {
+                        from: from.clone(),
+                        to: to.clone(),
+                        message: message.clone(),
+                        time: *time,
+                        clock: *clock,
+                    }));
+                }
+            }
+            Entry::Delete { user, clock } => {
+                if let Some(u) = users.get_mut(user) {
+                    u.delete(*clock);
+                }
+                self.unlink_homes(user, *clock, users);
+            }
+            Entry::Edit {
+                user,
+                clock,
+                message,
+                time,
+            } => {
+                let trib = match users.get_mut(user) {
+                    Some(u) => u.edit(*clock, message, *time),
+                    None => None,
+                };
+                if let Some(trib) = trib {
+                    index.add_message(&trib, users, &self.limits);
+                    self.patch_homes(&trib, users);
+                }
+            }
+        }
+    }
+
+    /// adds a newly posted [Trib] to the homes of its author and of the
+    /// followers who have not muted it, to the indexes, and tells the
+    /// subscribers
+    fn publish(&self, trib: Arc<Trib>, users: &HashMap<String, User>, index: &mut TribIndex) {
+        let who = trib.user.as_str();
+        // the followers who have not muted me, and me
+        let mut audience: Vec<String> = match users.get(who) {
+            Some(user) => user
+                .followers
+                .iter()
+                .filter(|f| !users.get(*f).is_some_and(|f| f.is_muting(who)))
+                .cloned()
+                .collect(),
+            None => vec![],
+        };
+        audience.push(who.to_string());
+        let mut homes = self.homes.write().unwrap();
+        for name in audience.iter() {
+            if let Some(home) = homes.get_mut(name) {
+                push_home(home, trib.clone(), self.limits.max_trib_fetch);
+            }
+        }
+        index.add(&trib, users, &self.limits);
+        // no receivers is not an error
+        let _ = self.posted.send(Arc::new(Posted {
+            trib,
+            homes: audience,
+        }));
+    }
+
+    /// returns the current physical time in seconds
+    fn now() -> TribResult<u64> {
+        Ok(SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)?
+            .as_secs())
+    }
+
+    /// the users whose tribs are on the home of `who`: itself and the users it
+    /// follows and does not mute
+    fn home_sources<'a>(
+        who: &'a User,
+        users: &'a HashMap<String, User>,
+    ) -> impl Iterator<Item = &'a User> + 'a {
+        std::iter::once(who).chain(
+            who.following
+                .iter()
+                .filter(|u| !who.is_muting(u))
+                .filter_map(|u| users.get(u)),
+        )
+    }
+
+    /// rebuilds the home buffer of a user from the newest
+    /// [TribLimits::max_trib_fetch] live tribs of its
+    /// [RefServer::home_sources]
     fn rebuild_home(&self, who: &User, users: &HashMap<String, User>) -> Vec<Arc<Trib>> {
-        let mut home: Vec<SeqTrib> = vec![];
-        home.append(&mut who.seq_tribs.clone());
-        for user in who.following.iter() {
-            match users.get(user) {
-                Some(v) => {
-                    home.append(&mut v.seq_tribs.clone());
+        timeline::newest(
+            Self::home_sources(who, users).map(|u| u.live_tribs(0, u64::MAX)),
+            self.limits.max_trib_fetch,
+        )
+    }
+
+    /// signs up `user`, with `credential` when given, in a single entry
+    async fn sign_up_user(&self, user: &str, credential: Option<&str>) -> TribResult<()> {
+        if !self.limits.is_valid_username(user) {
+            return Err(Box::new(TribblerError::InvalidUsername(user.to_string())));
+        }
+        let time = Self::now()?;
+        self.commit(|users, _| {
+            if self.limits.unicode_usernames {
+                let skeletons = self.skeletons.read().unwrap();
+                if let Some(other) = skeletons
+                    .get(&username_skeleton(user))
+                    .filter(|u| *u != user)
+                {
+                    return Err(Box::new(TribblerError::UsernameConfusable(
+                        user.to_string(),
+                        other.to_string(),
+                    )));
+                }
+            }
+            match users.contains_key(user) {
+                true => Err(Box::new(TribblerError::UsernameTaken(user.to_string()))),
+                false => Ok(Entry::SignUp {
+                    user: user.to_string(),
+                    time,
+                    credential: credential.map(str::to_string),
+                }),
+            }
+        })
+        .await
+    }
+
+    /// posts a new [Trib], optionally in reply to the [Trib] with id
+    /// `reply_to` or as a retrib of the [Trib] with id `retrib_of`, and adds
+    /// it to the timelines and indexes
+    async fn post_trib(
+        &self,
+        who: &str,
+        post: &str,
+        clock: u64,
+        reply_to: Option<u64>,
+        retrib_of: Option<u64>,
+    ) -> TribResult<()> {
+        if text_len(post) > self.limits.max_trib_len {
+            return Err(Box::new(TribblerError::TribTooLong));
+        }
+        self.commit(|users, index| {
+            if let Some(id) = reply_to {
+                if index.lookup(users, id).is_none() {
+                    return Err(Box::new(TribblerError::TribIdDoesNotExist(id)));
                 }
-                None => continue,
+            }
+            if let Some(id) = retrib_of {
+                if index.lookup(users, id).is_none() {
+                    return Err(Box::new(TribblerError::TribIdDoesNotExist(id)));
+                }
+                if index.retribbed(users, who, id) {
+                    return Err(Box::new(TribblerError::AlreadyRetribbed(
+                        who.to_string(),
+                        id,
+                    )));
+                }
+            }
+            if !users.contains_key(who) {
+                return Err(Box::new(TribblerError::UserDoesNotExist(who.to_string())));
+            }
+            let seq = self.seq.load(atomic::Ordering::SeqCst);
+            if seq == u64::MAX {
+                return Err(Box::new(TribblerError::MaxedSeq));
+            }
+            Ok(Entry::Post {
+                who: who.to_string(),
+                message: post.to_string(),
+                clock,
+                // order the trib after the clock it has seen
+                id: match self.hlc_clocks {
+                    true => Hlc::from(seq.max(clock)).max(Hlc::now()).into(),
+                    false => seq.max(clock),
+                },
+                reply_to,
+                retrib_of,
+            })
+        })
+        .await
+    }
+
+    /// rebuilds the home of `who` alone
+    fn rebuild_own_home(&self, who: &str, users: &HashMap<String, User>) {
+        if let Some(user) = users.get(who) {
+            let home = self.rebuild_home(user, users);
+            self.homes.write().unwrap().insert(who.to_string(), home);
+        }
+    }
+
+    /// the author of a [Trib] and its followers, whose homes may hold it
+    fn audience<'a>(
+        author: &'a str,
+        users: &'a HashMap<String, User>,
+    ) -> impl Iterator<Item = &'a str> + 'a {
+        let followers = users
+            .get(author)
+            .into_iter()
+            .flat_map(|u| u.followers.iter());
+        std::iter::once(author).chain(followers.map(String::as_str))
+    }
+
+    /// replaces the copies of a changed [Trib] held by the homes in place
+    fn patch_homes(&self, trib: &Arc<Trib>, users: &HashMap<String, User>) {
+        let mut homes = self.homes.write().unwrap();
+        for name in Self::audience(&trib.user, users) {
+            if let Some(home) = homes.get_mut(name) {
+                patch_home(home, trib);
+            }
+        }
+    }
+
+    /// removes the [Trib] `author` posted at `clock` from the homes holding
+    /// it. A full buffer takes in the newest of the older tribs in its place.
+    fn unlink_homes(&self, author: &str, clock: u64, users: &HashMap<String, User>) {
+        let fetch = self.limits.max_trib_fetch;
+        let mut homes = self.homes.write().unwrap();
+        for name in Self::audience(author, users) {
+            let (home, owner) = match (homes.get_mut(name), users.get(name)) {
+                (Some(home), Some(owner)) => (home, owner),
+                _ => continue,
+            };
+            let i = match home.binary_search_by_key(&clock, |t| t.clock) {
+                Ok(i) => i,
+                Err(_) => continue,
             };
+            let oldest = home[0].clock;
+            home.remove(i);
+            if home.len() + 1 == fetch {
+                let older = timeline::newest(
+                    Self::home_sources(owner, users).map(|u| u.live_tribs(0, oldest)),
+                    1,
+                );
+                home.splice(0..0, older);
+            }
         }
-        home.sort();
-        home.iter()
-            .map(|x| x.trib.clone())
-            .collect::<Vec<Arc<Trib>>>()
     }
 }
 
@@ -194,28 +1065,72 @@ impl Default for RefServer {
     }
 }
 
+#[async_trait]
 impl Server for RefServer {
-    fn sign_up(&self, user: &str) -> TribResult<()> {
-        let mut users = self.users.write().unwrap();
-        if !is_valid_username(user) {
-            return Err(Box::new(TribblerError::InvalidUsername(user.to_string())));
+    fn limits(&self) -> TribLimits {
+        self.limits
+    }
+
+    async fn sign_up(&self, user: &str) -> TribResult<()> {
+        self.sign_up_user(user, None).await
+    }
+
+    async fn sign_up_with_credential(&self, user: &str, credential: &str) -> TribResult<()> {
+        self.sign_up_user(user, Some(credential)).await
+    }
+
+    async fn get_profile(&self, user: &str) -> TribResult<Profile> {
+        let users = self.users.read().unwrap();
+        match users.get(user) {
+            Some(u) => Ok(u.profile.clone()),
+            None => Err(Box::new(TribblerError::UserDoesNotExist(user.to_string()))),
         }
-        match users.contains_key(user) {
-            true => Err(Box::new(TribblerError::UsernameTaken(user.to_string()))),
-            false => {
-                users.insert(user.to_string(), User::new());
-                let mut homes = self.homes.write().unwrap();
-                homes.insert(user.to_string(), vec![]);
-                Ok(())
-            }
+    }
+
+    async fn set_credential(&self, user: &str, credential: &str) -> TribResult<()> {
+        self.commit(|users, _| match users.contains_key(user) {
+            true => Ok(Entry::Credential {
+                user: user.to_string(),
+                credential: credential.to_string(),
+            }),
+            false => Err(Box::new(TribblerError::UserDoesNotExist(user.to_string()))),
+        })
+        .await
+    }
+
+    async fn credential(&self, user: &str) -> TribResult<Option<String>> {
+        let users = self.users.read().unwrap();
+        match users.get(user) {
+            Some(u) => Ok(u.credential.clone()),
+            None => Err(Box::new(TribblerError::UserDoesNotExist(user.to_string()))),
+        }
+    }
+
+    async fn update_profile(&self, user: &str, display_name: &str, bio: &str) -> TribResult<()> {
+        if display_name.is_empty() || text_len(display_name) > self.limits.max_display_name_len {
+            return Err(Box::new(TribblerError::InvalidDisplayName(
+                display_name.to_string(),
+            )));
+        }
+        if text_len(bio) > self.limits.max_bio_len {
+            return Err(Box::new(TribblerError::BioTooLong));
         }
+        self.commit(|users, _| match users.contains_key(user) {
+            true => Ok(Entry::UpdateProfile {
+                user: user.to_string(),
+                display_name: display_name.to_string(),
+                bio: bio.to_string(),
+            }),
+            false => Err(Box::new(TribblerError::UserDoesNotExist(user.to_string()))),
+        })
+        .await
     }
 
-    fn list_users(&self) -> TribResult<Vec<String>> {
+    async fn list_users(&self) -> TribResult<Vec<String>> {
         let users = self.users.read().unwrap();
         let mut k: Vec<&String> = users.keys().collect();
         k.sort();
-        let sorted = k[..min(MIN_LIST_USER, k.len())].to_vec();
+        let sorted = k[..min(self.limits.min_list_user, k.len())].to_vec();
         let res: Vec<String> = sorted
             .iter()
             .map(|x| x.to_string())
@@ -223,128 +1138,201 @@ impl Server for RefServer {
         Ok(res)
     }
 
-    fn post(&self, who: &str, post: &str, clock: u64) -> TribResult<()> {
-        if post.len() > MAX_TRIB_LEN {
-            return Err(Box::new(TribblerError::TribTooLong));
-        }
-        let mut users = self.users.write().unwrap();
-        match users.get_mut(who) {
-            Some(user) => {
-                if self.seq.load(atomic::Ordering::SeqCst) == u64::MAX {
-                    return Err(Box::new(TribblerError::MaxedSeq));
-                }
-                let _ = self.seq.fetch_update(
-                    atomic::Ordering::SeqCst,
-                    atomic::Ordering::SeqCst,
-                    |v| {
-                        if v < clock {
-                            Some(clock)
-                        } else {
-                            None
-                        }
-                    },
-                );
+    async fn post(&self, who: &str, post: &str, clock: u64) -> TribResult<()> {
+        self.post_trib(who, post, clock, None, None).await
+    }
 
-                let trib = user.post(
-                    who,
-                    post,
-                    self.seq.fetch_add(1, atomic::Ordering::SeqCst),
-                    clock,
-                );
-                // add it to the timeline of my followers
-                let mut homes = self.homes.write().unwrap();
-                for follower in user.followers.iter() {
-                    homes
-                        .entry(follower.to_string())
-                        .and_modify(|e| e.push(trib.clone()));
-                }
-                // add it to my own timeline
-                homes
-                    .entry(who.to_string())
-                    .and_modify(|e| e.push(trib.clone()));
-                Ok(())
+    async fn reply(&self, who: &str, post: &str, clock: u64, reply_to: u64) -> TribResult<()> {
+        self.post_trib(who, post, clock, Some(reply_to), None).await
+    }
+
+    async fn retrib(&self, who: &str, trib_id: u64) -> TribResult<()> {
+        let orig = {
+            let users = self.users.read().unwrap();
+            self.index.read().unwrap().original(&users, trib_id)?
+        };
+        // order the retrib after the original
+        self.post_trib(who, &orig.message, orig.clock, None, Some(orig.id))
+            .await
+    }
+
+    async fn like(&self, who: &str, trib_id: u64) -> TribResult<()> {
+        self.commit(|users, index| {
+            if !users.contains_key(who) {
+                return Err(Box::new(TribblerError::UserDoesNotExist(who.to_string())));
             }
-            None => Err(Box::new(TribblerError::UserDoesNotExist(who.to_string()))),
-        }
+            let orig = index.original(users, trib_id)?;
+            if index.likes.get(&orig.id).is_some_and(|l| l.contains(who)) {
+                return Err(Box::new(TribblerError::AlreadyLiked(
+                    who.to_string(),
+                    trib_id,
+                )));
+            }
+            Ok(Entry::Like {
+                who: who.to_string(),
+                id: trib_id,
+            })
+        })
+        .await
     }
 
-    fn tribs(&self, user: &str) -> TribResult<Vec<Arc<Trib>>> {
+    async fn tribs(&self, user: &str) -> TribResult<Vec<Arc<Trib>>> {
         let users = self.users.read().unwrap();
         match users.get(user) {
-            Some(user) => Ok(user.list_tribs().to_vec()),
+            Some(user) => Ok(user.list_tribs(self.limits.max_trib_fetch).to_vec()),
             None => Err(Box::new(TribblerError::UserDoesNotExist(user.to_string()))),
         }
     }
 
-    fn follow(&self, who: &str, whom: &str) -> TribResult<()> {
+    async fn follow(&self, who: &str, whom: &str) -> TribResult<()> {
         if who == whom {
             return Err(Box::new(TribblerError::WhoWhom(who.to_string())));
         }
-        let mut users = self.users.write().unwrap();
-        if !users.contains_key(whom) {
-            return Err(Box::new(TribblerError::UserDoesNotExist(who.to_string())));
-        }
-        match users.get_mut(who) {
-            Some(u) => {
-                if u.is_following(whom) {
-                    return Err(Box::new(TribblerError::AlreadyFollowing(
+        self.commit(|users, _| {
+            match users.get(whom) {
+                Some(u) if u.is_blocking(who) => {
+                    return Err(Box::new(TribblerError::Blocked(
                         who.to_string(),
                         whom.to_string(),
-                    )));
+                    )))
                 }
-                u.follow(whom);
+                Some(_) => (),
+                None => return Err(Box::new(TribblerError::UserDoesNotExist(who.to_string()))),
             }
-            None => return Err(Box::new(TribblerError::UserDoesNotExist(who.to_string()))),
-        };
-        let _ = users
-            .entry(whom.to_string())
-            .and_modify(|e| e.add_follower(who));
-        // rebuild home
-        match users.get(who) {
-            Some(user) => {
-                let mut homes = self.homes.write().unwrap();
-                homes.insert(who.to_string(), self.rebuild_home(user, &users));
-                Ok(())
+            match users.get(who) {
+                Some(u) if u.is_following(whom) => Err(Box::new(TribblerError::AlreadyFollowing(
+                    who.to_string(),
+                    whom.to_string(),
+                ))),
+                Some(u) if u.following.len() >= self.limits.max_following => {
+                    Err(Box::new(TribblerError::FollowingTooMany))
+                }
+                Some(_) => Ok(Entry::Follow {
+                    who: who.to_string(),
+                    whom: whom.to_string(),
+                }),
+                None => Err(Box::new(TribblerError::UserDoesNotExist(who.to_string()))),
             }
-            None => Err(Box::new(TribblerError::UserDoesNotExist(who.to_string()))),
+        })
+        .await
+    }
+
+    async fn unfollow(&self, who: &str, whom: &str) -> TribResult<()> {
+        if who == whom {
+            return Err(Box::new(TribblerError::WhoWhom(who.to_string())));
         }
+        self.commit(|users, _| {
+            if !users.contains_key(whom) {
+                return Err(Box::new(TribblerError::UserDoesNotExist(whom.to_string())));
+            }
+            match users.get(who) {
+                Some(u) if !u.is_following(whom) => Err(Box::new(TribblerError::NotFollowing(
+                    who.to_string(),
+                    whom.to_string(),
+                ))),
+                Some(_) => Ok(Entry::Unfollow {
+                    who: who.to_string(),
+                    whom: whom.to_string(),
+                }),
+                None => Err(Box::new(TribblerError::UserDoesNotExist(whom.to_string()))),
+            }
+        })
+        .await
     }
 
-    fn unfollow(&self, who: &str, whom: &str) -> TribResult<()> {
+    async fn block(&self, who: &str, whom: &str) -> TribResult<()> {
         if who == whom {
             return Err(Box::new(TribblerError::WhoWhom(who.to_string())));
         }
-        let mut users = self.users.write().unwrap();
-        if !users.contains_key(whom) {
-            return Err(Box::new(TribblerError::UserDoesNotExist(whom.to_string())));
+        self.commit(|users, _| {
+            if !users.contains_key(whom) {
+                return Err(Box::new(TribblerError::UserDoesNotExist(whom.to_string())));
+            }
+            match users.get(who) {
+                Some(u) if u.is_blocking(whom) => Err(Box::new(TribblerError::AlreadyBlocked(
+                    who.to_string(),
+                    whom.to_string(),
+                ))),
+                Some(_) => Ok(Entry::Block {
+                    who: who.to_string(),
+                    whom: whom.to_string(),
+                }),
+                None => Err(Box::new(TribblerError::UserDoesNotExist(who.to_string()))),
+            }
+        })
+        .await
+    }
+
+    async fn unblock(&self, who: &str, whom: &str) -> TribResult<()> {
+        if who == whom {
+            return Err(Box::new(TribblerError::WhoWhom(who.to_string())));
         }
-        match users.get_mut(who) {
-            Some(u) => {
-                if !u.is_following(whom) {
-                    return Err(Box::new(TribblerError::NotFollowing(
-                        who.to_string(),
-                        whom.to_string(),
-                    )));
-                }
-                u.unfollow(whom);
+        self.commit(|users, _| {
+            if !users.contains_key(whom) {
+                return Err(Box::new(TribblerError::UserDoesNotExist(whom.to_string())));
             }
-            None => return Err(Box::new(TribblerError::UserDoesNotExist(whom.to_string()))),
-        };
-        let _ = users
-            .entry(whom.to_string())
-            .and_modify(|e| e.remove_follower(who));
-        // rebuild home
-        match users.get(who) {
-            Some(user) => {
-                let mut homes = self.homes.write().unwrap();
-                homes.insert(who.to_string(), self.rebuild_home(user, &users));
-                Ok(())
+            match users.get(who) {
+                Some(u) if !u.is_blocking(whom) => Err(Box::new(TribblerError::NotBlocked(
+                    who.to_string(),
+                    whom.to_string(),
+                ))),
+                Some(_) => Ok(Entry::Unblock {
+                    who: who.to_string(),
+                    whom: whom.to_string(),
+                }),
+                None => Err(Box::new(TribblerError::UserDoesNotExist(who.to_string()))),
             }
-            None => Err(Box::new(TribblerError::UserDoesNotExist(who.to_string()))),
+        })
+        .await
+    }
+
+    async fn mute(&self, who: &str, whom: &str) -> TribResult<()> {
+        if who == whom {
+            return Err(Box::new(TribblerError::WhoWhom(who.to_string())));
+        }
+        self.commit(|users, _| {
+            if !users.contains_key(whom) {
+                return Err(Box::new(TribblerError::UserDoesNotExist(whom.to_string())));
+            }
+            match users.get(who) {
+                Some(u) if u.is_muting(whom) => Err(Box::new(TribblerError::AlreadyMuted(
+                    who.to_string(),
+                    whom.to_string(),
+                ))),
+                Some(_) => Ok(Entry::Mute {
+                    who: who.to_string(),
+                    whom: whom.to_string(),
+                }),
+                None => Err(Box::new(TribblerError::UserDoesNotExist(who.to_string()))),
+            }
+        })
+        .await
+    }
+
+    async fn unmute(&self, who: &str, whom: &str) -> TribResult<()> {
+        if who == whom {
+            return Err(Box::new(TribblerError::WhoWhom(who.to_string())));
         }
+        self.commit(|users, _| {
+            if !users.contains_key(whom) {
+                return Err(Box::new(TribblerError::UserDoesNotExist(whom.to_string())));
+            }
+            match users.get(who) {
+                Some(u) if !u.is_muting(whom) => Err(Box::new(TribblerError::NotMuted(
+                    who.to_string(),
+                    whom.to_string(),
+                ))),
+                Some(_) => Ok(Entry::Unmute {
+                    who: who.to_string(),
+                    whom: whom.to_string(),
+                }),
+                None => Err(Box::new(TribblerError::UserDoesNotExist(who.to_string()))),
+            }
+        })
+        .await
     }
 
-    fn is_following(&self, who: &str, whom: &str) -> TribResult<bool> {
+    async fn is_following(&self, who: &str, whom: &str) -> TribResult<bool> {
         if who == whom {
             return Err(Box::new(TribblerError::WhoWhom(who.to_string())));
         }
@@ -358,7 +1346,7 @@ impl Server for RefServer {
         }
     }
 
-    fn following(&self, who: &str) -> TribResult<Vec<String>> {
+    async fn following(&self, who: &str) -> TribResult<Vec<String>> {
         let users = self.users.read().unwrap();
         match users.get(who) {
             Some(user) => Ok(user.list_following()),
@@ -366,19 +1354,1135 @@ impl Server for RefServer {
         }
     }
 
-    fn home(&self, user: &str) -> TribResult<Vec<Arc<Trib>>> {
+    async fn send_dm(&self, from: &str, to: &str, msg: &str) -> TribResult<()> {
+        if text_len(msg) > self.limits.max_dm_len {
+            return Err(Box::new(TribblerError::DmTooLong));
+        }
+        if from == to {
+            return Err(Box::new(TribblerError::WhoWhom(from.to_string())));
+        }
+        let time = Self::now()?;
+        self.commit(|users, _| {
+            let sender = match users.get(from) {
+                Some(u) => u,
+                None => return Err(Box::new(TribblerError::UserDoesNotExist(from.to_string()))),
+            };
+            let mutual = match users.get(to) {
+                Some(u) if u.is_blocking(from) => {
+                    return Err(Box::new(TribblerError::Blocked(
+                        from.to_string(),
+                        to.to_string(),
+                    )))
+                }
+                Some(u) => sender.is_following(to) && u.is_following(from),
+                None => return Err(Box::new(TribblerError::UserDoesNotExist(to.to_string()))),
+            };
+            if !mutual {
+                return Err(Box::new(TribblerError::NotMutualFollowers(
+                    from.to_string(),
+                    to.to_string(),
+                )));
+            }
+            let clock = self.seq.load(atomic::Ordering::SeqCst);
+            if clock == u64::MAX {
+                return Err(Box::new(TribblerError::MaxedSeq));
+            }
+            Ok(Entry::Dm {
+                from: from.to_string(),
+                to: to.to_string(),
+                message: msg.to_string(),
+                time,
+                clock,
+            })
+        })
+        .await
+    }
+
+    async fn inbox(&self, user: &str) -> TribResult<Vec<Arc<DirectMessage>>> {
+        let users = self.users.read().unwrap();
+        match users.get(user) {
+            Some(u) => Ok(u.list_inbox(self.limits.max_trib_fetch).to_vec()),
+            None => Err(Box::new(TribblerError::UserDoesNotExist(user.to_string()))),
+        }
+    }
+
+    async fn mentions(&self, user: &str) -> TribResult<Vec<Arc<Trib>>> {
+        let users = self.users.read().unwrap();
+        if !users.contains_key(user) {
+            return Err(Box::new(TribblerError::UserDoesNotExist(user.to_string())));
+        }
+        let index = self.index.read().unwrap();
+        // an edit may have removed the mention
+        Ok(index.collect(
+            &users,
+            index.mentions.get(user),
+            self.limits.max_trib_fetch,
+            |t| {
+                self.limits
+                    .parse_mentions(&t.message)
+                    .iter()
+                    .any(|m| m == user)
+            },
+        ))
+    }
+
+    async fn thread(&self, trib_id: u64) -> TribResult<Vec<Arc<Trib>>> {
+        let users = self.users.read().unwrap();
+        let index = self.index.read().unwrap();
+        let mut root = match index.lookup(&users, trib_id) {
+            Some(t) => t,
+            None => return Err(Box::new(TribblerError::TribIdDoesNotExist(trib_id))),
+        };
+        // a deleted ancestor ends the chain
+        while let Some(parent) = root.reply_to.and_then(|id| index.lookup(&users, id)) {
+            root = parent;
+        }
+        let mut thread = vec![];
+        let mut pending = vec![root.id];
+        while let Some(id) = pending.pop() {
+            if let Some(t) = index.lookup(&users, id) {
+                thread.push(t);
+            }
+            if let Some(replies) = index.replies.get(&id) {
+                pending.extend(replies.iter());
+            }
+        }
+        thread.sort();
+        Ok(thread)
+    }
+
+    async fn search_tag(&self, tag: &str) -> TribResult<Vec<Arc<Trib>>> {
+        let name = tag.strip_prefix('#').unwrap_or(tag).to_ascii_lowercase();
+        if !self.limits.is_valid_tag(&name) {
+            return Err(Box::new(TribblerError::InvalidTag(tag.to_string())));
+        }
+        let users = self.users.read().unwrap();
+        let index = self.index.read().unwrap();
+        Ok(index.collect(
+            &users,
+            index.tags.get(&name),
+            self.limits.max_trib_fetch,
+            |t| self.limits.parse_tags(&t.message).contains(&name),
+        ))
+    }
+
+    async fn search(&self, query: &str, limit: usize) -> TribResult<Vec<Arc<Trib>>> {
+        let words = tokenize(query);
+        let users = self.users.read().unwrap();
+        let index = self.index.read().unwrap();
+        let ids: BTreeSet<u64> = words
+            .iter()
+            .filter_map(|w| index.words.get(w))
+            .flatten()
+            .copied()
+            .collect();
+        let mut hits: Vec<(usize, Arc<Trib>)> = vec![];
+        for id in ids.into_iter() {
+            if let Some(t) = index.lookup(&users, id) {
+                // an edit may have removed some of the words
+                let score = tokenize(&t.message)
+                    .iter()
+                    .filter(|w| words.contains(w))
+                    .count();
+                if score > 0 {
+                    hits.push((score, t));
+                }
+            }
+        }
+        hits.sort_by(|a, b| b.0.cmp(&a.0).then_with(|| a.1.cmp(&b.1)));
+        Ok(hits
+            .into_iter()
+            .take(min(limit, self.limits.max_trib_fetch))
+            .map(|(_, t)| t)
+            .collect())
+    }
+
+    async fn trending_tags(&self) -> TribResult<Vec<TagCount>> {
+        let users = self.users.read().unwrap();
+        let index = self.index.read().unwrap();
+        let mut counts: HashMap<String, usize> = HashMap::new();
+        for id in index.recent.iter() {
+            if let Some(t) = index.lookup(&users, *id) {
+                for tag in self.limits.parse_tags(&t.message) {
+                    *counts.entry(tag).or_default() += 1;
+                }
+            }
+        }
+        let mut res: Vec<TagCount> = counts
+            .into_iter()
+            .map(|(tag, count)| TagCount { tag, count })
+            .collect();
+        res.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.tag.cmp(&b.tag)));
+        res.truncate(self.limits.max_trending_tags);
+        Ok(res)
+    }
+
+    async fn delete_trib(&self, user: &str, clock: u64) -> TribResult<()> {
+        self.commit(|users, _| match users.get(user) {
+            Some(u) if u.has_trib(clock) => Ok(Entry::Delete {
+                user: user.to_string(),
+                clock,
+            }),
+            Some(_) => Err(Box::new(TribblerError::TribDoesNotExist(
+                user.to_string(),
+                clock,
+            ))),
+            None => Err(Box::new(TribblerError::UserDoesNotExist(user.to_string()))),
+        })
+        .await
+    }
+
+    async fn edit_trib(&self, user: &str, clock: u64, new_message: &str) -> TribResult<()> {
+        if text_len(new_message) > self.limits.max_trib_len {
+            return Err(Box::new(TribblerError::TribTooLong));
+        }
+        let time = Self::now()?;
+        self.commit(|users, _| match users.get(user).map(|u| u.trib_at(clock)) {
+            Some(Some(t)) if t.retrib_of.is_some() => Err(Box::new(
+                TribblerError::RetribNotEditable(user.to_string(), clock),
+            )),
+            Some(Some(_)) => Ok(Entry::Edit {
+                user: user.to_string(),
+                clock,
+                message: new_message.to_string(),
+                time,
+            }),
+            Some(None) => Err(Box::new(TribblerError::TribDoesNotExist(
+                user.to_string(),
+                clock,
+            ))),
+            None => Err(Box::new(TribblerError::UserDoesNotExist(user.to_string()))),
+        })
+        .await
+    }
+
+    async fn followers(&self, who: &str) -> TribResult<Vec<String>> {
+        let users = self.users.read().unwrap();
+        match users.get(who) {
+            Some(user) => Ok(user.list_followers()),
+            None => Err(Box::new(TribblerError::UserDoesNotExist(who.to_string()))),
+        }
+    }
+
+    async fn follower_count(&self, who: &str) -> TribResult<usize> {
+        let users = self.users.read().unwrap();
+        match users.get(who) {
+            Some(user) => Ok(user.followers.len()),
+            None => Err(Box::new(TribblerError::UserDoesNotExist(who.to_string()))),
+        }
+    }
+
+    async fn following_count(&self, who: &str) -> TribResult<usize> {
+        let users = self.users.read().unwrap();
+        match users.get(who) {
+            Some(user) => Ok(user.following.len()),
+            None => Err(Box::new(TribblerError::UserDoesNotExist(who.to_string()))),
+        }
+    }
+
+    async fn suggest_follows(&self, user: &str, n: usize) -> TribResult<Vec<String>> {
+        let n = min(n, self.limits.max_suggestions);
+        let users = self.users.read().unwrap();
+        let me = match users.get(user) {
+            Some(u) => u,
+            None => return Err(Box::new(TribblerError::UserDoesNotExist(user.to_string()))),
+        };
+        let candidate = |name: &str| {
+            name != user
+                && !me.is_following(name)
+                && !me.is_blocking(name)
+                && !users.get(name).is_some_and(|u| u.is_blocking(user))
+        };
+        // count the users I follow who follow each candidate
+        let mut mutuals: HashMap<&str, usize> = HashMap::new();
+        for f in me.following.iter().filter_map(|f| users.get(f)) {
+            for g in f.following.iter().filter(|g| candidate(g)) {
+                *mutuals.entry(g.as_str()).or_default() += 1;
+            }
+        }
+        let mut ranked: Vec<(&str, usize)> = mutuals.into_iter().collect();
+        ranked.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(b.0)));
+        let mut res: Vec<String> = ranked
+            .into_iter()
+            .take(n)
+            .map(|(name, _)| name.to_string())
+            .collect();
+        // fall back to the authors of the most recent tribs
+        let index = self.index.read().unwrap();
+        for id in index.recent.iter().rev() {
+            if res.len() >= n {
+                break;
+            }
+            if let Some(author) = index.authors.get(id) {
+                if candidate(author) && !res.contains(author) {
+                    res.push(author.clone());
+                }
+            }
+        }
+        Ok(res)
+    }
+
+    async fn home(&self, user: &str) -> TribResult<Vec<Arc<Trib>>> {
         let homes = self.homes.read().unwrap();
         match homes.get(user) {
-            Some(home) => {
-                let ntrib = home.len();
-                let start = match ntrib.cmp(&MAX_TRIB_FETCH) {
-                    Ordering::Greater => ntrib - MAX_TRIB_FETCH,
-                    _ => 0,
-                };
-                // let hm = &home[start..];
-                Ok(home[start..].to_vec())
-            }
+            Some(home) => Ok(home.clone()),
+            None => Err(Box::new(TribblerError::UserDoesNotExist(user.to_string()))),
+        }
+    }
+
+    async fn home_page(
+        &self,
+        user: &str,
+        before_clock: u64,
+        limit: usize,
+    ) -> TribResult<Vec<Arc<Trib>>> {
+        let users = self.users.read().unwrap();
+        let homes = self.homes.read().unwrap();
+        let (who, home) = match (users.get(user), homes.get(user)) {
+            (Some(who), Some(home)) => (who, home),
+            _ => return Err(Box::new(TribblerError::UserDoesNotExist(user.to_string()))),
+        };
+        let fetch = self.limits.max_trib_fetch;
+        let n = min(limit, fetch);
+        let res = page(home, before_clock, n);
+        // a short buffer holds the whole home
+        if res.len() == n || home.len() < fetch {
+            return Ok(res);
+        }
+        // the page reaches past the buffer
+        Ok(timeline::newest(
+            Self::home_sources(who, &users).map(|u| u.live_tribs(0, before_clock)),
+            n,
+        ))
+    }
+
+    async fn home_since(&self, user: &str, after_clock: u64) -> TribResult<Vec<Arc<Trib>>> {
+        let users = self.users.read().unwrap();
+        let homes = self.homes.read().unwrap();
+        let (who, home) = match (users.get(user), homes.get(user)) {
+            (Some(who), Some(home)) => (who, home),
+            _ => return Err(Box::new(TribblerError::UserDoesNotExist(user.to_string()))),
+        };
+        let fetch = self.limits.max_trib_fetch;
+        match home.first() {
+            Some(t) if t.clock <= after_clock => return Ok(since(home, after_clock, fetch)),
+            _ if home.len() < fetch => return Ok(since(home, after_clock, fetch)),
+            _ => (),
+        }
+        // the tribs right after `after_clock` are older than the buffer
+        let sources = Self::home_sources(who, &users)
+            .map(|u| u.live_tribs(after_clock.saturating_add(1), u64::MAX));
+        Ok(timeline::merge(sources).take(fetch).collect())
+    }
+
+    async fn tribs_page(
+        &self,
+        user: &str,
+        before_clock: u64,
+        limit: usize,
+    ) -> TribResult<Vec<Arc<Trib>>> {
+        let users = self.users.read().unwrap();
+        match users.get(user) {
+            Some(u) => Ok(page(
+                &u.tribs,
+                before_clock,
+                min(limit, self.limits.max_trib_fetch),
+            )),
+            None => Err(Box::new(TribblerError::UserDoesNotExist(user.to_string()))),
+        }
+    }
+
+    async fn tribs_since(&self, user: &str, after_clock: u64) -> TribResult<Vec<Arc<Trib>>> {
+        let users = self.users.read().unwrap();
+        match users.get(user) {
+            Some(u) => Ok(since(&u.tribs, after_clock, self.limits.max_trib_fetch)),
             None => Err(Box::new(TribblerError::UserDoesNotExist(user.to_string()))),
         }
     }
+
+    async fn subscribe(&self) -> TribResult<PostReceiver> {
+        Ok(self.posted.subscribe())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        err::TribResult,
+        hlc::Hlc,
+        trib::{Server, TribLimits, MAX_BIO_LEN, MAX_DM_LEN, MAX_TRIB_FETCH, MAX_TRIB_LEN},
+    };
+
+    use std::sync::atomic;
+
+    use super::RefServer;
+
+    async fn setup_test_server() -> TribResult<RefServer> {
+        let server = RefServer::new();
+        server.sign_up("alice").await?;
+        server.sign_up("bob").await?;
+        server.follow("alice", "bob").await?;
+        Ok(server)
+    }
+
+    #[tokio::test]
+    async fn home_page_before_clock() -> TribResult<()> {
+        let server = setup_test_server().await?;
+        for i in 0..10 {
+            let who = if i % 2 == 0 { "alice" } else { "bob" };
+            server.post(who, &format!("m{}", i), 0).await?;
+        }
+        let newest = server.home_page("alice", u64::MAX, 4).await?;
+        assert_eq!(vec!["m6", "m7", "m8", "m9"], messages(&newest));
+        let older = server.home_page("alice", newest[0].clock, 4).await?;
+        assert_eq!(vec!["m2", "m3", "m4", "m5"], messages(&older));
+        let oldest = server.home_page("alice", older[0].clock, 4).await?;
+        assert_eq!(vec!["m0", "m1"], messages(&oldest));
+        assert!(server.home_page("alice", 0, 4).await?.is_empty());
+        let mine = server.tribs_page("alice", u64::MAX, 2).await?;
+        assert_eq!(vec!["m6", "m8"], messages(&mine));
+        assert!(server.home_page("carol", u64::MAX, 4).await.is_err());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn credentials() -> TribResult<()> {
+        let server = setup_test_server().await?;
+        assert_eq!(None, server.credential("alice").await?);
+        server.set_credential("alice", "hash1").await?;
+        server.set_credential("alice", "hash2").await?;
+        assert_eq!(Some("hash2".to_string()), server.credential("alice").await?);
+        assert_eq!(None, server.credential("bob").await?);
+        assert!(server.set_credential("carol", "hash").await.is_err());
+        assert!(server.credential("carol").await.is_err());
+        server.sign_up_with_credential("carol", "hash3").await?;
+        assert_eq!(Some("hash3".to_string()), server.credential("carol").await?);
+        assert!(server.sign_up_with_credential("carol", "x").await.is_err());
+        assert!(server.sign_up_with_credential("Dave", "x").await.is_err());
+        assert_eq!(Some("hash3".to_string()), server.credential("carol").await?);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn journal_replay() -> TribResult<()> {
+        let path = journal_path("replay");
+        let server = RefServer::open(&path).await?;
+        for user in ["alice", "bob", "carol"] {
+            server.sign_up(user).await?;
+        }
+        server.set_credential("alice", "hash").await?;
+        server.sign_up_with_credential("dave", "hash").await?;
+        server.update_profile("alice", "Alice", "hi").await?;
+        server.follow("alice", "bob").await?;
+        server.follow("bob", "alice").await?;
+        server.follow("carol", "alice").await?;
+        server.post("bob", "hello #tag", 0).await?;
+        server.post("alice", "from the future", 100).await?;
+        let id = server.tribs("bob").await?[0].id;
+        server.reply("alice", "hi @bob", 0, id).await?;
+        server.retrib("alice", id).await?;
+        server.like("carol", id).await?;
+        server.send_dm("alice", "bob", "psst").await?;
+        server.edit_trib("bob", id, "hello again #tag").await?;
+        let clock = server.tribs("alice").await?[0].clock;
+        server.delete_trib("alice", clock).await?;
+        server.mute("bob", "alice").await?;
+        server.unmute("bob", "alice").await?;
+        server.block("alice", "carol").await?;
+        server.unblock("alice", "carol").await?;
+        server.unfollow("bob", "alice").await?;
+        assert!(server.follow("bob", "bob").await.is_err());
+        let before = dump(&server).await?;
+        let seq = server.seq.load(atomic::Ordering::SeqCst);
+        drop(server);
+
+        let server = RefServer::open(&path).await?;
+        assert_eq!(before, dump(&server).await?);
+        assert_eq!(seq, server.seq.load(atomic::Ordering::SeqCst));
+        assert_eq!(
+            vec!["hello again #tag"],
+            messages(&server.search_tag("tag").await?)
+        );
+        assert_eq!(2, server.thread(id).await?.len());
+        server.post("carol", "after restart", 0).await?;
+        drop(server);
+
+        let server = RefServer::open(&path).await?;
+        assert_eq!(
+            vec!["after restart"],
+            messages(&server.tribs("carol").await?)
+        );
+        std::fs::remove_file(&path)?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn journal_under_lower_limits() -> TribResult<()> {
+        let path = journal_path("limits");
+        let server = RefServer::open(&path).await?;
+        server.sign_up("alicealice").await?;
+        server.sign_up("bob").await?;
+        server.follow("alicealice", "bob").await?;
+        server.post("bob", "a long enough trib", 0).await?;
+        drop(server);
+
+        let limits = TribLimits {
+            max_username_len: 3,
+            max_trib_len: 5,
+            max_following: 0,
+            ..TribLimits::default()
+        };
+        let server = RefServer::open_with_limits(&path, limits).await?;
+        assert_eq!(1, server.home("alicealice").await?.len());
+        assert!(server.post("bob", "too long now", 0).await.is_err());
+        std::fs::remove_file(&path)?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn journal_cut_short() -> TribResult<()> {
+        let path = journal_path("cut");
+        let server = RefServer::open(&path).await?;
+        server.sign_up("alice").await?;
+        drop(server);
+        let mut data = std::fs::read_to_string(&path)?;
+        std::fs::write(&path, format!("{}{{\"op\":\"sign_up\",\"us", data))?;
+        let server = RefServer::open(&path).await?;
+        assert_eq!(data, std::fs::read_to_string(&path)?);
+        server.sign_up("bob").await?;
+        drop(server);
+        let server = RefServer::open(&path).await?;
+        assert_eq!(vec!["alice", "bob"], server.list_users().await?);
+        drop(server);
+
+        data.push_str("not json\n");
+        std::fs::write(&path, data)?;
+        assert!(RefServer::open(&path).await.is_err());
+        std::fs::remove_file(&path)?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn journal_torn_line() -> TribResult<()> {
+        let path = journal_path("torn");
+        let server = RefServer::open(&path).await?;
+        server.sign_up("alice").await?;
+        drop(server);
+        // a write which failed partway, followed by one which did not
+        let data = std::fs::read_to_string(&path)?;
+        let carol = data.replace("alice", "carol");
+        let torn = "{\"op\":\"post\",\"who\":\"alice\",\"message\":\"{\\\"op\\\":";
+        std::fs::write(&path, format!("{}{}{}", data, torn, carol))?;
+        let server = RefServer::open(&path).await?;
+        assert_eq!(vec!["alice", "carol"], server.list_users().await?);
+        assert!(server.tribs("alice").await?.is_empty());
+        server.sign_up("dave").await?;
+        drop(server);
+        let server = RefServer::open(&path).await?;
+        assert_eq!(vec!["alice", "carol", "dave"], server.list_users().await?);
+        std::fs::remove_file(&path)?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn subscribe_to_posts() -> TribResult<()> {
+        let server = setup_test_server().await?;
+        server.post("bob", "before", 0).await?;
+        let mut rx = server.subscribe().await?;
+        server.sign_up("carol").await?;
+        server.follow("carol", "bob").await?;
+        server.mute("carol", "bob").await?;
+        server.post("bob", "hello", 0).await?;
+        let posted = rx.recv().await?;
+        assert_eq!("hello", posted.trib.message);
+        let mut homes = posted.homes.clone();
+        homes.sort();
+        assert_eq!(vec!["alice", "bob"], homes);
+        assert!(server
+            .post("carol", &"x".repeat(MAX_TRIB_LEN + 1), 0)
+            .await
+            .is_err());
+        server.reply("alice", "hi", 0, posted.trib.id).await?;
+        let posted = rx.recv().await?;
+        assert_eq!(vec!["alice"], posted.homes);
+        assert!(rx.try_recv().is_err());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn home_since_after_clock() -> TribResult<()> {
+        let server = setup_test_server().await?;
+        server.post("bob", "first", 0).await?;
+        let seen = server.home("alice").await?;
+        server.post("bob", "second", 0).await?;
+        server.post("alice", "third", 0).await?;
+        let last = seen.last().unwrap().clock;
+        let new = server.home_since("alice", last).await?;
+        assert_eq!(vec!["second", "third"], messages(&new));
+        let bob_new = server.tribs_since("bob", last).await?;
+        assert_eq!(vec!["second"], messages(&bob_new));
+        assert!(server.home_since("alice", u64::MAX).await?.is_empty());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn page_limits() -> TribResult<()> {
+        let server = setup_test_server().await?;
+        for i in 0..(MAX_TRIB_FETCH + 10) {
+            server.post("bob", &format!("m{}", i), 0).await?;
+        }
+        let page = server
+            .home_page("alice", u64::MAX, MAX_TRIB_FETCH * 2)
+            .await?;
+        assert_eq!(MAX_TRIB_FETCH, page.len());
+        let first = server.home_since("alice", 0).await?;
+        assert_eq!(MAX_TRIB_FETCH, first.len());
+        assert_eq!("m1", first[0].message);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn home_past_buffer() -> TribResult<()> {
+        let server = setup_test_server().await?;
+        server.sign_up("carol").await?;
+        for i in 0..(MAX_TRIB_FETCH * 3) {
+            let who = ["alice", "bob", "carol"][i % 3];
+            server.post(who, &format!("m{}", i), 0).await?;
+        }
+        let home = server.home("alice").await?;
+        assert_eq!(MAX_TRIB_FETCH, home.len());
+        let oldest = home[0].clock;
+        // alice sees her own tribs and bob's, not carol's
+        let page = server.home_page("alice", oldest, 3).await?;
+        assert_eq!(vec!["m145", "m147", "m148"], messages(&page));
+        let first = server.home_since("alice", 0).await?;
+        assert_eq!(MAX_TRIB_FETCH, first.len());
+        assert_eq!(vec!["m1", "m3", "m4"], messages(&first[..3]));
+        assert!(first.windows(2).all(|w| w[0].clock < w[1].clock));
+        // following carol brings her tribs into the buffer
+        server.follow("alice", "carol").await?;
+        let home = server.home("alice").await?;
+        assert_eq!("m200", home[0].message);
+        server.delete_trib("alice", home[1].clock).await?;
+        assert_eq!("m199", server.home("alice").await?[0].message);
+        // likes, edits and deletes patch the buffers in place
+        let id = home[5].id;
+        server.like("bob", id).await?;
+        server
+            .edit_trib(&home[5].user, home[5].clock, "edited")
+            .await?;
+        server.retrib("carol", id).await?;
+        server.like("alice", id).await?;
+        server.delete_trib(&home[0].user, home[0].clock).await?;
+        let home = server.home("alice").await?;
+        assert_eq!(MAX_TRIB_FETCH, home.len());
+        assert!(home
+            .iter()
+            .any(|t| t.id == id && t.likes == 2 && t.message == "edited"));
+        let users = server.users.read().unwrap();
+        for name in ["alice", "bob", "carol"] {
+            let rebuilt = server.rebuild_home(&users[name], &users);
+            assert_eq!(rebuilt, server.homes.read().unwrap()[name]);
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn limits() -> TribResult<()> {
+        let server = RefServer::with_limits(TribLimits {
+            max_username_len: 20,
+            max_trib_len: 5,
+            max_trib_fetch: 3,
+            min_list_user: 2,
+            max_following: 1,
+            max_display_name_len: 3,
+            max_bio_len: 3,
+            max_dm_len: 3,
+            max_suggestions: 1,
+            max_tag_len: 3,
+            max_trending_tags: 1,
+            trending_window: 2,
+            unicode_usernames: false,
+        });
+        server.sign_up("waytoolongusername1").await?;
+        server.sign_up("alice").await?;
+        server.sign_up("bob").await?;
+        assert_eq!(2, server.list_users().await?.len());
+        assert!(server.post("alice", "123456", 0).await.is_err());
+        for i in 0..5 {
+            server.post("alice", &format!("m{}", i), 0).await?;
+        }
+        assert_eq!(
+            vec!["m2", "m3", "m4"],
+            messages(&server.home("alice").await?)
+        );
+        assert_eq!(3, server.tribs("alice").await?.len());
+        assert_eq!(2, server.home_page("alice", u64::MAX, 2).await?.len());
+        assert_eq!(3, server.home_since("alice", 0).await?.len());
+        server.follow("alice", "bob").await?;
+        assert!(server.follow("alice", "waytoolongusername1").await.is_err());
+
+        assert!(server.update_profile("alice", "Alic", "").await.is_err());
+        assert!(server.update_profile("alice", "Al", "bio!").await.is_err());
+        server.update_profile("alice", "Al", "bio").await?;
+        server.follow("bob", "alice").await?;
+        assert!(server.send_dm("alice", "bob", "hey!").await.is_err());
+        server.send_dm("alice", "bob", "hey").await?;
+        server.post("bob", "#abc", 0).await?;
+        server.post("bob", "#abcd", 0).await?;
+        assert!(server.search_tag("abcd").await.is_err());
+        server.post("bob", "#x", 0).await?;
+        server.post("bob", "#y", 0).await?;
+        // only the last two tribs count, and only the top tag is returned
+        let trending = server.trending_tags().await?;
+        assert_eq!(1, trending.len());
+        assert_ne!("abc", trending[0].tag);
+        let suggested = server.suggest_follows("waytoolongusername1", 5).await?;
+        assert_eq!(1, suggested.len());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn multibyte_lengths() -> TribResult<()> {
+        let server = setup_test_server().await?;
+        let emoji = "🙂".repeat(MAX_TRIB_LEN);
+        server.post("alice", &emoji, 0).await?;
+        let clock = server.tribs("alice").await?[0].clock;
+        assert!(server
+            .post("alice", &format!("{}🙂", emoji), 0)
+            .await
+            .is_err());
+        server
+            .edit_trib("alice", clock, &"é".repeat(MAX_TRIB_LEN))
+            .await?;
+        assert!(server
+            .edit_trib("alice", clock, &"é".repeat(MAX_TRIB_LEN + 1))
+            .await
+            .is_err());
+        server.follow("bob", "alice").await?;
+        server
+            .send_dm("alice", "bob", &"日".repeat(MAX_DM_LEN))
+            .await?;
+        assert!(server
+            .send_dm("alice", "bob", &"日".repeat(MAX_DM_LEN + 1))
+            .await
+            .is_err());
+        server
+            .update_profile("alice", &"Ä".repeat(50), &"ß".repeat(MAX_BIO_LEN))
+            .await?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn unicode_usernames() -> TribResult<()> {
+        let server = RefServer::with_limits(TribLimits {
+            unicode_usernames: true,
+            ..Default::default()
+        });
+        server.sign_up("ace").await?;
+        server.sign_up("zoë").await?;
+        server.sign_up("山田").await?;
+        assert!(server.sign_up("Zoë").await.is_err());
+        assert!(server.sign_up("zoe\u{308}").await.is_err());
+        // the Cyrillic "асе" looks like "ace"
+        let err = server.sign_up("асе").await.unwrap_err();
+        assert_eq!(
+            "username \"асе\" is confusable with \"ace\"",
+            err.to_string()
+        );
+        assert!(RefServer::new().sign_up("zoë").await.is_err());
+        server.post("zoë", "hello", 0).await?;
+        assert_eq!(1, server.tribs("zoë").await?.len());
+        server.post("ace", "hi @zoë and @山田", 0).await?;
+        assert_eq!(1, server.mentions("zoë").await?.len());
+        assert_eq!(1, server.mentions("山田").await?.len());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn hlc_clocks() -> TribResult<()> {
+        let server = RefServer::new().with_hlc_clocks();
+        server.sign_up("alice").await?;
+        let before = Hlc::now();
+        server.post("alice", "first", 0).await?;
+        server.post("alice", "second", 0).await?;
+        let tribs = server.tribs("alice").await?;
+        assert!(Hlc::from(tribs[0].clock) >= before);
+        assert!(tribs[1].clock > tribs[0].clock);
+        let ahead = u64::from(Hlc::new(before.physical() + 60_000, 0));
+        server.post("alice", "third", ahead).await?;
+        assert_eq!(ahead, server.tribs("alice").await?[2].clock);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn followers_and_counts() -> TribResult<()> {
+        let server = setup_test_server().await?;
+        server.sign_up("carol").await?;
+        server.follow("carol", "bob").await?;
+        server.follow("bob", "alice").await?;
+        let mut followers = server.followers("bob").await?;
+        followers.sort();
+        assert_eq!(vec!["alice", "carol"], followers);
+        assert_eq!(2, server.follower_count("bob").await?);
+        assert_eq!(1, server.following_count("bob").await?);
+        assert_eq!(1, server.following_count("carol").await?);
+        server.unfollow("carol", "bob").await?;
+        assert_eq!(vec!["alice"], server.followers("bob").await?);
+        assert_eq!(0, server.follower_count("carol").await?);
+        assert!(server.followers("dave").await.is_err());
+        assert!(server.follower_count("dave").await.is_err());
+        assert!(server.following_count("dave").await.is_err());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn delete_and_edit_tribs() -> TribResult<()> {
+        let server = setup_test_server().await?;
+        server.post("bob", "one", 0).await?;
+        server.post("bob", "two", 0).await?;
+        server.post("alice", "three", 0).await?;
+        let bob = server.tribs("bob").await?;
+        server.delete_trib("bob", bob[0].clock).await?;
+        assert_eq!(vec!["two"], messages(&server.tribs("bob").await?));
+        assert_eq!(vec!["two", "three"], messages(&server.home("alice").await?));
+        assert!(server.delete_trib("bob", bob[0].clock).await.is_err());
+        assert!(server.edit_trib("bob", bob[0].clock, "x").await.is_err());
+
+        server.edit_trib("bob", bob[1].clock, "deux").await?;
+        let home = server.home("alice").await?;
+        assert_eq!(vec!["deux", "three"], messages(&home));
+        assert_eq!(bob[1].clock, home[0].clock);
+        assert_eq!(bob[1].id, home[0].id);
+        assert!(home[0].edited.is_some());
+        assert!(home[1].edited.is_none());
+
+        // a later follower never sees the deleted trib
+        server.sign_up("carol").await?;
+        server.follow("carol", "bob").await?;
+        assert_eq!(vec!["deux"], messages(&server.home("carol").await?));
+
+        let long = "a".repeat(MAX_TRIB_LEN + 1);
+        assert!(server.edit_trib("bob", bob[1].clock, &long).await.is_err());
+        assert!(server.delete_trib("dave", 0).await.is_err());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn replies_and_mentions() -> TribResult<()> {
+        let server = setup_test_server().await?;
+        server.post("alice", "hi @bob and @carol", 0).await?;
+        let root = server.tribs("alice").await?[0].clone();
+        server.reply("bob", "@alice hello", 0, root.id).await?;
+        let reply = server.tribs("bob").await?[0].clone();
+        assert_eq!(Some(root.id), reply.reply_to);
+        server.reply("alice", "how are you", 0, reply.id).await?;
+        server.post("bob", "unrelated", 0).await?;
+        assert!(server.reply("bob", "x", 0, 1000).await.is_err());
+
+        let thread = server.thread(reply.id).await?;
+        assert_eq!(
+            vec!["hi @bob and @carol", "@alice hello", "how are you"],
+            messages(&thread)
+        );
+        assert_eq!(messages(&thread), messages(&server.thread(root.id).await?));
+
+        assert_eq!(
+            vec!["hi @bob and @carol"],
+            messages(&server.mentions("bob").await?)
+        );
+        assert_eq!(
+            vec!["@alice hello"],
+            messages(&server.mentions("alice").await?)
+        );
+        assert!(server.mentions("carol").await.is_err());
+
+        server.edit_trib("alice", root.clock, "hi @alice").await?;
+        assert!(server.mentions("bob").await?.is_empty());
+        assert_eq!(2, server.mentions("alice").await?.len());
+        server.delete_trib("bob", reply.clock).await?;
+        assert_eq!(
+            vec!["hi @alice"],
+            messages(&server.mentions("alice").await?)
+        );
+        assert_eq!(2, server.thread(root.id).await?.len());
+        assert!(server.thread(reply.id).await.is_err());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn tags() -> TribResult<()> {
+        let server = setup_test_server().await?;
+        server.post("alice", "#rust is fun #lab", 0).await?;
+        server.post("bob", "#Rust again", 0).await?;
+        server.post("bob", "#go", 0).await?;
+        assert_eq!(
+            vec!["#rust is fun #lab", "#Rust again"],
+            messages(&server.search_tag("#RUST").await?)
+        );
+        assert!(server.search_tag("zig").await?.is_empty());
+        assert!(server.search_tag("#").await.is_err());
+        let trending = server.trending_tags().await?;
+        let names: Vec<&str> = trending.iter().map(|t| t.tag.as_str()).collect();
+        assert_eq!(vec!["rust", "go", "lab"], names);
+        assert_eq!(2, trending[0].count);
+
+        let bob = server.tribs("bob").await?;
+        server.edit_trib("bob", bob[0].clock, "no tags").await?;
+        server.delete_trib("bob", bob[1].clock).await?;
+        assert_eq!(1, server.search_tag("rust").await?.len());
+        assert!(server.search_tag("go").await?.is_empty());
+        assert_eq!(2, server.trending_tags().await?.len());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn retribs_and_likes() -> TribResult<()> {
+        let server = setup_test_server().await?;
+        server.sign_up("carol").await?;
+        server.follow("carol", "alice").await?;
+        server.post("bob", "original", 5).await?;
+        let orig = server.tribs("bob").await?[0].clone();
+        server.post("alice", "before", 0).await?;
+
+        server.retrib("alice", orig.id).await?;
+        let home = server.home("carol").await?;
+        assert_eq!(vec!["before", "original"], messages(&home));
+        assert_eq!(Some(orig.id), home[1].retrib_of);
+        assert!(home[1].clock > orig.clock);
+        assert!(server.retrib("alice", orig.id).await.is_err());
+        assert!(server.retrib("alice", home[1].id).await.is_err());
+        let err = server
+            .edit_trib("alice", home[1].clock, "not what bob said")
+            .await
+            .unwrap_err();
+        assert_eq!(
+            format!(
+                "trib {} of alice is a retrib, which can't be edited",
+                home[1].clock
+            ),
+            err.to_string()
+        );
+        assert_eq!(
+            vec!["original"],
+            messages(&server.tribs("alice").await?[1..])
+        );
+        server.retrib("carol", home[1].id).await?;
+        assert_eq!(Some(orig.id), server.tribs("carol").await?[0].retrib_of);
+        assert!(server.retrib("dave", orig.id).await.is_err());
+        assert!(server.retrib("alice", 1000).await.is_err());
+
+        server.like("alice", orig.id).await?;
+        assert!(server.like("alice", orig.id).await.is_err());
+        server.like("carol", home[1].id).await?;
+        assert!(server.like("carol", orig.id).await.is_err());
+        assert_eq!(2, server.tribs("bob").await?[0].likes);
+        let home = server.home("carol").await?;
+        assert!(home.iter().all(|t| t.message == "before" || t.likes == 2));
+        assert!(server.like("dave", orig.id).await.is_err());
+
+        // a deleted retrib can be retribbed again
+        server.delete_trib("alice", home[1].clock).await?;
+        server.retrib("alice", orig.id).await?;
+        assert_eq!(2, server.tribs("alice").await?[1].likes);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn direct_messages() -> TribResult<()> {
+        let server = setup_test_server().await?;
+        // alice follows bob, but not the other way around
+        assert!(server.send_dm("alice", "bob", "hi").await.is_err());
+        server.follow("bob", "alice").await?;
+        server.send_dm("alice", "bob", "hi").await?;
+        server.send_dm("bob", "alice", "hello").await?;
+        server.send_dm("alice", "bob", "bye").await?;
+        assert!(server.send_dm("alice", "alice", "me").await.is_err());
+        assert!(server.send_dm("alice", "carol", "hi").await.is_err());
+        assert!(server
+            .send_dm("alice", "bob", &"a".repeat(MAX_DM_LEN + 1))
+            .await
+            .is_err());
+
+        let inbox = server.inbox("bob").await?;
+        let msgs: Vec<&str> = inbox.iter().map(|m| m.message.as_str()).collect();
+        assert_eq!(vec!["hi", "bye"], msgs);
+        assert!(inbox.iter().all(|m| m.from == "alice" && m.to == "bob"));
+        assert!(inbox[0].clock < inbox[1].clock);
+        assert_eq!(1, server.inbox("alice").await?.len());
+        assert!(server.inbox("carol").await.is_err());
+
+        server.unfollow("alice", "bob").await?;
+        assert!(server
+            .send_dm("bob", "alice", "still there?")
+            .await
+            .is_err());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn profiles() -> TribResult<()> {
+        let server = setup_test_server().await?;
+        let alice = server.get_profile("alice").await?;
+        assert_eq!("alice", alice.user);
+        assert_eq!("alice", alice.display_name);
+        assert_eq!("", alice.bio);
+        server.post("alice", "first", 0).await?;
+        server.sign_up("carol").await?;
+        assert!(alice.join_clock < server.get_profile("carol").await?.join_clock);
+        assert!(server.get_profile("dave").await.is_err());
+
+        server
+            .update_profile("alice", "Alice A.", "likes tribbling")
+            .await?;
+        let updated = server.get_profile("alice").await?;
+        assert_eq!("Alice A.", updated.display_name);
+        assert_eq!("likes tribbling", updated.bio);
+        assert_eq!(alice.join_clock, updated.join_clock);
+        assert!(server.update_profile("alice", "", "").await.is_err());
+        assert!(server
+            .update_profile("alice", "a", &"b".repeat(MAX_BIO_LEN + 1))
+            .await
+            .is_err());
+        assert!(server.update_profile("dave", "d", "").await.is_err());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn blocks() -> TribResult<()> {
+        let server = setup_test_server().await?;
+        server.follow("bob", "alice").await?;
+        server.block("bob", "alice").await?;
+        assert!(server.block("bob", "alice").await.is_err());
+        assert!(!server.is_following("alice", "bob").await?);
+        assert!(!server.is_following("bob", "alice").await?);
+        assert!(server.follow("alice", "bob").await.is_err());
+        assert!(server.send_dm("alice", "bob", "hi").await.is_err());
+        server.post("bob", "hidden", 0).await?;
+        assert!(server.home("alice").await?.is_empty());
+        assert!(server.block("bob", "bob").await.is_err());
+        assert!(server.block("bob", "carol").await.is_err());
+
+        server.unblock("bob", "alice").await?;
+        assert!(server.unblock("bob", "alice").await.is_err());
+        server.follow("alice", "bob").await?;
+        assert_eq!(vec!["hidden"], messages(&server.home("alice").await?));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn mutes() -> TribResult<()> {
+        let server = setup_test_server().await?;
+        server.post("bob", "before", 0).await?;
+        server.mute("alice", "bob").await?;
+        assert!(server.mute("alice", "bob").await.is_err());
+        assert!(server.home("alice").await?.is_empty());
+        server.post("bob", "during", 0).await?;
+        server.post("alice", "mine", 0).await?;
+        assert_eq!(vec!["mine"], messages(&server.home("alice").await?));
+        // muting does not unfollow
+        assert!(server.is_following("alice", "bob").await?);
+
+        server.unmute("alice", "bob").await?;
+        assert!(server.unmute("alice", "bob").await.is_err());
+        assert_eq!(
+            vec!["before", "during", "mine"],
+            messages(&server.home("alice").await?)
+        );
+        assert!(server.mute("alice", "carol").await.is_err());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn suggestions() -> TribResult<()> {
+        let server = setup_test_server().await?;
+        for name in ["carol", "dave", "erin", "frank"] {
+            server.sign_up(name).await?;
+        }
+        server.follow("alice", "carol").await?;
+        server.follow("bob", "dave").await?;
+        server.follow("carol", "dave").await?;
+        server.follow("carol", "erin").await?;
+        server.follow("bob", "alice").await?;
+        server.post("frank", "hello", 0).await?;
+        server.post("alice", "me", 0).await?;
+        server.post("bob", "followed", 0).await?;
+
+        assert_eq!(
+            vec!["dave", "erin", "frank"],
+            server.suggest_follows("alice", 10).await?
+        );
+        assert_eq!(vec!["dave"], server.suggest_follows("alice", 1).await?);
+        server.block("erin", "alice").await?;
+        server.follow("alice", "dave").await?;
+        assert_eq!(vec!["frank"], server.suggest_follows("alice", 10).await?);
+        assert!(server.suggest_follows("zed", 10).await.is_err());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn full_text_search() -> TribResult<()> {
+        let server = setup_test_server().await?;
+        server.post("alice", "The quick brown fox", 0).await?;
+        server.post("bob", "a brown dog", 0).await?;
+        server.post("bob", "QUICK, brown!", 0).await?;
+        server.post("alice", "nothing here", 0).await?;
+        assert_eq!(
+            vec!["The quick brown fox", "QUICK, brown!", "a brown dog"],
+            messages(&server.search("brown quick", 10).await?)
+        );
+        assert_eq!(
+            vec!["The quick brown fox"],
+            messages(&server.search("Brown quick", 1).await?)
+        );
+        assert!(server.search("cat", 10).await?.is_empty());
+        assert!(server.search("  !! ", 10).await?.is_empty());
+
+        let tribs = server.tribs("alice").await?;
+        server.edit_trib("alice", tribs[0].clock, "slow").await?;
+        server
+            .delete_trib("bob", server.tribs("bob").await?[0].clock)
+            .await?;
+        assert_eq!(
+            vec!["QUICK, brown!"],
+            messages(&server.search("quick brown", 10).await?)
+        );
+        assert_eq!(vec!["slow"], messages(&server.search("slow", 10).await?));
+        Ok(())
+    }
+
+    /// returns a path for a journal which does not exist yet
+    fn journal_path(name: &str) -> std::path::PathBuf {
+        let path = std::env::temp_dir().join(format!(
+            "tribbler-{}-{}-{}.journal",
+            name,
+            std::process::id(),
+            std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .unwrap()
+                .as_nanos()
+        ));
+        let _ = std::fs::remove_file(&path);
+        path
+    }
+
+    /// dumps everything a user can see about the server
+    async fn dump(server: &RefServer) -> TribResult<String> {
+        let mut out = vec![serde_json::to_string(&server.list_users().await?)?];
+        for user in server.list_users().await? {
+            out.push(serde_json::to_string(&server.get_profile(&user).await?)?);
+            out.push(format!("{:?}", server.credential(&user).await?));
+            out.push(serde_json::to_string(&server.tribs(&user).await?)?);
+            out.push(serde_json::to_string(&server.home(&user).await?)?);
+            out.push(serde_json::to_string(&server.inbox(&user).await?)?);
+            let mut following = server.following(&user).await?;
+            following.sort();
+            out.push(format!("{:?}", following));
+        }
+        Ok(out.join("\n"))
+    }
+
+    fn messages(tribs: &[std::sync::Arc<crate::trib::Trib>]) -> Vec<&str> {
+        tribs.iter().map(|t| t.message.as_str()).collect()
+    }
 }
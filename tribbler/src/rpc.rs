@@ -214,6 +214,20 @@ pub mod trib_storage_client {
             let path = http::uri::PathAndQuery::from_static("/rpc.TribStorage/clock");
             self.inner.unary(request.into_request(), path, codec).await
         }
+        pub async fn hlc(
+            &mut self,
+            request: impl tonic::IntoRequest<super::Clock>,
+        ) -> Result<tonic::Response<super::Clock>, tonic::Status> {
+            self.inner.ready().await.map_err(|e| {
+                tonic::Status::new(
+                    tonic::Code::Unknown,
+                    format!("Service was not ready: {}", e.into()),
+                )
+            })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static("/rpc.TribStorage/hlc");
+            self.inner.unary(request.into_request(), path, codec).await
+        }
     }
 }
 #[doc = r" Generated server implementations."]
@@ -255,6 +269,10 @@ pub mod trib_storage_server {
             &self,
             request: tonic::Request<super::Clock>,
         ) -> Result<tonic::Response<super::Clock>, tonic::Status>;
+        async fn hlc(
+            &self,
+            request: tonic::Request<super::Clock>,
+        ) -> Result<tonic::Response<super::Clock>, tonic::Status>;
     }
     #[derive(Debug)]
     pub struct TribStorageServer<T: TribStorage> {
@@ -534,6 +552,34 @@ pub mod trib_storage_server {
                     };
                     Box::pin(fut)
                 }
+                "/rpc.TribStorage/hlc" => {
+                    #[allow(non_camel_case_types)]
+                    struct hlcSvc<T: TribStorage>(pub Arc<T>);
+                    impl<T: TribStorage> tonic::server::UnaryService<super::Clock> for hlcSvc<T> {
+                        type Response = super::Clock;
+                        type Future = BoxFuture<tonic::Response<Self::Response>, tonic::Status>;
+                        fn call(&mut self, request: tonic::Request<super::Clock>) -> Self::Future {
+                            let inner = self.0.clone();
+                            let fut = async move { (*inner).hlc(request).await };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let inner = inner.0;
+                        let method = hlcSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec).apply_compression_config(
+                            accept_compression_encodings,
+                            send_compression_encodings,
+                        );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
                 _ => Box::pin(async move {
                     Ok(http::Response::builder()
                         .status(200)
@@ -3,7 +3,7 @@
 use async_trait::async_trait;
 use std::{collections::HashMap, sync::RwLock};
 
-use crate::err::TribResult;
+use crate::{err::TribResult, hlc::Hlc};
 
 #[derive(Debug, Clone)]
 
@@ -89,6 +89,17 @@ pub trait Storage: KeyString + KeyList + Send + Sync {
     /// be unique, no smaller than `at_least`, and strictly larger than the
     /// value returned last time, unless it was [u64::MAX]
     async fn clock(&self, at_least: u64) -> TribResult<u64>;
+
+    /// Returns a hybrid logical clock timestamp, which follows the physical
+    /// time of the storage when it is later than `at_least`. The returned
+    /// value is unique, no smaller than `at_least`, and strictly larger than
+    /// the value returned last time, unless it was [u64::MAX]. The default
+    /// implementation takes it from [Storage::clock], which then counts on
+    /// from it; storages which keep the two apart implement this themselves.
+    async fn hlc(&self, at_least: Hlc) -> TribResult<Hlc> {
+        let at_least = at_least.max(Hlc::now());
+        Ok(Hlc::from(self.clock(u64::from(at_least)).await?))
+    }
 }
 
 /// This is a toy implementation of a backend storage service.
@@ -100,6 +111,8 @@ pub struct MemStorage {
     kvs: RwLock<HashMap<String, String>>,
     kv_list: RwLock<HashMap<String, List>>,
     clock: RwLock<u64>,
+    /// the next timestamp of [Storage::hlc], apart from `clock`
+    hlc: RwLock<Hlc>,
 }
 
 impl MemStorage {
@@ -134,7 +147,7 @@ impl KeyString for MemStorage {
             .read()
             .map_err(|e| e.to_string())?
             .iter()
-            .filter(|(k, _)| p.matches(*k))
+            .filter(|(k, _)| p.matches(k))
             .map(|(k, _)| k.to_string())
             .collect::<Vec<String>>();
         Ok(List(result))
@@ -195,7 +208,7 @@ impl KeyList for MemStorage {
             .read()
             .map_err(|e| e.to_string())?
             .iter()
-            .filter(|(k, _)| p.matches(*k))
+            .filter(|(k, _)| p.matches(k))
             .for_each(|(v, _)| result.push((*v).clone()));
         result.sort();
         Ok(List(result))
@@ -212,9 +225,14 @@ impl Storage for MemStorage {
 
         let ret = *clk;
 
-        if *clk < u64::MAX {
-            *clk += 1;
-        }
+        *clk = clk.saturating_add(1);
+        Ok(ret)
+    }
+
+    async fn hlc(&self, at_least: Hlc) -> TribResult<Hlc> {
+        let mut next = self.hlc.write().map_err(|e| e.to_string())?;
+        let ret = (*next).max(at_least).max(Hlc::now());
+        *next = Hlc::from(u64::from(ret).saturating_add(1));
         Ok(ret)
     }
 }
@@ -230,6 +248,7 @@ pub trait BinStorage {
 mod test {
     use crate::{
         err::TribResult,
+        hlc::Hlc,
         storage::{KeyValue, Pattern, Storage},
     };
 
@@ -257,8 +276,7 @@ mod test {
     #[tokio::test]
     async fn storage_get_set() -> TribResult<()> {
         let storage = MemStorage::new();
-        assert_eq!(
-            true,
+        assert!(
             storage
                 .set(&KeyValue {
                     key: "test".to_string(),
@@ -347,7 +365,7 @@ mod test {
                 value: "val2".to_string(),
             })
             .await?;
-        assert_eq!(true, res);
+        assert!(res);
         assert_eq!(2, storage.list_get("test").await.unwrap().0.len());
         Ok(())
     }
@@ -359,9 +377,9 @@ mod test {
             key: "test".to_string(),
             value: "val2".to_string(),
         };
-        assert_eq!(true, storage.list_append(&kv).await.unwrap());
-        assert_eq!(true, storage.list_append(&kv).await.unwrap());
-        assert_eq!(true, storage.list_append(&kv).await.unwrap());
+        assert!(storage.list_append(&kv).await.unwrap());
+        assert!(storage.list_append(&kv).await.unwrap());
+        assert!(storage.list_append(&kv).await.unwrap());
         assert_eq!(3, storage.list_remove(&kv).await.unwrap());
         println!("{:?}", storage.list_get("test").await.unwrap().0);
         assert_eq!("test-value", storage.list_get("test").await.unwrap().0[0]);
@@ -408,6 +426,21 @@ mod test {
         let storage = setup_test_storage().await;
         let c1 = storage.clock(1234).await.unwrap();
         let c2 = storage.clock(0).await.unwrap();
-        assert_eq!(true, c2 > c1);
+        assert!(c2 > c1);
+    }
+
+    #[tokio::test]
+    async fn hlc_follows_time() {
+        let storage = setup_test_storage().await;
+        let before = Hlc::now();
+        let h1 = storage.hlc(Hlc::default()).await.unwrap();
+        assert!(h1 >= before);
+        let h2 = storage.hlc(Hlc::default()).await.unwrap();
+        assert!(h2 > h1);
+        let ahead = Hlc::new(before.physical() + 60_000, 3);
+        assert_eq!(ahead, storage.hlc(ahead).await.unwrap());
+        assert!(storage.hlc(Hlc::default()).await.unwrap() > ahead);
+        // the logical clock counts apart from the timestamps
+        assert_eq!(0, storage.clock(0).await.unwrap());
     }
 }
@@ -0,0 +1,190 @@
+//! module containing functions which combine timelines of tribbles, like the
+//! timelines of followed users into a home timeline.
+//!
+//! A timeline is sorted oldest first by the order of its items, which is the
+//! Tribble Order for [Trib](crate::trib::Trib)s and `Arc<Trib>`s.
+use std::{
+    cmp::Reverse,
+    collections::{BinaryHeap, HashSet},
+    hash::Hash,
+};
+
+/// An iterator over sorted timelines merged into one, returned by [merge].
+pub struct Merge<I: Iterator>
+where
+    I::Item: Ord,
+{
+    timelines: Vec<I>,
+    /// the next item of each timeline, with its index in `timelines`
+    heads: BinaryHeap<Reverse<(I::Item, usize)>>,
+}
+
+impl<I: Iterator> Iterator for Merge<I>
+where
+    I::Item: Ord,
+{
+    type Item = I::Item;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let Reverse((item, i)) = self.heads.pop()?;
+        if let Some(next) = self.timelines[i].next() {
+            self.heads.push(Reverse((next, i)));
+        }
+        Some(item)
+    }
+}
+
+/// Merges sorted timelines into one sorted timeline. Only the next item of
+/// each timeline is held at a time, so taking the first `n` items of `k`
+/// timelines takes O(k + n log k), however long the timelines are. Equal
+/// items come in the order of their timelines.
+///
+/// ```rust
+/// use tribbler::timeline::merge;
+/// let merged: Vec<u64> = merge(vec![vec![1, 4, 9], vec![2, 3], vec![]]).collect();
+/// assert_eq!(vec![1, 2, 3, 4, 9], merged);
+/// ```
+pub fn merge<I>(timelines: impl IntoIterator<Item = I>) -> Merge<I::IntoIter>
+where
+    I: IntoIterator,
+    I::Item: Ord,
+{
+    let mut timelines: Vec<I::IntoIter> = timelines.into_iter().map(|t| t.into_iter()).collect();
+    let mut heads = BinaryHeap::with_capacity(timelines.len());
+    for (i, timeline) in timelines.iter_mut().enumerate() {
+        if let Some(item) = timeline.next() {
+            heads.push(Reverse((item, i)));
+        }
+    }
+    Merge { timelines, heads }
+}
+
+/// Returns the `n` newest items of sorted timelines merged into one, oldest
+/// first. Like [merge], this only reads the timelines from their newest end
+/// as far as it needs to.
+///
+/// ```rust
+/// use tribbler::timeline::newest;
+/// assert_eq!(vec![4, 9], newest(vec![vec![1, 4, 9], vec![2, 3]], 2));
+/// ```
+pub fn newest<I>(timelines: impl IntoIterator<Item = I>, n: usize) -> Vec<I::Item>
+where
+    I: IntoIterator,
+    I::IntoIter: DoubleEndedIterator,
+    I::Item: Ord,
+{
+    let mut res: Vec<I::Item> = merge(
+        timelines
+            .into_iter()
+            .map(|t| t.into_iter().rev().map(Reverse)),
+    )
+    .take(n)
+    .map(|Reverse(item)| item)
+    .collect();
+    res.reverse();
+    res
+}
+
+/// Returns the `n` largest of `items`, which need not be sorted, in order.
+/// Takes O(m log n) for `m` items.
+///
+/// ```rust
+/// use tribbler::timeline::top_n;
+/// assert_eq!(vec![7, 8, 9], top_n(vec![8, 1, 9, 3, 7], 3));
+/// ```
+pub fn top_n<T: Ord>(items: impl IntoIterator<Item = T>, n: usize) -> Vec<T> {
+    if n == 0 {
+        return vec![];
+    }
+    let mut heap = BinaryHeap::with_capacity(n);
+    for item in items {
+        if heap.len() < n {
+            heap.push(Reverse(item));
+        } else if let Some(mut smallest) = heap.peek_mut() {
+            // the heap moves the replaced item into place once dropped
+            if item > smallest.0 {
+                *smallest = Reverse(item);
+            }
+        }
+    }
+    let mut res: Vec<T> = heap.into_iter().map(|Reverse(item)| item).collect();
+    res.sort();
+    res
+}
+
+/// Removes the items of a timeline whose `key` was seen before, keeping the
+/// first item of each key. Use it on merged timelines which may hold the
+/// same tribble twice, with its id as the key.
+///
+/// ```rust
+/// use tribbler::timeline::dedup_by_key;
+/// assert_eq!(vec![(1, 'a'), (2, 'b')], dedup_by_key(vec![(1, 'a'), (2, 'b'), (1, 'c')], |x| x.0));
+/// ```
+pub fn dedup_by_key<T, K: Hash + Eq>(
+    timeline: impl IntoIterator<Item = T>,
+    key: impl Fn(&T) -> K,
+) -> Vec<T> {
+    let mut seen = HashSet::new();
+    timeline
+        .into_iter()
+        .filter(|item| seen.insert(key(item)))
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use super::{dedup_by_key, merge, newest, top_n};
+    use crate::trib::Trib;
+
+    fn trib(id: u64, clock: u64, user: &str) -> Arc<Trib> {
+        Arc::new(Trib {
+            user: user.to_string(),
+            message: format!("m{}", id),
+            time: 0,
+            clock,
+            id,
+            edited: None,
+            reply_to: None,
+            retrib_of: None,
+            likes: 0,
+        })
+    }
+
+    fn ids(tribs: &[Arc<Trib>]) -> Vec<u64> {
+        tribs.iter().map(|t| t.id).collect()
+    }
+
+    #[test]
+    fn merge_tribs() {
+        let alice = vec![trib(1, 1, "alice"), trib(4, 5, "alice")];
+        let bob = vec![trib(2, 2, "bob"), trib(3, 5, "bob"), trib(5, 6, "bob")];
+        // the same clock is ordered by user
+        let merged: Vec<Arc<Trib>> = merge(vec![bob.clone(), alice.clone()]).collect();
+        assert_eq!(vec![1, 2, 4, 3, 5], ids(&merged));
+        assert_eq!(
+            vec![4, 3, 5],
+            ids(&newest(vec![bob.clone(), alice.clone()], 3))
+        );
+        assert_eq!(5, newest(vec![bob, alice], 10).len());
+        assert!(newest(Vec::<Vec<u64>>::new(), 3).is_empty());
+    }
+
+    #[test]
+    fn top() {
+        assert!(top_n(vec![3, 1, 2], 0).is_empty());
+        assert_eq!(vec![1, 2, 3], top_n(vec![3, 1, 2], 5));
+        assert_eq!(vec![5, 5, 6], top_n(vec![5, 1, 6, 5, 2, 4], 3));
+        let tribs = vec![trib(3, 9, "a"), trib(1, 1, "a"), trib(2, 4, "a")];
+        assert_eq!(vec![2, 3], ids(&top_n(tribs, 2)));
+    }
+
+    #[test]
+    fn dedup() {
+        let a = vec![trib(1, 1, "a"), trib(2, 2, "a")];
+        let b = vec![trib(2, 2, "a"), trib(3, 3, "b")];
+        let merged = dedup_by_key(merge(vec![a, b]), |t| t.id);
+        assert_eq!(vec![1, 2, 3], ids(&merged));
+    }
+}
@@ -1,11 +1,17 @@
 #![allow(dead_code)]
 //! Package trib defines basic interfaces and constants
 //! for the Tribbler service implementation.
-use std::sync::Arc;
+use std::{cmp::Ordering, sync::Arc};
 
+use async_trait::async_trait;
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
+use tokio::sync::broadcast;
+use unicode_normalization::UnicodeNormalization;
+use unicode_security::{skeleton, GeneralSecurityProfile, MixedScript};
+use unicode_segmentation::UnicodeSegmentation;
 
-use crate::err::TribResult;
+use crate::err::{TribResult, TribblerError};
 
 /// Maximum length of a username
 #[allow(dead_code)]
@@ -15,6 +21,18 @@ pub const MAX_USERNAME_LEN: usize = 15;
 #[allow(dead_code)]
 pub const MAX_TRIB_LEN: usize = 140;
 
+/// Maximum length of a display name
+#[allow(dead_code)]
+pub const MAX_DISPLAY_NAME_LEN: usize = 50;
+
+/// Maximum length of a profile bio
+#[allow(dead_code)]
+pub const MAX_BIO_LEN: usize = 160;
+
+/// Maximum length of a direct message
+#[allow(dead_code)]
+pub const MAX_DM_LEN: usize = 140;
+
 /// Maximum count of tribbles for `Home()` and `Tribs()`
 #[allow(dead_code)]
 pub const MAX_TRIB_FETCH: usize = 100;
@@ -23,12 +41,168 @@ pub const MAX_TRIB_FETCH: usize = 100;
 #[allow(dead_code)]
 pub const MIN_LIST_USER: usize = 20;
 
+/// Maximum count of users returned by `SuggestFollows()`
+#[allow(dead_code)]
+pub const MAX_SUGGESTIONS: usize = 20;
+
 /// Maximum count of users that one can follow
 #[allow(dead_code)]
 pub const MAX_FOLLOWING: usize = 2000;
 
+/// Maximum length of a hashtag, not counting the `#`
+#[allow(dead_code)]
+pub const MAX_TAG_LEN: usize = 32;
+
+/// Maximum count of tags returned by `TrendingTags()`
+#[allow(dead_code)]
+pub const MAX_TRENDING_TAGS: usize = 10;
+
+/// Count of the most recent tribs that `TrendingTags()` counts tags over
+#[allow(dead_code)]
+pub const TRENDING_WINDOW: usize = 1000;
+
+/// The limits enforced by a tribbler service, which a deployment may change
+/// in the `limits` of its config file. Missing fields default to the
+/// constants of the same name.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(default)]
+pub struct TribLimits {
+    /// see [MAX_USERNAME_LEN]
+    pub max_username_len: usize,
+    /// see [MAX_TRIB_LEN]
+    pub max_trib_len: usize,
+    /// see [MAX_TRIB_FETCH]
+    pub max_trib_fetch: usize,
+    /// see [MIN_LIST_USER]
+    pub min_list_user: usize,
+    /// see [MAX_FOLLOWING]
+    pub max_following: usize,
+    /// see [MAX_DISPLAY_NAME_LEN]
+    pub max_display_name_len: usize,
+    /// see [MAX_BIO_LEN]
+    pub max_bio_len: usize,
+    /// see [MAX_DM_LEN]
+    pub max_dm_len: usize,
+    /// see [MAX_SUGGESTIONS]
+    pub max_suggestions: usize,
+    /// see [MAX_TAG_LEN]
+    pub max_tag_len: usize,
+    /// see [MAX_TRENDING_TAGS]
+    pub max_trending_tags: usize,
+    /// see [TRENDING_WINDOW]
+    pub trending_window: usize,
+    /// accept usernames in any script instead of only ASCII, see
+    /// [TribLimits::is_valid_username]
+    pub unicode_usernames: bool,
+}
+
+impl Default for TribLimits {
+    fn default() -> Self {
+        TribLimits {
+            max_username_len: MAX_USERNAME_LEN,
+            max_trib_len: MAX_TRIB_LEN,
+            max_trib_fetch: MAX_TRIB_FETCH,
+            min_list_user: MIN_LIST_USER,
+            max_following: MAX_FOLLOWING,
+            max_display_name_len: MAX_DISPLAY_NAME_LEN,
+            max_bio_len: MAX_BIO_LEN,
+            max_dm_len: MAX_DM_LEN,
+            max_suggestions: MAX_SUGGESTIONS,
+            max_tag_len: MAX_TAG_LEN,
+            max_trending_tags: MAX_TRENDING_TAGS,
+            trending_window: TRENDING_WINDOW,
+            unicode_usernames: false,
+        }
+    }
+}
+
+impl TribLimits {
+    /// Checks if a username is a valid one under these limits. Returns true
+    /// if it is.
+    ///
+    /// By default, a valid username has at most `max_username_len` lower-case
+    /// ASCII letters or digits. Under the Unicode username policy, it has at
+    /// most `max_username_len` letters or digits of a single script, and must
+    /// be its own [normalize_username].
+    pub fn is_valid_username(&self, s: &str) -> bool {
+        if s.is_empty() {
+            return false;
+        }
+
+        if self.unicode_usernames {
+            return text_len(s) <= self.max_username_len
+                && s.chars()
+                    .all(|c| c.is_alphanumeric() && c.identifier_allowed())
+                && s.is_single_script()
+                && normalize_username(s) == s;
+        }
+
+        if s.len() > self.max_username_len {
+            return false;
+        }
+
+        for r in s.chars() {
+            if !r.is_ascii_lowercase() && !r.is_ascii_digit() {
+                return false;
+            }
+        }
+        true
+    }
+
+    /// Checks if a tag, without the leading `#`, is a valid one under these
+    /// limits. Returns true if it is.
+    pub fn is_valid_tag(&self, s: &str) -> bool {
+        if s.is_empty() || s.len() > self.max_tag_len {
+            return false;
+        }
+        s.chars()
+            .all(|r| r.is_ascii_lowercase() || r.is_ascii_digit() || r == '_')
+    }
+
+    /// Extracts the hashtags in a message which are valid under these limits,
+    /// see [parse_tags].
+    pub fn parse_tags(&self, message: &str) -> Vec<String> {
+        let mut tags: Vec<String> = vec![];
+        let mut prev: Option<char> = None;
+        for (i, c) in message.char_indices() {
+            if c == '#' && !prev.is_some_and(|p| p.is_alphanumeric()) {
+                let tag: String = message[i + 1..]
+                    .chars()
+                    .take_while(|x| x.is_ascii_alphanumeric() || *x == '_')
+                    .collect::<String>()
+                    .to_ascii_lowercase();
+                if self.is_valid_tag(&tag) && !tags.contains(&tag) {
+                    tags.push(tag);
+                }
+            }
+            prev = Some(c);
+        }
+        tags
+    }
+
+    /// Extracts the users mentioned in a message whose names are valid under
+    /// these limits, see [parse_mentions].
+    pub fn parse_mentions(&self, message: &str) -> Vec<String> {
+        let mut names: Vec<String> = vec![];
+        let mut prev: Option<char> = None;
+        for (i, c) in message.char_indices() {
+            if c == '@' && !prev.is_some_and(|p| p.is_alphanumeric()) {
+                let name: String = message[i + 1..]
+                    .chars()
+                    .take_while(|x| x.is_alphanumeric())
+                    .collect();
+                if self.is_valid_username(&name) && !names.contains(&name) {
+                    names.push(name);
+                }
+            }
+            prev = Some(c);
+        }
+        names
+    }
+}
+
 /// A [Trib] is a post by a user to the tribbler service.
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, PartialEq, Eq)]
 pub struct Trib {
     /// who posted this trib
     pub user: String,
@@ -38,37 +212,196 @@ pub struct Trib {
     pub time: u64,
     /// the logical timestamp when posted
     pub clock: u64,
+    /// the id of this trib, unique among all tribs of the service
+    #[serde(default)]
+    pub id: u64,
+    /// the physical timestamp of the last edit, if the trib was edited
+    #[serde(default)]
+    pub edited: Option<u64>,
+    /// the id of the trib this trib replies to, if it is a reply
+    #[serde(default)]
+    pub reply_to: Option<u64>,
+    /// the id of the original trib, if this trib is a retrib of it
+    #[serde(default)]
+    pub retrib_of: Option<u64>,
+    /// the count of users who liked the trib
+    #[serde(default)]
+    pub likes: u64,
+}
+
+/// Orders [Trib]s in the Tribble Order: by `clock`, then `time`, then `user`,
+/// then `message`. Tribs agreeing on all four are ordered by their remaining
+/// fields, so that only equal tribs compare as equal.
+impl Ord for Trib {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.clock
+            .cmp(&other.clock)
+            .then_with(|| self.time.cmp(&other.time))
+            .then_with(|| self.user.cmp(&other.user))
+            .then_with(|| self.message.cmp(&other.message))
+            .then_with(|| self.id.cmp(&other.id))
+            .then_with(|| self.edited.cmp(&other.edited))
+            .then_with(|| self.reply_to.cmp(&other.reply_to))
+            .then_with(|| self.retrib_of.cmp(&other.retrib_of))
+            .then_with(|| self.likes.cmp(&other.likes))
+    }
+}
+
+impl PartialOrd for Trib {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
 }
 
+/// A [Profile] holds the public information about a user.
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone)]
+pub struct Profile {
+    /// the username the profile belongs to
+    pub user: String,
+    /// the name shown for the user, which defaults to the username
+    pub display_name: String,
+    /// a short description of the user
+    pub bio: String,
+    /// the physical timestamp when the user signed up
+    pub join_time: u64,
+    /// the logical timestamp when the user signed up
+    pub join_clock: u64,
+}
+
+/// A [DirectMessage] is a private message from one user to another.
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone)]
+pub struct DirectMessage {
+    /// who sent this message
+    pub from: String,
+    /// who the message was sent to
+    pub to: String,
+    /// the content of the message
+    pub message: String,
+    /// the physical timestamp when sent
+    pub time: u64,
+    /// the logical timestamp when sent
+    pub clock: u64,
+}
+
+/// A [TagCount] is a hashtag together with how many tribs used it.
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, PartialEq, Eq)]
+pub struct TagCount {
+    /// the tag, without the leading `#`
+    pub tag: String,
+    /// the count of tribs carrying the tag
+    pub count: usize,
+}
+
+/// A [Posted] notification is sent for every new trib, see
+/// [Server::subscribe].
+#[derive(Debug, Clone)]
+pub struct Posted {
+    /// the trib which was posted
+    pub trib: Arc<Trib>,
+    /// the users whose home timeline the trib was added to, including its
+    /// author
+    pub homes: Vec<String>,
+}
+
+/// Receives the [Posted] notifications of a [Server]. A receiver which falls
+/// too far behind misses the oldest notifications and gets
+/// [broadcast::error::RecvError::Lagged] instead.
+pub type PostReceiver = broadcast::Receiver<Arc<Posted>>;
+
+#[async_trait]
 /// A tribbler server object represents the front-end interface
 /// that serves tribbler-related data.
+///
+/// Only the methods of the basic Tribbler service are required. The paging
+/// methods and [Server::following_count] default to filtering the results of
+/// the basic ones, and the other methods default to returning
+/// [TribblerError::NotSupported], so an implementation can take on the other
+/// features one at a time.
+#[allow(unused_variables)]
 pub trait Server {
-    /// Creates a user.
+    /// Returns the limits this server enforces, which the default methods go
+    /// by. Defaults to the [TribLimits::default] of the constants.
+    fn limits(&self) -> TribLimits {
+        TribLimits::default()
+    }
+
+    /// Creates a user, along with a [Profile] which has the username as its
+    /// display name and an empty bio.
     /// Returns error when the username is invalid;
     /// returns error when the user already exists.
     /// Concurrent sign ups on the same user might both succeed with no error.
-    fn sign_up(&self, user: &str) -> TribResult<()>;
+    async fn sign_up(&self, user: &str) -> TribResult<()>;
+
+    /// Returns the profile of a user.
+    /// Returns error when user has not signed up.
+    async fn get_profile(&self, user: &str) -> TribResult<Profile> {
+        not_supported("get_profile")
+    }
+
+    /// Stores a credential for a user, such as a password hash, replacing
+    /// the previous one. The server stores it as is; hashing and checking
+    /// passwords is up to the caller.
+    /// Returns error when user has not signed up.
+    async fn set_credential(&self, user: &str, credential: &str) -> TribResult<()> {
+        not_supported("set_credential")
+    }
+
+    /// Signs up a new user together with their credential, see
+    /// [Server::set_credential], so that the user never exists without it.
+    /// The default implementation signs up and then sets the credential; a
+    /// server which can do both at once should, as a user left without a
+    /// credential by a failure in between can never sign in.
+    /// Returns the errors of [Server::sign_up].
+    async fn sign_up_with_credential(&self, user: &str, credential: &str) -> TribResult<()> {
+        self.sign_up(user).await?;
+        self.set_credential(user, credential).await
+    }
+
+    /// Returns the credential stored for a user, or `None` when none was set.
+    /// Returns error when user has not signed up.
+    async fn credential(&self, user: &str) -> TribResult<Option<String>> {
+        not_supported("credential")
+    }
+
+    /// Replaces the display name and bio of a user's profile.
+    /// Returns error when user has not signed up;
+    /// returns error when the display name is empty or longer than
+    /// [MAX_DISPLAY_NAME_LEN];
+    /// returns error when the bio is longer than [MAX_BIO_LEN].
+    async fn update_profile(&self, user: &str, display_name: &str, bio: &str) -> TribResult<()> {
+        not_supported("update_profile")
+    }
 
     /// List 20 registered users.  When there are less than 20 users that
     /// signed up the service, all of them needs to be listed.  When there
     /// are more than 20 users that signed up the service, an arbitrary set
     /// of at lest 20 of them needs to be listed.
     /// The result should be sorted in alphabetical order.
-    fn list_users(&self) -> TribResult<Vec<String>>;
+    async fn list_users(&self) -> TribResult<Vec<String>>;
 
     /// Post a tribble.  The clock is the maximum clock value this user has
     /// seen so far by reading tribbles or clock sync.
     /// Returns error when who does not exist;
     /// returns error when post is too long.
-    fn post(&self, who: &str, post: &str, clock: u64) -> TribResult<()>;
+    async fn post(&self, who: &str, post: &str, clock: u64) -> TribResult<()>;
+
+    /// Post a tribble in reply to the trib with id `reply_to`. The clock is
+    /// the same as in [Server::post].
+    /// Returns error when who does not exist;
+    /// returns error when post is too long;
+    /// returns error when there is no trib with id `reply_to`.
+    async fn reply(&self, who: &str, post: &str, clock: u64, reply_to: u64) -> TribResult<()> {
+        not_supported("reply")
+    }
 
     /// List the tribs that a particular user posted.
     /// Returns error when user has not signed up.
-    fn tribs(&self, user: &str) -> TribResult<Vec<Arc<Trib>>>;
+    async fn tribs(&self, user: &str) -> TribResult<Vec<Arc<Trib>>>;
 
     /// Follow someone's timeline.
     /// Returns error when who == whom;
     /// returns error when who is already following whom;
+    /// returns error when whom is blocking who;
     /// returns error when who is tryting to following
     /// more than trib.MaxFollowing users.
     /// returns error when who or whom has not signed up.
@@ -76,57 +409,632 @@ pub trait Server {
     /// The count of following users might exceed trib.MaxFollowing=2000,
     /// if and only if the 2000'th user is generated by concurrent Follow()
     /// calls.
-    fn follow(&self, who: &str, whom: &str) -> TribResult<()>;
+    async fn follow(&self, who: &str, whom: &str) -> TribResult<()>;
 
     /// Unfollow someone's timeline.
     /// Returns error when who == whom.
     /// returns error when who is not following whom;
     /// returns error when who or whom has not signed up.
-    fn unfollow(&self, who: &str, whom: &str) -> TribResult<()>;
+    async fn unfollow(&self, who: &str, whom: &str) -> TribResult<()>;
+
+    /// Block whom. A blocked user cannot follow or send direct messages to
+    /// who, and blocking ends any following between the two users.
+    /// Returns error when who == whom;
+    /// returns error when who is already blocking whom;
+    /// returns error when who or whom has not signed up.
+    async fn block(&self, who: &str, whom: &str) -> TribResult<()> {
+        not_supported("block")
+    }
+
+    /// Unblock whom.
+    /// Returns error when who == whom;
+    /// returns error when who is not blocking whom;
+    /// returns error when who or whom has not signed up.
+    async fn unblock(&self, who: &str, whom: &str) -> TribResult<()> {
+        not_supported("unblock")
+    }
+
+    /// Mute whom. The tribs of a muted user are left out of who's home
+    /// timeline, whether or not who follows them.
+    /// Returns error when who == whom;
+    /// returns error when who is already muting whom;
+    /// returns error when who or whom has not signed up.
+    async fn mute(&self, who: &str, whom: &str) -> TribResult<()> {
+        not_supported("mute")
+    }
+
+    /// Unmute whom.
+    /// Returns error when who == whom;
+    /// returns error when who is not muting whom;
+    /// returns error when who or whom has not signed up.
+    async fn unmute(&self, who: &str, whom: &str) -> TribResult<()> {
+        not_supported("unmute")
+    }
 
     /// Returns true when who following whom.
     /// Returns error when who == whom.
     /// Returns error when who or whom has not signed up.
-    fn is_following(&self, who: &str, whom: &str) -> TribResult<bool>;
+    async fn is_following(&self, who: &str, whom: &str) -> TribResult<bool>;
 
     /// Returns the list of following users.
     /// Returns error when who has not signed up.
     /// The list have users more than trib.MaxFollowing=2000,
     /// if and only if the 2000'th user is generate d by concurrent Follow()
     /// calls.
-    fn following(&self, who: &str) -> TribResult<Vec<String>>;
+    async fn following(&self, who: &str) -> TribResult<Vec<String>>;
+
+    /// Returns the list of users following who.
+    /// Returns error when who has not signed up.
+    async fn followers(&self, who: &str) -> TribResult<Vec<String>> {
+        not_supported("followers")
+    }
+
+    /// Returns the number of users following who.
+    /// Returns error when who has not signed up.
+    async fn follower_count(&self, who: &str) -> TribResult<usize> {
+        not_supported("follower_count")
+    }
+
+    /// Returns the number of users who is following.
+    /// Returns error when who has not signed up.
+    async fn following_count(&self, who: &str) -> TribResult<usize> {
+        Ok(self.following(who).await?.len())
+    }
+
+    /// Suggest at most `n` (capped at [MAX_SUGGESTIONS]) users for user to
+    /// follow. Users followed by the users that user follows come first,
+    /// ranked by how many of them follow each one, with ties in alphabetical
+    /// order. The rest are the authors of the most recent tribs. Users that
+    /// user already follows, or that block or are blocked by user, are never
+    /// suggested.
+    /// Returns error when user has not signed up.
+    async fn suggest_follows(&self, user: &str, n: usize) -> TribResult<Vec<String>> {
+        not_supported("suggest_follows")
+    }
 
     /// List the tribs of someone's following users (including himself).
     /// Returns error when user has not signed up.
-    fn home(&self, user: &str) -> TribResult<Vec<Arc<Trib>>>;
+    async fn home(&self, user: &str) -> TribResult<Vec<Arc<Trib>>>;
+
+    /// List at most `limit` of the most recent tribs on the user's home
+    /// timeline whose clock is smaller than `before_clock`, in Tribble Order.
+    /// No more than [MAX_TRIB_FETCH] tribs are returned on a single call.
+    /// Pass [u64::MAX] as `before_clock` to fetch the newest page, and the
+    /// clock of its oldest trib to fetch the page before it. A page never
+    /// ends within tribs sharing a clock: it holds fewer tribs instead, or
+    /// all of them when they alone fill it.
+    /// Returns error when user has not signed up.
+    async fn home_page(
+        &self,
+        user: &str,
+        before_clock: u64,
+        limit: usize,
+    ) -> TribResult<Vec<Arc<Trib>>> {
+        let home = self.home(user).await?;
+        Ok(page(
+            home,
+            before_clock,
+            limit,
+            self.limits().max_trib_fetch,
+        ))
+    }
+
+    /// List the tribs on the user's home timeline whose clock is larger than
+    /// `after_clock`, in Tribble Order. At most the [MAX_TRIB_FETCH] oldest
+    /// of those tribs are returned, so a caller can call again with the
+    /// clock of the last trib to catch up. As with [Server::home_page], the
+    /// tribs sharing a clock come on the same call.
+    /// Returns error when user has not signed up.
+    async fn home_since(&self, user: &str, after_clock: u64) -> TribResult<Vec<Arc<Trib>>> {
+        let home = self.home(user).await?;
+        Ok(since(home, after_clock, self.limits().max_trib_fetch))
+    }
+
+    /// Same as [Server::home_page], for the tribs that a user posted.
+    async fn tribs_page(
+        &self,
+        user: &str,
+        before_clock: u64,
+        limit: usize,
+    ) -> TribResult<Vec<Arc<Trib>>> {
+        let tribs = self.tribs(user).await?;
+        Ok(page(
+            tribs,
+            before_clock,
+            limit,
+            self.limits().max_trib_fetch,
+        ))
+    }
+
+    /// Same as [Server::home_since], for the tribs that a user posted.
+    async fn tribs_since(&self, user: &str, after_clock: u64) -> TribResult<Vec<Arc<Trib>>> {
+        let tribs = self.tribs(user).await?;
+        Ok(since(tribs, after_clock, self.limits().max_trib_fetch))
+    }
+
+    /// Re-post the trib with id `trib_id` on who's timeline. The retrib
+    /// copies the message of the original trib, and is ordered after it.
+    /// Retribbing a retrib retribs its original trib.
+    /// Returns error when who does not exist;
+    /// returns error when there is no trib with id `trib_id`;
+    /// returns error when who already retribbed the trib.
+    async fn retrib(&self, who: &str, trib_id: u64) -> TribResult<()> {
+        not_supported("retrib")
+    }
+
+    /// Like the trib with id `trib_id`. The like count of a trib is carried
+    /// by the trib and by all of its retribs. Liking a retrib likes its
+    /// original trib.
+    /// Returns error when who does not exist;
+    /// returns error when there is no trib with id `trib_id`;
+    /// returns error when who already liked the trib.
+    async fn like(&self, who: &str, trib_id: u64) -> TribResult<()> {
+        not_supported("like")
+    }
+
+    /// Send a private message from one user to another. Messages can only
+    /// be sent between users who follow each other.
+    /// Returns error when from == to;
+    /// returns error when from or to has not signed up;
+    /// returns error when to is blocking from;
+    /// returns error when from and to do not follow each other;
+    /// returns error when the message is longer than [MAX_DM_LEN].
+    async fn send_dm(&self, from: &str, to: &str, msg: &str) -> TribResult<()> {
+        not_supported("send_dm")
+    }
+
+    /// List the [MAX_TRIB_FETCH] most recent messages sent to user, oldest
+    /// first.
+    /// Returns error when user has not signed up.
+    async fn inbox(&self, user: &str) -> TribResult<Vec<Arc<DirectMessage>>> {
+        not_supported("inbox")
+    }
+
+    /// List the recent tribs that mention user with `@user`, in Tribble
+    /// Order. See [parse_mentions] for what counts as a mention.
+    /// Returns error when user has not signed up.
+    async fn mentions(&self, user: &str) -> TribResult<Vec<Arc<Trib>>> {
+        not_supported("mentions")
+    }
+
+    /// List the thread that the trib with id `trib_id` belongs to: the trib
+    /// at the root of its reply chain followed by all replies to it, directly
+    /// or indirectly, in Tribble Order.
+    /// Returns error when there is no trib with id `trib_id`.
+    async fn thread(&self, trib_id: u64) -> TribResult<Vec<Arc<Trib>>> {
+        not_supported("thread")
+    }
+
+    /// List the recent tribs tagged with `#tag`, in Tribble Order. The tag
+    /// may be given with or without the leading `#`, in any case. See
+    /// [parse_tags] for what counts as a tag.
+    /// Returns error when the tag is invalid.
+    async fn search_tag(&self, tag: &str) -> TribResult<Vec<Arc<Trib>>> {
+        not_supported("search_tag")
+    }
+
+    /// Search the tribs for the words in `query`, and list at most `limit`
+    /// (capped at [MAX_TRIB_FETCH]) of them. See [tokenize] for how a text
+    /// is split into words. Tribs matching more of the words come first;
+    /// tribs matching the same count of words are in Tribble Order.
+    /// A query with no words matches no tribs.
+    async fn search(&self, query: &str, limit: usize) -> TribResult<Vec<Arc<Trib>>> {
+        not_supported("search")
+    }
+
+    /// List at most [MAX_TRENDING_TAGS] of the tags used the most among the
+    /// last [TRENDING_WINDOW] tribs posted, from the most used one. Ties are
+    /// broken in alphabetical order.
+    async fn trending_tags(&self) -> TribResult<Vec<TagCount>> {
+        not_supported("trending_tags")
+    }
+
+    /// Delete the trib that user posted with the given clock. The trib is
+    /// removed from the user's tribs and from every home timeline.
+    /// Returns error when user has not signed up;
+    /// returns error when there is no such trib.
+    async fn delete_trib(&self, user: &str, clock: u64) -> TribResult<()> {
+        not_supported("delete_trib")
+    }
+
+    /// Replace the message of the trib that user posted with the given clock.
+    /// The trib keeps its clock and position in every timeline, and records
+    /// the time of the edit.
+    /// Returns error when user has not signed up;
+    /// returns error when there is no such trib;
+    /// returns error when the trib is a retrib;
+    /// returns error when the new message is too long.
+    async fn edit_trib(&self, user: &str, clock: u64, new_message: &str) -> TribResult<()> {
+        not_supported("edit_trib")
+    }
+
+    /// Subscribe to the tribs posted from now on, including replies and
+    /// retribs. Every successful post sends one [Posted] notification to
+    /// every receiver.
+    async fn subscribe(&self) -> TribResult<PostReceiver> {
+        not_supported("subscribe")
+    }
 }
 
-/// Checks if a username is a valid one. Returns true if it is.
-pub fn is_valid_username(s: &str) -> bool {
-    if s.is_empty() {
-        return false;
+fn not_supported<T>(op: &str) -> TribResult<T> {
+    Err(Box::new(TribblerError::NotSupported(op.to_string())))
+}
+
+/// the newest `limit` (at most `fetch`) of a sorted timeline whose clock is
+/// smaller than `before_clock`. Tribs sharing the clock of the
+/// oldest trib of the page are left to the next page, or all taken when they
+/// fill the page, so that paging on before that clock skips none of them.
+fn page(timeline: Vec<Arc<Trib>>, before_clock: u64, limit: usize, fetch: usize) -> Vec<Arc<Trib>> {
+    let end = timeline.partition_point(|t| t.clock < before_clock);
+    let mut start = end.saturating_sub(limit.min(fetch));
+    if start > 0 && start < end && timeline[start - 1].clock == timeline[start].clock {
+        let cut = timeline[start].clock;
+        start = match timeline[start..end].partition_point(|t| t.clock == cut) {
+            n if start + n < end => start + n,
+            _ => timeline.partition_point(|t| t.clock < cut),
+        };
     }
+    timeline[start..end].to_vec()
+}
 
-    if s.len() > MAX_USERNAME_LEN {
-        return false;
+/// the oldest `fetch` of a sorted timeline whose clock is larger than
+/// `after_clock`. Like [page], tribs sharing the clock of the newest
+/// trib are left to the next call or all taken.
+fn since(timeline: Vec<Arc<Trib>>, after_clock: u64, fetch: usize) -> Vec<Arc<Trib>> {
+    let start = timeline.partition_point(|t| t.clock <= after_clock);
+    let mut end = start.saturating_add(fetch).min(timeline.len());
+    if start < end && end < timeline.len() && timeline[end - 1].clock == timeline[end].clock {
+        let cut = timeline[end - 1].clock;
+        end = match timeline[start..end].partition_point(|t| t.clock < cut) {
+            0 => timeline.partition_point(|t| t.clock <= cut),
+            n => start + n,
+        };
     }
+    timeline[start..end].to_vec()
+}
 
-    for r in s.chars() {
-        if !('a'..='z').contains(&r) && !('0'..='9').contains(&r) {
-            return false;
+/// Counts the user-perceived characters (extended grapheme clusters) of a
+/// text, which is what the length limits of tribs, messages and profiles
+/// count. An emoji made of several code points counts as one.
+///
+/// ```rust
+/// use tribbler::trib::text_len;
+/// assert_eq!(5, text_len("héllo"));
+/// assert_eq!(1, text_len("👍🏽"));
+/// ```
+pub fn text_len(s: &str) -> usize {
+    s.graphemes(true).count()
+}
+
+/// Returns the NFKC normal form of a username in lower case, so that names
+/// differing only in width, compatibility characters or case are the same.
+///
+/// ```rust
+/// use tribbler::trib::normalize_username;
+/// assert_eq!("alice", normalize_username("Ａｌｉｃｅ"));
+/// ```
+pub fn normalize_username(s: &str) -> String {
+    s.nfkc().flat_map(char::to_lowercase).nfkc().collect()
+}
+
+/// Returns the confusable skeleton of a username, as defined by Unicode
+/// Technical Standard #39. Names with the same skeleton look alike, like the
+/// Latin "ace" and the Cyrillic "асе".
+pub fn username_skeleton(s: &str) -> String {
+    skeleton(s).collect()
+}
+
+/// Checks if a username is a valid one under the default [TribLimits].
+/// Returns true if it is.
+pub fn is_valid_username(s: &str) -> bool {
+    TribLimits::default().is_valid_username(s)
+}
+
+/// Extracts the users mentioned in a message with `@username`. A mention must
+/// not directly follow a letter or digit, and must name a valid username.
+/// Each user is returned once, in order of first appearance.
+///
+/// ```rust
+/// use tribbler::trib::parse_mentions;
+/// assert_eq!(
+///     vec!["h8liu", "fenglu"],
+///     parse_mentions("@h8liu: hi @fenglu and @h8liu")
+/// );
+/// assert!(parse_mentions("mail a@b.com or @Bob").is_empty());
+/// ```
+pub fn parse_mentions(message: &str) -> Vec<String> {
+    TribLimits::default().parse_mentions(message)
+}
+
+/// Checks if a tag, without the leading `#`, is a valid one under the default
+/// [TribLimits]. Returns true if it is.
+pub fn is_valid_tag(s: &str) -> bool {
+    TribLimits::default().is_valid_tag(s)
+}
+
+/// Extracts the hashtags in a message, lowercased and without the `#`. Like
+/// a mention, a tag must not directly follow a letter or digit. It runs over
+/// the following letters, digits and `_`, and is skipped if the result is not
+/// a valid tag under the default [TribLimits]. Each tag is returned once, in
+/// order of first appearance.
+///
+/// ```rust
+/// use tribbler::trib::parse_tags;
+/// assert_eq!(vec!["rust", "cse223b"], parse_tags("#Rust in #cse223b, #rust!"));
+/// assert!(parse_tags("a#b # ##").is_empty());
+/// ```
+pub fn parse_tags(message: &str) -> Vec<String> {
+    TribLimits::default().parse_tags(message)
+}
+
+/// Splits a text into the words used for searching: the runs of letters and
+/// digits, lowercased. Each word is returned once, in order of first
+/// appearance.
+///
+/// ```rust
+/// use tribbler::trib::tokenize;
+/// assert_eq!(vec!["hello", "world", "über"], tokenize("Hello, #world! ÜBER hello"));
+/// ```
+pub fn tokenize(text: &str) -> Vec<String> {
+    let mut words: Vec<String> = vec![];
+    for word in text.split(|c: char| !c.is_alphanumeric()) {
+        let word = word.to_lowercase();
+        if !word.is_empty() && !words.contains(&word) {
+            words.push(word);
         }
     }
-    true
+    words
 }
 
 #[cfg(test)]
 mod test {
-    use crate::trib::is_valid_username;
+    use std::sync::Arc;
+
+    use async_trait::async_trait;
+
+    use crate::{
+        err::{TribResult, TribblerError},
+        ref_impl::RefServer,
+        trib::{
+            is_valid_username, normalize_username, page, parse_mentions, parse_tags, since,
+            text_len, tokenize, username_skeleton, Server, Trib, TribLimits, MAX_TRIB_FETCH,
+        },
+    };
+
+    /// a server implementing only the required methods, under the limits of
+    /// the [RefServer] it wraps
+    struct Basic(RefServer);
+
+    #[async_trait]
+    impl Server for Basic {
+        fn limits(&self) -> TribLimits {
+            self.0.limits()
+        }
+        async fn sign_up(&self, user: &str) -> TribResult<()> {
+            self.0.sign_up(user).await
+        }
+        async fn list_users(&self) -> TribResult<Vec<String>> {
+            self.0.list_users().await
+        }
+        async fn post(&self, who: &str, post: &str, clock: u64) -> TribResult<()> {
+            self.0.post(who, post, clock).await
+        }
+        async fn tribs(&self, user: &str) -> TribResult<Vec<Arc<Trib>>> {
+            self.0.tribs(user).await
+        }
+        async fn follow(&self, who: &str, whom: &str) -> TribResult<()> {
+            self.0.follow(who, whom).await
+        }
+        async fn unfollow(&self, who: &str, whom: &str) -> TribResult<()> {
+            self.0.unfollow(who, whom).await
+        }
+        async fn is_following(&self, who: &str, whom: &str) -> TribResult<bool> {
+            self.0.is_following(who, whom).await
+        }
+        async fn following(&self, who: &str) -> TribResult<Vec<String>> {
+            self.0.following(who).await
+        }
+        async fn home(&self, user: &str) -> TribResult<Vec<Arc<Trib>>> {
+            self.0.home(user).await
+        }
+    }
+
+    #[tokio::test]
+    async fn default_methods() -> TribResult<()> {
+        let server = Basic(RefServer::new());
+        server.sign_up("alice").await?;
+        server.sign_up("bob").await?;
+        server.follow("alice", "bob").await?;
+        for i in 0..5 {
+            server.post("bob", &format!("m{}", i), 0).await?;
+        }
+        let clocks: Vec<u64> = server
+            .home("alice")
+            .await?
+            .iter()
+            .map(|t| t.clock)
+            .collect();
+        let page = server.home_page("alice", clocks[3], 2).await?;
+        assert_eq!(
+            &clocks[1..3],
+            &page.iter().map(|t| t.clock).collect::<Vec<_>>()
+        );
+        assert_eq!(2, server.home_since("alice", clocks[2]).await?.len());
+        assert_eq!(5, server.tribs_page("bob", u64::MAX, 10).await?.len());
+        assert!(server.tribs_since("bob", clocks[4]).await?.is_empty());
+        assert_eq!(1, server.following_count("alice").await?);
+        let err = server.like("alice", 1).await.unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<TribblerError>(),
+            Some(TribblerError::NotSupported(_))
+        ));
+        assert_eq!("like is not supported", err.to_string());
+
+        let server = Basic(RefServer::with_limits(TribLimits {
+            max_trib_fetch: 3,
+            ..Default::default()
+        }));
+        server.sign_up("bob").await?;
+        for i in 0..5 {
+            server.post("bob", &format!("m{}", i), 0).await?;
+        }
+        assert_eq!(3, server.tribs_page("bob", u64::MAX, 10).await?.len());
+        assert_eq!(3, server.home_since("bob", 0).await?.len());
+        Ok(())
+    }
+
+    fn trib(clock: u64, time: u64, user: &str, message: &str) -> Trib {
+        Trib {
+            user: user.to_string(),
+            message: message.to_string(),
+            time,
+            clock,
+            id: 0,
+            edited: None,
+            reply_to: None,
+            retrib_of: None,
+            likes: 0,
+        }
+    }
+
+    #[test]
+    fn shared_clock_pages() {
+        let clocks = |tribs: Vec<Arc<Trib>>| tribs.iter().map(|t| t.clock).collect::<Vec<u64>>();
+        let timeline: Vec<Arc<Trib>> = [1, 2, 2, 2, 3]
+            .iter()
+            .zip(["a", "b", "c", "d", "e"])
+            .map(|(&c, u)| Arc::new(trib(c, 0, u, "m")))
+            .collect();
+        assert_eq!(vec![3], clocks(page(timeline.clone(), u64::MAX, 2, 100)));
+        assert_eq!(vec![2, 2, 2], clocks(page(timeline.clone(), 3, 2, 100)));
+        assert_eq!(vec![1], clocks(page(timeline.clone(), 2, 2, 100)));
+        assert_eq!(vec![1, 2, 2, 2], clocks(page(timeline.clone(), 3, 4, 100)));
+        assert_eq!(vec![2, 2, 2], clocks(page(timeline.clone(), 3, 4, 2)));
+
+        assert_eq!(vec![1], clocks(since(timeline.clone(), 0, 2)));
+        assert_eq!(vec![2, 2, 2], clocks(since(timeline.clone(), 1, 2)));
+        assert_eq!(vec![3], clocks(since(timeline.clone(), 2, 2)));
+        assert_eq!(vec![1, 2, 2, 2], clocks(since(timeline, 0, 4)));
+    }
+
+    #[test]
+    fn tribble_order() {
+        let mut tribs = [
+            trib(2, 0, "a", "a"),
+            trib(1, 9, "b", "b"),
+            trib(1, 9, "a", "b"),
+            trib(1, 9, "a", "a"),
+            trib(1, 3, "z", "z"),
+        ];
+        tribs.sort();
+        let order: Vec<(u64, u64, &str, &str)> = tribs
+            .iter()
+            .map(|t| (t.clock, t.time, t.user.as_str(), t.message.as_str()))
+            .collect();
+        assert_eq!(
+            vec![
+                (1, 3, "z", "z"),
+                (1, 9, "a", "a"),
+                (1, 9, "a", "b"),
+                (1, 9, "b", "b"),
+                (2, 0, "a", "a"),
+            ],
+            order
+        );
+        let mut liked = trib(1, 9, "a", "a");
+        liked.likes = 3;
+        assert_ne!(tribs[1], liked);
+        assert!(tribs[1] < liked && liked < tribs[2]);
+        assert_eq!(tribs[1], trib(1, 9, "a", "a"));
+    }
 
     #[test]
     fn valid_usernames() {
-        assert_eq!(true, is_valid_username("h8liu"));
-        assert_eq!(true, is_valid_username("rkapoor"));
-        assert_eq!(true, is_valid_username("fenglu"));
+        assert!(is_valid_username("h8liu"));
+        assert!(is_valid_username("rkapoor"));
+        assert!(is_valid_username("fenglu"));
+    }
+
+    #[test]
+    fn limits() {
+        let limits: TribLimits =
+            serde_json::from_str(r#"{"max_username_len": 20, "max_trib_len": 280}"#).unwrap();
+        assert_eq!(280, limits.max_trib_len);
+        assert_eq!(MAX_TRIB_FETCH, limits.max_trib_fetch);
+        assert!(!is_valid_username("waytoolongusername1"));
+        assert!(limits.is_valid_username("waytoolongusername1"));
+        assert!(!limits.is_valid_username("Alice"));
+    }
+
+    #[test]
+    fn multibyte_text_len() {
+        assert_eq!(0, text_len(""));
+        assert_eq!(3, text_len("日本語"));
+        // e and a combining acute accent
+        assert_eq!(1, text_len("e\u{301}"));
+        // a family joined with zero width joiners
+        assert_eq!(1, text_len("👨‍👩‍👧"));
+        assert_eq!(140, text_len(&"🇫🇷".repeat(140)));
+    }
+
+    #[test]
+    fn unicode_usernames() {
+        let limits = TribLimits {
+            unicode_usernames: true,
+            ..Default::default()
+        };
+        assert!(!is_valid_username("zoë"));
+        assert!(limits.is_valid_username("zoë"));
+        assert!(limits.is_valid_username("h8liu"));
+        assert!(limits.is_valid_username("δημήτρης"));
+        assert!(limits.is_valid_username("山田"));
+        assert!(limits.is_valid_username(&"é".repeat(15)));
+        assert!(!limits.is_valid_username(&"é".repeat(16)));
+        // decomposed, full width or upper case names are not normalized
+        assert!(!limits.is_valid_username("zoe\u{308}"));
+        assert!(!limits.is_valid_username("ａｌｉｃｅ"));
+        assert!(!limits.is_valid_username("Zoë"));
+        // mixing the Latin "a" and the Cyrillic "а"
+        assert!(!limits.is_valid_username("pа\u{443}pal"));
+        assert!(!limits.is_valid_username("a b"));
+        assert!(!limits.is_valid_username("👍"));
+        assert!(!limits.is_valid_username("a\u{200d}b"));
+        assert_eq!("zoë", normalize_username("ZOE\u{308}"));
+        assert_eq!("fi", normalize_username("ﬁ"));
+        assert_eq!(username_skeleton("ace"), username_skeleton("асе"));
+        assert_ne!(username_skeleton("ace"), username_skeleton("acme"));
+    }
+
+    #[test]
+    fn mentions() {
+        assert_eq!(vec!["rkapoor"], parse_mentions("RT @rkapoor: hello"));
+        assert_eq!(vec!["a", "b1"], parse_mentions("@a,@b1 @a"));
+        assert!(parse_mentions("@ @@ x@y").is_empty());
+        assert!(parse_mentions("@waytoolongusername1").is_empty());
+        assert_eq!(vec!["fenglu"], parse_mentions("héllo@x @fenglu!"));
+        assert!(parse_mentions("@zoë @山田").is_empty());
+        let limits = TribLimits {
+            unicode_usernames: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            vec!["zoë", "山田"],
+            limits.parse_mentions("@zoë, @山田 @Zoë")
+        );
+        assert!(limits.parse_mentions("山田@zoë").is_empty());
+    }
+
+    #[test]
+    fn tags() {
+        assert_eq!(vec!["lab_3", "go"], parse_tags("#LAB_3 done (#go #lab_3)"));
+        assert_eq!(vec!["x"], parse_tags("é#y #x"));
+        assert!(parse_tags(&format!("#{}", "a".repeat(33))).is_empty());
+    }
+
+    #[test]
+    fn tokens() {
+        assert_eq!(vec!["rt", "h8liu", "hi"], tokenize("RT @h8liu: hi"));
+        assert_eq!(vec!["a", "b", "c", "42"], tokenize("a b_c A 42"));
+        assert!(tokenize(" ,.!? ").is_empty());
     }
 }
@@ -1,6 +1,6 @@
 // Generated by CoffeeScript 2.6.1
 (function() {
-  var _postRetrib, _postTrib, _showHome, _showUser, _updateFollow, _updateFollowing, addUser, appendError, countPostLength, follow, hoveringFollow, lclock, listTribs, listUsers, main, me, postDone, postTrib, seenClock, showHome, showUser, showing, signIn, signOut, unfollow, updateFollow, updateFollowing, updateUsers;
+  var _deleteTrib, _editTrib, _followDone, _likeTrib, _postTrib, _profileDone, _restoreSession, _retribTrib, _showHome, _showTag, _showUser, _signIn, _signedIn, _updateCounts, _updateFollow, _updateFollowers, _updateFollowing, _updateProfile, _updateSuggest, _updateTrending, addUser, appendError, countPostLength, editProfile, failedRequest, follow, hoveringFollow, lclock, listTribs, listUsers, listenLive, live, logIn, main, me, postDone, postTrib, refreshHome, restoreSession, searchTag, seenClock, showHome, showTag, showUser, showing, signIn, signOut, stopLive, textLen, unfollow, updateCounts, updateFollow, updateFollowing, updateProfile, updateSuggest, updateTrending, updateUsers;
 
   me = "";
 
@@ -8,6 +8,8 @@
 
   lclock = 0;
 
+  live = null;
+
   seenClock = function(c) {
     if (c > lclock) {
       lclock = c;
@@ -16,7 +18,7 @@
   };
 
   listTribs = function(data) {
-    var i, len1, li, ref, ret, retrib, trib, tribs, ul;
+    var i, len1, li, like, ref, ret, retrib, trib, tribs, ul;
     ret = JSON.parse(data);
     if (ret.err !== "") {
       appendError(ret.err);
@@ -38,6 +40,15 @@
       li.append('<span class="author"><a class="author" href="#">@' + trib.user + '</a></span> ');
       li.append('<span class="time">' + trib.time + '</span> ');
       li.append($('<span class="trib" />').text(trib.message));
+      if (trib.edited != null) {
+        li.find("span.time").append(' (edited)');
+      }
+      if (trib.retrib_of != null) {
+        li.find("span.time").append(' (retrib)');
+      }
+      if (trib.likes > 0) {
+        li.find("span.time").append(' ' + trib.likes + ' likes');
+      }
       li.find("a.author").click(function(ev) {
         var name;
         ev.preventDefault();
@@ -50,20 +61,52 @@
       li.append('<a href="#" class="retrib button">Retribble</a>');
       retrib = li.find("a.retrib");
       retrib.hide();
+      li.append('<a href="#" class="like button">Like</a>');
+      like = li.find("a.like");
+      like.hide();
+      if (trib.user === me) {
+        li.append('<a href="#" class="edit button">Edit</a>');
+        li.append('<a href="#" class="delete button">Delete</a>');
+        li.find("a.edit").click((function() {
+          var clock, msg;
+          clock = trib.clock;
+          msg = trib.message;
+          return function(ev) {
+            ev.preventDefault();
+            return _editTrib(clock, msg);
+          };
+        })());
+        li.find("a.delete").click((function() {
+          var clock;
+          clock = trib.clock;
+          return function(ev) {
+            ev.preventDefault();
+            return _deleteTrib(clock);
+          };
+        })());
+        li.find("a.edit, a.delete").hide();
+      }
       li.hover((function(ev) {
         if (me !== "") {
-          $(this).find("a.retrib").show();
+          $(this).find("a.button").show();
         }
       }), (function(ev) {
-        $(this).find("a.retrib").hide();
+        $(this).find("a.button").hide();
       }));
       retrib.click((function() {
-        var msg, who;
-        msg = trib.message;
-        who = trib.user;
+        var id;
+        id = trib.id;
+        return function(ev) {
+          ev.preventDefault();
+          return _retribTrib(id);
+        };
+      })());
+      like.click((function() {
+        var id;
+        id = trib.id;
         return function(ev) {
           ev.preventDefault();
-          return _postRetrib('RT @' + who + ': ' + msg, who);
+          return _likeTrib(id);
         };
       })());
       ul.append(li);
@@ -93,6 +136,38 @@
     $("h2#title").html("Home of " + me);
   };
 
+  refreshHome = function() {
+    if (showing !== "!home") {
+      return;
+    }
+    $.ajax({
+      url: "api/list-home",
+      type: "POST",
+      data: me,
+      success: listTribs,
+      cache: false
+    });
+  };
+
+  listenLive = function() {
+    stopLive();
+    live = new EventSource("api/live/" + encodeURIComponent(me));
+    live.onmessage = function(ev) {
+      var trib;
+      trib = JSON.parse(ev.data);
+      seenClock(trib.clock);
+      refreshHome();
+    };
+    live.addEventListener("lagged", refreshHome);
+  };
+
+  stopLive = function() {
+    if (live !== null) {
+      live.close();
+      live = null;
+    }
+  };
+
   showUser = function(ev) {
     var name;
     ev.preventDefault();
@@ -116,6 +191,43 @@
     $("div#whom").show();
     $("a#follow").show();
     updateFollow();
+    updateCounts(name);
+    updateProfile(name);
+  };
+
+  searchTag = function() {
+    var tag;
+    tag = $("form#searchtag input#tag").val();
+    if (tag === "") {
+      return false;
+    }
+    $("form#searchtag input#tag").val("");
+    _showTag(tag);
+    return false;
+  };
+
+  showTag = function(ev) {
+    ev.preventDefault();
+    _showTag($(this).text());
+  };
+
+  _showTag = function(tag) {
+    if (tag.indexOf('#') === 0) {
+      tag = tag.substring(1);
+    }
+    $.ajax({
+      url: "api/tag/search",
+      type: "POST",
+      data: tag,
+      success: listTribs,
+      cache: false
+    });
+    showing = "#" + tag;
+    $("h2#title").text(showing);
+    $("div#tribs").empty();
+    $("div#timeline").show();
+    $("div#whom").hide();
+    $("a#follow").hide();
   };
 
   updateUsers = function(data) {
@@ -142,23 +254,82 @@
   };
 
   addUser = function() {
-    var name;
+    var name, password;
     name = $("form#adduser input#username").val();
+    password = $("form#adduser input#newpassword").val();
     if (name === "") {
       return false;
     }
     $("form#adduser input#username").val("");
+    $("form#adduser input#newpassword").val("");
     console.log("add user", name);
     $.ajax({
-      url: "api/add-user",
+      url: "api/signup",
       type: "POST",
-      data: name,
-      success: updateUsers,
+      data: JSON.stringify({
+        user: name,
+        password: password
+      }),
+      success: _signedIn(name),
       cache: false
     });
     return false;
   };
 
+  logIn = function() {
+    var name, password;
+    name = $("form#signinas input#signinuser").val();
+    password = $("form#signinas input#signinpassword").val();
+    if (name === "") {
+      return false;
+    }
+    $("form#signinas input#signinpassword").val("");
+    $.ajax({
+      url: "api/login",
+      type: "POST",
+      data: JSON.stringify({
+        user: name,
+        password: password
+      }),
+      success: _signedIn(name),
+      cache: false
+    });
+    return false;
+  };
+
+  _signedIn = function(name) {
+    return function(data) {
+      var ret;
+      ret = JSON.parse(data);
+      if (ret.err !== "") {
+        appendError(ret.err);
+        return;
+      }
+      listUsers();
+      _signIn(name);
+    };
+  };
+
+  restoreSession = function() {
+    $.ajax({
+      url: "api/whoami",
+      success: _restoreSession,
+      cache: false
+    });
+  };
+
+  _restoreSession = function(data) {
+    var ret;
+    ret = JSON.parse(data);
+    if (ret.err !== "") {
+      appendError(ret.err);
+      return;
+    }
+    if (ret.user !== "") {
+      _signIn(ret.user);
+    }
+  };
+
   listUsers = function() {
     $.ajax({
       url: "api/list-users",
@@ -172,30 +343,55 @@
     return $("div#errors").append('<div class="error">Error: ' + e + '</div>');
   };
 
+  // a refused request answers with the error in a Bool, or in plain text
+  failedRequest = function(ev, xhr) {
+    var ref;
+    try {
+      appendError((ref = JSON.parse(xhr.responseText).err) != null ? ref : xhr.responseText);
+    } catch (error) {
+      appendError(xhr.responseText);
+    }
+  };
+
   signIn = function(ev) {
     ev.preventDefault();
-    if (showing === "" || showing === "!home") {
+    if (showing === "" || showing === "!home" || showing.indexOf('#') === 0) {
       return;
     }
-    console.log("sign in as: " + showing);
-    me = showing;
+    $("form#signinas input#signinuser").val(showing);
+    $("form#signinas input#signinpassword").focus();
+  };
+
+  _signIn = function(name) {
+    console.log("sign in as: " + name);
+    me = name;
     $("div#who").show();
     $("div#who h3").html("Signed in as " + me);
     $("div#compose").show();
     $("div#following").show();
+    $("div#suggest").show();
     _showHome();
     updateFollow();
+    listenLive();
     $("div#followings").empty();
     updateFollowing();
+    updateSuggest();
   };
 
   signOut = function(ev) {
     console.log("sign out");
     ev.preventDefault();
+    stopLive();
+    $.ajax({
+      url: "api/logout",
+      type: "POST",
+      cache: false
+    });
     me = "";
     $("div#who").hide();
     $("div#compose").hide();
     $("div#following").hide();
+    $("div#suggest").hide();
     $("a#follow").hide();
     if (showing === "!home") {
       $("div#timeline").hide();
@@ -235,6 +431,185 @@
     $("div#followings li").click(showUser);
   };
 
+  updateSuggest = function() {
+    $.ajax({
+      url: "api/suggest",
+      type: "POST",
+      data: JSON.stringify({
+        user: me,
+        n: 5
+      }),
+      success: _updateSuggest,
+      cache: false
+    });
+  };
+
+  _updateSuggest = function(data) {
+    var div, i, len1, name, ref, ret, ul;
+    ret = JSON.parse(data);
+    if (ret.err !== "") {
+      appendError(ret.err);
+      return;
+    }
+    div = $("div#suggestions");
+    div.empty();
+    if (ret.users === null || ret.users.length === 0) {
+      div.append("No suggestions.");
+      return;
+    }
+    ul = $("<ul/>");
+    ref = ret.users;
+    for (i = 0, len1 = ref.length; i < len1; i++) {
+      name = ref[i];
+      ul.append('<li><a href="#">' + name + '</a></li>');
+    }
+    div.append(ul);
+    $("div#suggestions li").click(showUser);
+  };
+
+  updateCounts = function(name) {
+    $.ajax({
+      url: "api/follow-counts",
+      type: "POST",
+      data: name,
+      success: _updateCounts,
+      cache: false
+    });
+    $.ajax({
+      url: "api/followers",
+      type: "POST",
+      data: name,
+      success: _updateFollowers,
+      cache: false
+    });
+  };
+
+  _updateCounts = function(data) {
+    var ret;
+    ret = JSON.parse(data);
+    if (ret.err !== "") {
+      appendError(ret.err);
+      return;
+    }
+    $("span#nfollowers").text(ret.followers + " followers");
+    $("span#nfollowing").text(ret.following + " following");
+  };
+
+  _updateFollowers = function(data) {
+    var div, i, len1, name, ref, ret;
+    ret = JSON.parse(data);
+    if (ret.err !== "") {
+      appendError(ret.err);
+      return;
+    }
+    div = $("div#followers");
+    div.empty();
+    if (ret.users === null || ret.users.length === 0) {
+      return;
+    }
+    div.append("Followed by ");
+    ref = ret.users;
+    for (i = 0, len1 = ref.length; i < len1; i++) {
+      name = ref[i];
+      div.append('<a class="follower" href="#">' + name + '</a> ');
+    }
+    $("div#followers a.follower").click(showUser);
+  };
+
+  updateTrending = function() {
+    $.ajax({
+      url: "api/tag/trending",
+      success: _updateTrending,
+      cache: false
+    });
+  };
+
+  _updateTrending = function(data) {
+    var div, i, len1, ref, ret, t, ul;
+    ret = JSON.parse(data);
+    if (ret.err !== "") {
+      appendError(ret.err);
+      return;
+    }
+    div = $("div#trending");
+    div.empty();
+    if (ret.tags === null || ret.tags.length === 0) {
+      div.append("No trending tags.");
+      return;
+    }
+    ul = $("<ul/>");
+    ref = ret.tags;
+    for (i = 0, len1 = ref.length; i < len1; i++) {
+      t = ref[i];
+      ul.append('<li><a class="tag" href="#">#' + t.tag + '</a> (' + t.count + ')</li>');
+    }
+    div.append(ul);
+    $("div#trending a.tag").click(showTag);
+  };
+
+  updateProfile = function(name) {
+    $.ajax({
+      url: "api/profile",
+      type: "POST",
+      data: name,
+      success: _updateProfile,
+      cache: false
+    });
+  };
+
+  _updateProfile = function(data) {
+    var joined, p, ret;
+    ret = JSON.parse(data);
+    if (ret.err !== "") {
+      appendError(ret.err);
+      return;
+    }
+    p = ret.profile;
+    $("span#displayname").text(p.display_name);
+    $("div#bio").text(p.bio);
+    joined = new Date(p.join_time * 1000);
+    $("div#joined").text("Joined " + joined.toLocaleDateString());
+    if (p.user === me) {
+      $("a#editprofile").show();
+    } else {
+      $("a#editprofile").hide();
+    }
+  };
+
+  editProfile = function(ev) {
+    var bio, name;
+    ev.preventDefault();
+    name = prompt("Display name", $("span#displayname").text());
+    if (name === null) {
+      return;
+    }
+    bio = prompt("Bio", $("div#bio").text());
+    if (bio === null) {
+      return;
+    }
+    $.ajax({
+      url: "api/update-profile",
+      type: "POST",
+      data: JSON.stringify({
+        user: me,
+        display_name: name,
+        bio: bio
+      }),
+      success: _profileDone,
+      cache: false
+    });
+  };
+
+  _profileDone = function(data) {
+    var ret;
+    ret = JSON.parse(data);
+    if (ret.err !== "") {
+      appendError(ret.err);
+      return;
+    }
+    updateProfile(showing);
+  };
+
   hoveringFollow = false;
 
   _updateFollow = function(data) {
@@ -273,6 +648,12 @@
     updateFollowing();
   };
 
+  _followDone = function(data) {
+    _updateFollow(data);
+    updateCounts(showing);
+    updateSuggest();
+  };
+
   follow = function(ev) {
     ev.preventDefault();
     $.ajax({
@@ -282,7 +663,7 @@
         who: me,
         whom: showing
       }),
-      success: _updateFollow,
+      success: _followDone,
       cache: false
     });
   };
@@ -296,7 +677,7 @@
         who: me,
         whom: showing
       }),
-      success: _updateFollow,
+      success: _followDone,
       cache: false
     });
   };
@@ -335,10 +716,18 @@
     });
   };
 
+  // counts the user-perceived characters of a text, like the server does
+  textLen = function(text) {
+    if ((typeof Intl !== "undefined" && Intl !== null) && (Intl.Segmenter != null)) {
+      return Array.from(new Intl.Segmenter().segment(text)).length;
+    }
+    return Array.from(text).length;
+  };
+
   countPostLength = function() {
     var left, len, text;
     text = $("form#post textarea").val();
-    len = text.length;
+    len = textLen(text);
     left = 140 - len;
     $("span#nchar").text("" + left);
     if (left < 0) {
@@ -348,12 +737,6 @@
     }
   };
 
-  _postRetrib = function(msg, at) {
-    $("form#post textarea").val(msg);
-    countPostLength();
-    _postTrib();
-  };
-
   postTrib = function(ev) {
     ev.preventDefault();
     _postTrib();
@@ -362,7 +745,7 @@
   _postTrib = function() {
     var len, text;
     text = $("form#post textarea").val();
-    len = text.length;
+    len = textLen(text);
     if (len === 0) {
       appendError("empty tweet");
       return;
@@ -386,6 +769,75 @@
     });
   };
 
+  _editTrib = function(clock, msg) {
+    var text;
+    text = prompt("Edit tribble", msg);
+    if (text === null || text === msg) {
+      return;
+    }
+    if (text.length === 0) {
+      appendError("empty tweet");
+      return;
+    }
+    if (textLen(text) > 140) {
+      appendError("tweet too long");
+      return;
+    }
+    $.ajax({
+      url: "api/edit-trib",
+      type: "POST",
+      data: JSON.stringify({
+        who: me,
+        clock: clock,
+        message: text
+      }),
+      success: postDone,
+      cache: false
+    });
+  };
+
+  _retribTrib = function(id) {
+    $.ajax({
+      url: "api/retrib",
+      type: "POST",
+      data: JSON.stringify({
+        who: me,
+        id: id
+      }),
+      success: postDone,
+      cache: false
+    });
+  };
+
+  _likeTrib = function(id) {
+    $.ajax({
+      url: "api/like",
+      type: "POST",
+      data: JSON.stringify({
+        who: me,
+        id: id
+      }),
+      success: postDone,
+      cache: false
+    });
+  };
+
+  _deleteTrib = function(clock) {
+    if (!confirm("Delete this tribble?")) {
+      return;
+    }
+    $.ajax({
+      url: "api/delete-trib",
+      type: "POST",
+      data: JSON.stringify({
+        who: me,
+        clock: clock
+      }),
+      success: postDone,
+      cache: false
+    });
+  };
+
   postDone = function(data) {
     var ret;
     ret = JSON.parse(data);
@@ -393,10 +845,13 @@
       appendError(ret.err);
       return;
     }
+    updateTrending();
     if (showing === "") {
       return;
     } else if (showing === "!home") {
       _showHome();
+    } else if (showing.indexOf('#') === 0) {
+      _showTag(showing);
     } else {
       _showUser(showing);
     }
@@ -404,12 +859,16 @@
 
   main = function() {
     $("form#adduser").submit(addUser);
+    $("form#signinas").submit(logIn);
     $("form#post").submit(postTrib);
+    $("form#searchtag").submit(searchTag);
     $("div#errors").hide();
     $("div#timeline").hide();
+    $(document).ajaxError(failedRequest);
     $("a#signin").click(signIn);
     $("a#home").click(showHome);
     $("a#signout").click(signOut);
+    $("a#editprofile").click(editProfile);
     $("form#post textarea").keydown(function() {
       return setTimeout((function() {
         return countPostLength();
@@ -423,6 +882,8 @@
     $("form#post textarea").keyup(countPostLength);
     $("form#post textarea").change(countPostLength);
     listUsers();
+    updateTrending();
+    restoreSession();
   };
 
   $(document).ready(main);